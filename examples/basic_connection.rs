@@ -22,7 +22,7 @@ fn main() {
     println!("  use sphero_rvr::SpheroRvr;");
     println!();
     println!("  fn main() -> Result<(), Box<dyn std::error::Error>> {{");
-    println!("      let mut rvr = SpheroRvr::connect(\"/dev/serial0\")?;");
+    println!("      let rvr = SpheroRvr::connect(\"/dev/serial0\")?;");
     println!("      rvr.wake()?;");
     println!("      rvr.set_all_leds(0, 255, 0)?; // Green");
     println!("      rvr.sleep()?;");
@@ -57,7 +57,7 @@ fn main() -> Result<()> {
     tracing::info!("Attempting to connect to RVR on {}", port);
 
     // Open connection (synchronous)
-    let mut rvr = SpheroRvr::connect(port)?;
+    let rvr = SpheroRvr::connect(port)?;
     tracing::info!("Successfully connected to RVR!");
 
     // Wake the robot
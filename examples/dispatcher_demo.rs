@@ -6,8 +6,10 @@
 //
 // Note: This requires a real Sphero RVR connected to /dev/serial0
 
+use sphero_rvr::api::command;
+use sphero_rvr::api::types::Color;
+use sphero_rvr::api::LedMask;
 use sphero_rvr::error::Result;
-use sphero_rvr::protocol::packet::{Packet, PacketFlags};
 use sphero_rvr::transport::Dispatcher;
 use std::thread;
 use std::time::Duration;
@@ -30,23 +32,9 @@ fn main() -> Result<()> {
     println!("Example 1: Wake Command");
     println!("-----------------------");
 
-    let wake_packet = Packet {
-        flags: PacketFlags {
-            is_response: false,
-            requests_response: true,
-            requests_only_error_response: false,
-            is_activity: false,
-            has_target_id: true,  // Required for UART routing
-            has_source_id: true,  // Required for UART routing
-            reserved: 0,
-        },
-        target_id: Some(0x01),    // Primary processor (Nordic MCU)
-        source_id: Some(0x02),    // UART expansion port
-        device_id: 0x13,          // Power device
-        command_id: 0x0D,         // Wake command
-        sequence_number: 0,       // Will be assigned by dispatcher
-        payload: vec![],
-    };
+    // Builders fill in the UART routing header (target: primary processor,
+    // source: UART port); the dispatcher assigns the sequence number
+    let wake_packet = command::wake();
 
     match dispatcher.send_command(wake_packet) {
         Ok(response) => {
@@ -65,28 +53,7 @@ fn main() -> Result<()> {
     println!("Example 2: Set LEDs to Green");
     println!("----------------------------");
 
-    let led_packet = Packet {
-        flags: PacketFlags {
-            is_response: false,
-            requests_response: true,
-            requests_only_error_response: false,
-            is_activity: false,
-            has_target_id: true,  // Required for UART routing
-            has_source_id: true,  // Required for UART routing
-            reserved: 0,
-        },
-        target_id: Some(0x01),    // Primary processor (Nordic MCU)
-        source_id: Some(0x02),    // UART expansion port
-        device_id: 0x1A,          // IO device
-        command_id: 0x1A,         // Set all LEDs command
-        sequence_number: 0,       // Will be assigned
-        payload: vec![
-            0x3F, // LED bitmask (all LEDs)
-            0x00, // Red: 0
-            0xFF, // Green: 255
-            0x00, // Blue: 0
-        ],
-    };
+    let led_packet = command::set_leds(LedMask::ALL, Color::GREEN);
 
     match dispatcher.send_command(led_packet) {
         Ok(response) => {
@@ -111,12 +78,7 @@ fn main() -> Result<()> {
             let start = std::time::Instant::now();
             while start.elapsed() < Duration::from_secs(5) {
                 if let Ok(packet) = rx.recv_timeout(Duration::from_millis(100)) {
-                    println!(
-                        "  Notification: dev={:#04x} cmd={:#04x} payload_len={}",
-                        packet.device_id,
-                        packet.command_id,
-                        packet.payload.len()
-                    );
+                    println!("  Notification: {}", packet);
                 }
             }
         });
@@ -130,23 +92,7 @@ fn main() -> Result<()> {
     println!("Example 4: Sleep Command");
     println!("------------------------");
 
-    let sleep_packet = Packet {
-        flags: PacketFlags {
-            is_response: false,
-            requests_response: true,
-            requests_only_error_response: false,
-            is_activity: false,
-            has_target_id: true,  // Required for UART routing
-            has_source_id: true,  // Required for UART routing
-            reserved: 0,
-        },
-        target_id: Some(0x01),    // Primary processor (Nordic MCU)
-        source_id: Some(0x02),    // UART expansion port
-        device_id: 0x13,          // Power device
-        command_id: 0x01,         // Sleep command
-        sequence_number: 0,
-        payload: vec![],
-    };
+    let sleep_packet = command::sleep();
 
     match dispatcher.send_command(sleep_packet) {
         Ok(response) => {
@@ -18,6 +18,7 @@
 //! Note: Requires a Sphero RVR connected to /dev/serial0
 
 use sphero_rvr::api::types::Color;
+use sphero_rvr::transport::RecvTimeoutError;
 use sphero_rvr::SpheroRvr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -34,7 +35,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Connect to robot
     println!("Connecting to /dev/serial0...");
-    let mut rvr = SpheroRvr::connect("/dev/serial0")?;
+    let rvr = SpheroRvr::connect("/dev/serial0")?;
     println!("✓ Connected!\n");
 
     // Spawn notification handler thread
@@ -50,33 +51,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 match rx.recv_timeout(Duration::from_millis(100)) {
                     Ok(packet) => {
                         notification_count += 1;
-                        println!(
-                            "  📨 Notification #{}: device={:#04x} cmd={:#04x} payload_len={}",
-                            notification_count,
-                            packet.device_id,
-                            packet.command_id,
-                            packet.payload.len()
-                        );
-
-                        // Show first few bytes of payload if present
-                        if !packet.payload.is_empty() {
-                            let preview: Vec<String> = packet
-                                .payload
-                                .iter()
-                                .take(8)
-                                .map(|b| format!("{:02x}", b))
-                                .collect();
-                            println!(
-                                "     Data: [{}{}]",
-                                preview.join(" "),
-                                if packet.payload.len() > 8 { " ..." } else { "" }
-                            );
-                        }
+                        println!("  📨 Notification #{}: {}", notification_count, packet);
                     }
-                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                    Err(RecvTimeoutError::Timeout) => {
                         // Normal timeout, continue
                     }
-                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                    Err(RecvTimeoutError::Disconnected) => {
                         println!("  📡 Notification channel closed");
                         break;
                     }
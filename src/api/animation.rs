@@ -0,0 +1,207 @@
+//! LED animations played on a background thread
+//!
+//! An `Animation` maps time since it started to the color every LED
+//! shows. `SpheroRvr::play_animation` samples it every
+//! `ANIMATION_FRAME_INTERVAL` and sends each frame as a no-ack LED write
+//! (see `SpheroRvr::set_all_leds_no_ack`), so a lost frame is simply
+//! replaced by the next one.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sphero_rvr::api::animation::Animation;
+//! use sphero_rvr::api::types::Color;
+//! use sphero_rvr::SpheroRvr;
+//! use std::time::Duration;
+//!
+//! let rvr = SpheroRvr::connect("/dev/serial0")?;
+//! rvr.wake()?;
+//! rvr.play_animation(Animation::rainbow(Duration::from_secs(5)))?;
+//! std::thread::sleep(Duration::from_secs(5));
+//! rvr.play_animation(Animation::blink(Color::RED, Duration::from_millis(500)))?;
+//! std::thread::sleep(Duration::from_secs(3));
+//! rvr.stop_animation()?;
+//! # Ok::<(), sphero_rvr::error::RvrError>(())
+//! ```
+
+use crate::api::types::Color;
+use crate::api::worker::Worker;
+use crate::error::Result;
+use std::time::{Duration, Instant};
+
+/// Time between animation frames (about 30 fps)
+pub const ANIMATION_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// A color sequence shown on all LEDs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Animation {
+    /// Blend from `from` to `to` over `duration`, then hold `to`
+    Fade {
+        from: Color,
+        to: Color,
+        duration: Duration,
+    },
+    /// Alternate `color` (first half of each `period`) and black, until stopped
+    Blink { color: Color, period: Duration },
+    /// Sweep once through the hues over `duration`
+    Rainbow { duration: Duration },
+}
+
+impl Animation {
+    /// Blend from one color to another
+    pub fn fade(from: Color, to: Color, duration: Duration) -> Self {
+        Self::Fade { from, to, duration }
+    }
+
+    /// Flash a color on and off
+    pub fn blink(color: Color, period: Duration) -> Self {
+        Self::Blink { color, period }
+    }
+
+    /// Cycle through the rainbow once
+    pub fn rainbow(duration: Duration) -> Self {
+        Self::Rainbow { duration }
+    }
+
+    /// How long the animation runs, or `None` if it runs until stopped
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            Self::Fade { duration, .. } | Self::Rainbow { duration } => Some(*duration),
+            Self::Blink { .. } => None,
+        }
+    }
+
+    /// Color shown `elapsed` after the animation started
+    pub fn color_at(&self, elapsed: Duration) -> Color {
+        match *self {
+            Self::Fade { from, to, duration } => from.lerp(to, progress(elapsed, duration)),
+            Self::Blink { color, period } => {
+                if period.is_zero()
+                    || elapsed.as_nanos() % period.as_nanos() < period.as_nanos() / 2
+                {
+                    color
+                } else {
+                    Color::BLACK
+                }
+            }
+            Self::Rainbow { duration } => {
+                Color::from_hsv(360.0 * progress(elapsed, duration), 1.0, 1.0)
+            }
+        }
+    }
+}
+
+/// Fraction of `duration` covered by `elapsed`, 0.0 to 1.0
+fn progress(elapsed: Duration, duration: Duration) -> f32 {
+    if duration.is_zero() {
+        return 1.0;
+    }
+    (elapsed.as_secs_f32() / duration.as_secs_f32()).min(1.0)
+}
+
+/// Pass each frame of `animation` to `show` on a background thread until it
+/// ends or is stopped
+///
+/// The last frame of a finite animation is always shown, so a fade ends on
+/// exactly its target color.
+pub(crate) fn play(
+    animation: Animation,
+    mut show: impl FnMut(Color) -> Result<()> + Send + 'static,
+) -> Worker {
+    Worker::spawn("animation", move |stop| {
+        let started = Instant::now();
+        loop {
+            let elapsed = started.elapsed();
+            let finished = animation.duration().filter(|&end| elapsed >= end);
+            if let Err(e) = show(animation.color_at(finished.unwrap_or(elapsed))) {
+                tracing::warn!("Animation frame failed: {}", e);
+            }
+            if finished.is_some() || !stop.wait(ANIMATION_FRAME_INTERVAL) {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[test]
+    fn test_fade_colors() {
+        let fade = Animation::fade(Color::BLACK, Color::WHITE, Duration::from_secs(2));
+        assert_eq!(fade.duration(), Some(Duration::from_secs(2)));
+        assert_eq!(fade.color_at(Duration::ZERO), Color::BLACK);
+        assert_eq!(
+            fade.color_at(Duration::from_secs(1)),
+            Color::new(128, 128, 128)
+        );
+        assert_eq!(fade.color_at(Duration::from_secs(5)), Color::WHITE);
+
+        // A zero-length fade jumps straight to the target
+        let instant = Animation::fade(Color::BLACK, Color::RED, Duration::ZERO);
+        assert_eq!(instant.color_at(Duration::ZERO), Color::RED);
+    }
+
+    #[test]
+    fn test_blink_colors() {
+        let blink = Animation::blink(Color::RED, Duration::from_millis(100));
+        assert_eq!(blink.duration(), None);
+        assert_eq!(blink.color_at(Duration::from_millis(10)), Color::RED);
+        assert_eq!(blink.color_at(Duration::from_millis(60)), Color::BLACK);
+        assert_eq!(blink.color_at(Duration::from_millis(110)), Color::RED);
+        assert_eq!(
+            Animation::blink(Color::RED, Duration::ZERO).color_at(Duration::from_secs(1)),
+            Color::RED
+        );
+    }
+
+    #[test]
+    fn test_rainbow_colors() {
+        let rainbow = Animation::rainbow(Duration::from_secs(3));
+        assert_eq!(rainbow.color_at(Duration::ZERO), Color::RED);
+        assert_eq!(rainbow.color_at(Duration::from_secs(1)), Color::GREEN);
+        assert_eq!(rainbow.color_at(Duration::from_secs(2)), Color::BLUE);
+    }
+
+    #[test]
+    fn test_player_ends_on_last_frame() {
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&frames);
+        let fade = Animation::fade(Color::BLACK, Color::BLUE, Duration::from_millis(100));
+
+        let player = play(fade, move |color| {
+            sink.lock().unwrap().push(color);
+            Ok(())
+        });
+        // Finite animations end on their own; joining must not hang
+        thread::sleep(Duration::from_millis(200));
+        player.stop().unwrap();
+
+        let frames = frames.lock().unwrap();
+        assert!(frames.len() >= 2, "{:?}", frames);
+        assert_eq!(frames.first(), Some(&Color::BLACK));
+        assert_eq!(frames.last(), Some(&Color::BLUE));
+    }
+
+    #[test]
+    fn test_player_stops_endless_animation() {
+        let frames = Arc::new(Mutex::new(0));
+        let sink = Arc::clone(&frames);
+        let blink = Animation::blink(Color::RED, Duration::from_millis(50));
+
+        let player = play(blink, move |_| {
+            *sink.lock().unwrap() += 1;
+            Ok(())
+        });
+        thread::sleep(Duration::from_millis(100));
+        player.stop().unwrap();
+
+        let shown = *frames.lock().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(shown > 0);
+        assert_eq!(*frames.lock().unwrap(), shown);
+    }
+}
@@ -1,17 +1,102 @@
 //! High-level Sphero RVR client
 
+use crate::api::animation::{self, Animation};
+use crate::api::command::{self, Command, TimeoutPolicy};
 use crate::api::constants::*;
-use crate::api::types::{BatteryState, Color, FirmwareVersion};
+use crate::api::events::{pump_events, CompassCalibrationEvent, RvrEvent};
+use crate::api::led::{
+    gradient_updates, group_by_color, led_payload_for, run_batch, BatchResult, LedCapabilities,
+    LedMask, LedPosition, RvrLedIndex,
+};
+use crate::api::sensors::{SensorConfig, SensorReading, SensorType};
+use crate::api::types::{
+    BatteryState, BatteryVoltageState, Color, DeviceClock, DriveFlags, FirmwareVersion, Locator,
+    MotorMode, PowerStatus, ProtectionState, RobotState, RvrModel,
+};
+use crate::api::worker::Worker;
 use crate::error::{Result, RvrError};
 use crate::protocol::packet::{Packet, PacketFlags};
-use crate::transport::Dispatcher;
-use std::sync::mpsc::Receiver;
+use crate::transport::dispatcher::DEFAULT_COMMAND_TIMEOUT;
+use crate::transport::{
+    CommandRecorder, Dispatcher, ErrorReceiver, NotificationReceiver, SerialTransport,
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// API protocol major version this crate speaks
+const SUPPORTED_API_PROTOCOL_MAJOR: u8 = 2;
+
+/// Wheel velocity corresponding to the full 255 drive speed (cm/s)
+const MAX_WHEEL_VELOCITY_CM_S: f32 = 155.5;
+
+/// Nominal capacity of the RVR battery pack, used for runtime estimates
+const BATTERY_CAPACITY_MAH: f32 = 2500.0;
+
+/// Current below which the robot is treated as idle (no meaningful estimate)
+const MIN_ESTIMATE_CURRENT_A: f32 = 0.01;
+
+/// How long `drive_distance` waits for progress before treating the robot as stalled
+const DRIVE_STALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Distance the robot must gain for `drive_distance` to count it as progress
+const MIN_DRIVE_PROGRESS_CM: f32 = 0.5;
+
+/// Extra attempts `wake` makes when the robot is slow or busy at boot
+const WAKE_RETRIES: u32 = 3;
+
+/// How long `sleep` waits for an ack it may never get
+const SLEEP_ACK_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Suggested `start_keep_awake` interval, well inside the ~5 minute sleep timeout
+pub const DEFAULT_KEEP_AWAKE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Highest IR message code
+const MAX_IR_CODE: u8 = 7;
+
+/// Highest IR emitter strength
+const MAX_IR_STRENGTH: u8 = 64;
+
+/// How often color detection notifications are sent once enabled
+pub const COLOR_DETECTION_INTERVAL_MS: u16 = 100;
+
+/// Shortest streaming interval the RVR keeps up with (about 30 Hz)
+pub const MIN_STREAMING_INTERVAL_MS: u16 = 33;
+
+/// Connection settings for `SpheroRvr::connect_with_config`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RvrConfig {
+    /// UART baud rate (the RVR uses 115200)
+    pub baud_rate: u32,
+    /// How long to wait for each command's response
+    pub command_timeout: std::time::Duration,
+    /// Routing node every command is addressed to
+    /// (`routing_node::PRIMARY_PROCESSOR` on a stock RVR)
+    pub target_id: u8,
+    /// Routing node commands are sent from
+    /// (`routing_node::UART_PORT` on a stock RVR)
+    pub source_id: u8,
+}
+
+impl Default for RvrConfig {
+    fn default() -> Self {
+        Self {
+            baud_rate: 115200,
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            target_id: routing_node::PRIMARY_PROCESSOR,
+            source_id: routing_node::UART_PORT,
+        }
+    }
+}
 
 /// High-level client for controlling Sphero RVR
 ///
 /// This is the main entry point for the Sphero RVR API. It provides
 /// strongly-typed, synchronous methods for controlling the robot.
 ///
+/// Commands take `&self`, and clones share one connection and one set of
+/// settings (speed limit, streaming configuration), so a control thread
+/// and a telemetry thread can each hold a clone and send concurrently.
+/// The dispatcher matches each response to its command by sequence number.
+///
 /// # Example
 ///
 /// ```no_run
@@ -20,7 +105,7 @@ use std::sync::mpsc::Receiver;
 ///
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// // Connect to the robot
-/// let mut rvr = SpheroRvr::connect("/dev/serial0")?;
+/// let rvr = SpheroRvr::connect("/dev/serial0")?;
 ///
 /// // Wake up
 /// rvr.wake()?;
@@ -33,8 +118,49 @@ use std::sync::mpsc::Receiver;
 /// # Ok(())
 /// # }
 /// ```
+#[derive(Clone)]
 pub struct SpheroRvr {
-    dispatcher: Dispatcher,
+    /// Shared with clones and the keep-awake thread
+    dispatcher: Arc<Dispatcher>,
+
+    /// Settings and cached robot state, shared with clones
+    state: Arc<Mutex<ClientState>>,
+
+    /// Background pinger from `start_keep_awake`
+    keep_awake: Arc<Mutex<Option<Worker>>>,
+
+    /// Background LED animation from `play_animation`
+    animation: Arc<Mutex<Option<Worker>>>,
+
+    /// Routing IDs stamped on every command (see `RvrConfig`)
+    target_id: u8,
+    source_id: u8,
+}
+
+/// Client-side state shared by every clone of a `SpheroRvr`
+///
+/// Locked only briefly, never across a command round trip.
+#[derive(Debug)]
+struct ClientState {
+    /// Maximum drive speed (0-255) applied to every drive command
+    max_speed: u8,
+
+    /// Device-to-host clock mapping from the last `get_device_timestamp`
+    device_clock: Option<DeviceClock>,
+
+    /// LED hardware, from the last `get_led_capabilities`
+    led_capabilities: LedCapabilities,
+
+    /// Whether the last wake/sleep command left the robot awake
+    ///
+    /// `None` until this client has sent either.
+    awake: Option<bool>,
+
+    /// Streaming period from the last `configure_sensor_streaming`
+    streaming_interval_ms: Option<u16>,
+
+    /// Streams set up by the last `configure_sensor_streaming`
+    streams: Vec<SensorConfig>,
 }
 
 impl SpheroRvr {
@@ -52,22 +178,100 @@ impl SpheroRvr {
     ///
     /// Returns an error if the serial port cannot be opened
     pub fn connect(port: &str) -> Result<Self> {
-        let dispatcher = Dispatcher::new(port, 115200)?;
-        Ok(Self { dispatcher })
+        Self::connect_with_config(port, RvrConfig::default())
+    }
+
+    /// List the serial ports present on this machine
+    ///
+    /// Useful for finding the right device name to pass to `connect`
+    /// (on a Raspberry Pi, usually `/dev/serial0`).
+    pub fn list_ports() -> Result<Vec<String>> {
+        Ok(serialport::available_ports()?
+            .into_iter()
+            .map(|port| port.port_name)
+            .collect())
+    }
+
+    /// Connect to a Sphero RVR with non-default connection settings
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sphero_rvr::api::RvrConfig;
+    /// use sphero_rvr::SpheroRvr;
+    /// use std::time::Duration;
+    ///
+    /// let config = RvrConfig {
+    ///     command_timeout: Duration::from_secs(5),
+    ///     ..RvrConfig::default()
+    /// };
+    /// let rvr = SpheroRvr::connect_with_config("/dev/serial0", config)?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn connect_with_config(port: &str, config: RvrConfig) -> Result<Self> {
+        let dispatcher =
+            Dispatcher::new(port, config.baud_rate)?.with_timeout(config.command_timeout);
+        Ok(Self::with_dispatcher(dispatcher).with_routing(config.target_id, config.source_id))
+    }
+
+    /// Connect over a serial port the caller has already opened
+    ///
+    /// See `Dispatcher::from_port`. With the `test-support` feature this
+    /// also accepts a `VirtualRvr`, to run code without a robot.
+    pub fn from_port(port: Box<dyn serialport::SerialPort>) -> Self {
+        Self::with_dispatcher(Dispatcher::from_port(port))
+    }
+
+    /// Connect over any byte transport
+    ///
+    /// See `Dispatcher::from_transport`.
+    pub fn from_transport(transport: impl SerialTransport + 'static) -> Self {
+        Self::with_dispatcher(Dispatcher::from_transport(transport))
+    }
+
+    /// Wrap an existing dispatcher with default client settings
+    fn with_dispatcher(dispatcher: Dispatcher) -> Self {
+        Self {
+            dispatcher: Arc::new(dispatcher),
+            state: Arc::new(Mutex::new(ClientState {
+                max_speed: u8::MAX,
+                device_clock: None,
+                led_capabilities: LedCapabilities::default(),
+                awake: None,
+                streaming_interval_ms: None,
+                streams: Vec::new(),
+            })),
+            keep_awake: Arc::new(Mutex::new(None)),
+            animation: Arc::new(Mutex::new(None)),
+            target_id: routing_node::PRIMARY_PROCESSOR,
+            source_id: routing_node::UART_PORT,
+        }
+    }
+
+    /// Address commands to `target_id` from `source_id` instead of the defaults
+    fn with_routing(mut self, target_id: u8, source_id: u8) -> Self {
+        self.target_id = target_id;
+        self.source_id = source_id;
+        self
     }
 
     /// Wake the robot from sleep mode
     ///
     /// The robot must be awake before other commands will work.
     /// This is typically the first command sent after connecting.
-    pub fn wake(&mut self) -> Result<()> {
+    ///
+    /// The robot can be slow to answer while it boots, so a wake that times
+    /// out or is answered BUSY is retried with backoff (see
+    /// `Dispatcher::send_command_with_retries`).
+    pub fn wake(&self) -> Result<()> {
         tracing::debug!("Sending wake command");
 
-        let packet = self.build_command(device::POWER, power_command::WAKE, vec![]);
-
-        let response = self.dispatcher.send_command(packet)?;
+        let response = self
+            .dispatcher
+            .send_command_with_retries(self.routed(Command::Wake.to_packet()), WAKE_RETRIES)?;
         self.check_response(&response)?;
 
+        self.state().awake = Some(true);
         tracing::debug!("Wake command successful");
         Ok(())
     }
@@ -75,18 +279,62 @@ impl SpheroRvr {
     /// Put the robot to sleep
     ///
     /// The robot will enter low-power sleep mode. Send wake() to resume.
-    pub fn sleep(&mut self) -> Result<()> {
+    ///
+    /// The robot can power down the UART before acking, so a sleep that
+    /// gets no answer within 500 ms counts as success (see
+    /// `Command::timeout_policy`). An error response is still reported.
+    pub fn sleep(&self) -> Result<()> {
         tracing::debug!("Sending sleep command");
 
-        let packet = self.build_command(device::POWER, power_command::SLEEP, vec![]);
-
-        let response = self.dispatcher.send_command(packet)?;
-        self.check_response(&response)?;
+        self.send_typed(&Command::Sleep, SLEEP_ACK_TIMEOUT)?;
 
+        self.state().awake = Some(false);
         tracing::debug!("Sleep command successful");
         Ok(())
     }
 
+    /// Wake the robot, run a closure, and put the robot back to sleep
+    ///
+    /// Sleep is sent on every exit path: when the closure succeeds, when it
+    /// returns an error, and (best-effort) when it panics. This prevents the
+    /// common mistake of leaving the robot awake and draining its battery.
+    ///
+    /// If the closure fails, its error is returned and any sleep error is only
+    /// logged. If the closure succeeds, a sleep error is returned instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::types::Color;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// let battery = rvr.with_awake(|rvr| {
+    ///     rvr.set_all_leds(Color::GREEN)?;
+    ///     rvr.get_battery_percentage()
+    /// })?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn with_awake<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        self.wake()?;
+
+        let mut guard = SleepGuard {
+            rvr: self,
+            armed: true,
+        };
+        let result = f(guard.rvr);
+        let sleep_result = guard.sleep_now();
+
+        match result {
+            Ok(value) => sleep_result.map(|_| value),
+            Err(e) => {
+                if let Err(sleep_err) = sleep_result {
+                    tracing::warn!("Failed to sleep after error: {}", sleep_err);
+                }
+                Err(e)
+            }
+        }
+    }
+
     /// Set all LEDs to the same color
     ///
     /// # Arguments
@@ -98,7 +346,7 @@ impl SpheroRvr {
     /// ```no_run
     /// # use sphero_rvr::SpheroRvr;
     /// # use sphero_rvr::api::types::Color;
-    /// # let mut rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
     /// // Set all LEDs to red
     /// rvr.set_all_leds(Color::RED)?;
     ///
@@ -106,7 +354,7 @@ impl SpheroRvr {
     /// rvr.set_all_leds(Color::new(128, 64, 255))?;
     /// # Ok::<(), sphero_rvr::error::RvrError>(())
     /// ```
-    pub fn set_all_leds(&mut self, color: Color) -> Result<()> {
+    pub fn set_all_leds(&self, color: Color) -> Result<()> {
         tracing::debug!(
             "Setting all LEDs to RGB({}, {}, {})",
             color.r,
@@ -114,14 +362,8 @@ impl SpheroRvr {
             color.b
         );
 
-        let payload = vec![
-            led_bitmask::ALL, // LED bitmask (all LEDs)
-            color.r,          // Red
-            color.g,          // Green
-            color.b,          // Blue
-        ];
-
-        let packet = self.build_command(device::IO, io_command::SET_ALL_LEDS, payload);
+        let mask = self.state().led_capabilities.all_mask();
+        let packet = self.routed(Command::SetLeds { mask, color }.to_packet());
 
         let response = self.dispatcher.send_command(packet)?;
         self.check_response(&response)?;
@@ -130,11 +372,25 @@ impl SpheroRvr {
         Ok(())
     }
 
+    /// Set all LEDs to one color without waiting for the robot to answer
+    ///
+    /// For continuous animations (fades at 30 fps and up): the packet asks
+    /// for no response, so there is no round trip to wait for and nothing
+    /// for the robot to answer BUSY to. The catch is that nothing is
+    /// reported back. If the robot rejects the command or the frame is
+    /// lost, the LEDs simply keep their old color. Only a failed write is
+    /// returned as an error. Use `set_all_leds` when the color must stick.
+    pub fn set_all_leds_no_ack(&self, color: Color) -> Result<()> {
+        let mask = self.state().led_capabilities.all_mask();
+        self.dispatcher
+            .send_packet_no_response(&self.routed(leds_no_ack_packet(mask, color)))
+    }
+
     /// Set specific LEDs to a color
     ///
     /// # Arguments
     ///
-    /// * `led_mask` - Bitmask of which LEDs to set (see `led_bitmask` constants)
+    /// * `led_mask` - Which LEDs to set
     /// * `color` - RGB color to set
     ///
     /// # Example
@@ -142,30 +398,29 @@ impl SpheroRvr {
     /// ```no_run
     /// # use sphero_rvr::SpheroRvr;
     /// # use sphero_rvr::api::types::Color;
-    /// # use sphero_rvr::api::constants::led_bitmask;
-    /// # let mut rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// # use sphero_rvr::api::LedMask;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
     /// // Set only headlights to blue
-    /// let headlights = led_bitmask::LEFT_HEADLIGHT | led_bitmask::RIGHT_HEADLIGHT;
+    /// let headlights = LedMask::LEFT_HEADLIGHT | LedMask::RIGHT_HEADLIGHT;
     /// rvr.set_leds(headlights, Color::BLUE)?;
     /// # Ok::<(), sphero_rvr::error::RvrError>(())
     /// ```
-    pub fn set_leds(&mut self, led_mask: u8, color: Color) -> Result<()> {
+    pub fn set_leds(&self, led_mask: LedMask, color: Color) -> Result<()> {
         tracing::debug!(
             "Setting LEDs (mask={:#04x}) to RGB({}, {}, {})",
-            led_mask,
+            led_mask.bits(),
             color.r,
             color.g,
             color.b
         );
 
-        let payload = vec![
-            led_mask, // LED bitmask
-            color.r,  // Red
-            color.g,  // Green
-            color.b,  // Blue
-        ];
-
-        let packet = self.build_command(device::IO, io_command::SET_ALL_LEDS, payload);
+        let packet = self.routed(
+            Command::SetLeds {
+                mask: led_mask,
+                color,
+            }
+            .to_packet(),
+        );
 
         let response = self.dispatcher.send_command(packet)?;
         self.check_response(&response)?;
@@ -173,213 +428,3251 @@ impl SpheroRvr {
         Ok(())
     }
 
-    /// Get the battery percentage
+    /// Read back the current color of a single LED
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// Battery state with percentage (0-100)
-    pub fn get_battery_percentage(&mut self) -> Result<BatteryState> {
-        tracing::debug!("Getting battery percentage");
+    /// * `led` - Which LED to read (see `RvrLedIndex` for the index mapping)
+    pub fn get_rgb_led(&self, led: RvrLedIndex) -> Result<Color> {
+        tracing::debug!("Getting color of LED {:?}", led);
 
-        let packet =
-            self.build_command(device::POWER, power_command::GET_BATTERY_PERCENTAGE, vec![]);
+        let packet = self.build_command(device::IO, io_command::GET_RGB_LED, vec![led.index()]);
 
         let response = self.dispatcher.send_command(packet)?;
         self.check_response(&response)?;
 
-        // Parse battery percentage from response payload
-        if response.payload.is_empty() {
-            return Err(RvrError::InvalidResponse(
-                "Battery response has no payload".to_string(),
-            ));
-        }
-
-        let percentage = response.payload[0];
-
-        tracing::debug!("Battery percentage: {}%", percentage);
-        Ok(BatteryState { percentage })
+        decode_color(response_data(&response))
     }
 
-    /// Reset the yaw angle to zero
+    /// Read back the current color of the LED selected by an `LedMask`
     ///
-    /// Useful for calibrating the robot's orientation
-    pub fn reset_yaw(&mut self) -> Result<()> {
-        tracing::debug!("Resetting yaw");
-
-        let packet = self.build_command(device::DRIVE, drive_command::RESET_YAW, vec![]);
-
-        let response = self.dispatcher.send_command(packet)?;
-        self.check_response(&response)?;
-
-        Ok(())
-    }
-
-    /// Stop all motors
+    /// Handy for checking the result of `set_leds` with the same mask.
     ///
     /// # Arguments
     ///
-    /// * `brake` - If true, brake motors. If false, coast to stop.
-    pub fn stop(&mut self, brake: bool) -> Result<()> {
-        tracing::debug!("Stopping motors (brake={})", brake);
-
-        let mode = if brake {
-            drive_mode::BRAKE
-        } else {
-            drive_mode::COAST
-        };
+    /// * `led` - Mask selecting exactly one LED, e.g. `LedMask::LEFT_STATUS`
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if the mask selects no LED or
+    /// more than one, since the response carries a single color.
+    pub fn get_led_color(&self, led: LedMask) -> Result<Color> {
+        let led = RvrLedIndex::from_mask(led.bits()).ok_or_else(|| {
+            RvrError::InvalidParameter(format!(
+                "LED mask {:#04x} must select exactly one LED",
+                led.bits()
+            ))
+        })?;
+        self.get_rgb_led(led)
+    }
 
-        let packet = self.build_command(device::DRIVE, drive_command::STOP, vec![mode]);
+    /// Query the robot's LED hardware
+    ///
+    /// The result is remembered and sizes the mask used by `set_all_leds`.
+    /// Firmware that answers with an unsupported-command error reports the
+    /// stock RVR layout of `RVR_LED_COUNT` (10) LEDs
+    /// (`LedCapabilities::default()`).
+    pub fn get_led_capabilities(&self) -> Result<LedCapabilities> {
+        tracing::debug!("Getting LED capabilities");
 
-        let response = self.dispatcher.send_command(packet)?;
-        self.check_response(&response)?;
+        let capabilities =
+            match self.optional_query(device::IO, io_command::GET_LED_CAPABILITIES)? {
+                Some(response) => LedCapabilities::from_bytes(response_data(&response))?,
+                None => LedCapabilities::default(),
+            };
 
-        Ok(())
+        tracing::debug!("LED capabilities: {:?}", capabilities);
+        self.state().led_capabilities = capabilities;
+        Ok(capabilities)
     }
 
-    /// Take ownership of the notification receiver
+    /// Set several LEDs individually, reporting each update's outcome
     ///
-    /// This allows you to receive async notifications like sensor data.
-    /// Can only be called once.
+    /// Each update is sent as a separate command. Unlike calling `set_leds`
+    /// in a loop, a failed update does not abort the batch; the returned
+    /// `BatchResult` lists which updates succeeded and which failed.
     ///
     /// # Example
     ///
     /// ```no_run
     /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::types::Color;
+    /// # use sphero_rvr::api::LedPosition;
     /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
-    /// if let Some(rx) = rvr.take_receiver() {
-    ///     std::thread::spawn(move || {
-    ///         for packet in rx {
-    ///             println!("Notification: {:?}", packet);
-    ///         }
-    ///     });
+    /// let result = rvr.set_leds_batch(&[
+    ///     (LedPosition::LeftHeadlight, Color::WHITE),
+    ///     (LedPosition::RightHeadlight, Color::WHITE),
+    ///     (LedPosition::BatteryDoorRear, Color::RED),
+    /// ]);
+    /// for (position, error) in &result.failed {
+    ///     eprintln!("{:?} failed: {}", position, error);
     /// }
     /// ```
-    pub fn take_receiver(&self) -> Option<Receiver<Packet>> {
-        self.dispatcher.take_receiver()
+    pub fn set_leds_batch(&self, updates: &[(LedPosition, Color)]) -> BatchResult {
+        run_batch(updates, |position, color| {
+            self.set_leds(position.into(), color)
+        })
     }
 
-    /// Shutdown the connection gracefully
+    /// Set several LEDs to their own colors
     ///
-    /// This will stop the background RX thread and close the serial port.
-    /// The robot will remain in its current state (awake/asleep).
-    pub fn shutdown(self) -> Result<()> {
-        tracing::debug!("Shutting down SpheroRvr");
-        self.dispatcher.shutdown()
+    /// The set-LEDs payload is `[MASK] [R] [G] [B]`: one color for every LED
+    /// in the mask (see the `led` module). LEDs sharing a color are
+    /// therefore merged into one command, so this sends one command per
+    /// distinct color rather than one per LED. If an LED is listed more
+    /// than once, its last color wins.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::types::Color;
+    /// # use sphero_rvr::api::LedPosition;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// // Two commands: white headlights, red rear light
+    /// rvr.set_individual_leds(&[
+    ///     (LedPosition::LeftHeadlight, Color::WHITE),
+    ///     (LedPosition::RightHeadlight, Color::WHITE),
+    ///     (LedPosition::BatteryDoorRear, Color::RED),
+    /// ])?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Stops at the first command that fails; colors already sent stay applied.
+    pub fn set_individual_leds(&self, colors: &[(LedPosition, Color)]) -> Result<()> {
+        for (mask, color) in group_by_color(colors) {
+            self.set_leds(mask, color)?;
+        }
+        Ok(())
     }
 
-    // === Helper Methods ===
-
-    /// Build a command packet with standard flags for UART board-to-board communication
+    /// Fade the LEDs from `start` at the front of the robot to `end` at the back
     ///
-    /// When communicating over the RVR's external UART expansion port, the internal
-    /// routing mesh requires explicit source and target node IDs:
-    /// - Target: Primary processor (Nordic MCU)
-    /// - Source: UART expansion port
+    /// The headlights take `start`, the rear battery door LEDs take `end`,
+    /// and the LEDs in between get evenly interpolated colors. LEDs in the
+    /// same row share a color, so this goes through `set_individual_leds`
+    /// and sends one command per row (four in all).
     ///
-    /// Without these, the internal router may drop packets or return routing errors.
-    fn build_command(&self, device_id: u8, command_id: u8, payload: Vec<u8>) -> Packet {
-        use routing_node::{PRIMARY_PROCESSOR, UART_PORT};
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::types::Color;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// rvr.set_led_gradient(Color::CYAN, Color::MAGENTA)?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Stops at the first command that fails; rows already sent stay applied.
+    pub fn set_led_gradient(&self, start: Color, end: Color) -> Result<()> {
+        self.set_individual_leds(&gradient_updates(start, end))
+    }
 
-        Packet {
-            flags: PacketFlags {
-                is_response: false,
-                requests_response: true,
-                requests_only_error_response: false,
-                is_activity: false,
-                has_target_id: true, // Required for UART routing
-                has_source_id: true, // Required for UART routing
-                reserved: 0,
-            },
-            target_id: Some(PRIMARY_PROCESSOR), // Target: Primary processor (Nordic MCU)
-            source_id: Some(UART_PORT),         // Source: UART expansion port
-            device_id,
-            command_id,
-            sequence_number: 0, // Will be assigned by dispatcher
-            payload,
+    /// Capture the LED colors and awake status so they can be restored
+    ///
+    /// Reads every settable LED back from the robot. Awake status is the
+    /// result of the last `wake` or `sleep` sent through this client, or
+    /// `None` if neither has been sent.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::types::Color;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// let saved = rvr.snapshot_state()?;
+    /// rvr.set_all_leds(Color::RED)?; // flash an alert
+    /// rvr.restore_state(&saved)?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn snapshot_state(&self) -> Result<RobotState> {
+        let mut leds = Vec::with_capacity(LedPosition::ALL.len());
+        for position in LedPosition::ALL {
+            leds.push((position, self.get_rgb_led(position.index())?));
         }
+
+        Ok(RobotState {
+            awake: self.state().awake,
+            leds,
+        })
     }
 
-    /// Check if a response indicates success or error
-    fn check_response(&self, response: &Packet) -> Result<()> {
-        // Response payload format: [ERROR_CODE, ...]
-        // If payload is empty, assume success
-        if response.payload.is_empty() {
-            return Ok(());
+    /// Put the robot back into a state captured by `snapshot_state`
+    ///
+    /// Wakes the robot first if needed, restores each LED, then puts it
+    /// back to sleep if it was asleep. If the snapshot's awake status is
+    /// unknown, the robot is neither woken nor put to sleep.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first LED update error; the remaining LEDs are still
+    /// restored.
+    pub fn restore_state(&self, state: &RobotState) -> Result<()> {
+        let awake = self.state().awake;
+        if state.awake == Some(true) && awake != Some(true) {
+            self.wake()?;
         }
 
-        let error_code = response.payload[0];
+        let result = self.set_leds_batch(&state.leds);
+        if let Some((position, error)) = result.failed.into_iter().next() {
+            tracing::warn!("Failed to restore {:?}: {}", position, error);
+            return Err(error);
+        }
 
-        match error_code {
-            error_code::SUCCESS => Ok(()),
-            error_code::BAD_DEVICE_ID => {
-                Err(RvrError::InvalidResponse("Bad device ID".to_string()))
-            }
-            error_code::BAD_COMMAND_ID => {
-                Err(RvrError::InvalidResponse("Bad command ID".to_string()))
-            }
-            error_code::NOT_YET_IMPLEMENTED => Err(RvrError::InvalidResponse(
-                "Command not yet implemented".to_string(),
-            )),
-            error_code::RESTRICTED => Err(RvrError::InvalidResponse(
-                "Command is restricted".to_string(),
-            )),
-            error_code::BAD_DATA_LENGTH => {
-                Err(RvrError::InvalidResponse("Bad data length".to_string()))
-            }
-            error_code::FAILED => Err(RvrError::CommandFailed(error_code)),
-            error_code::BAD_PARAMETER_VALUE => {
-                Err(RvrError::InvalidResponse("Bad parameter value".to_string()))
-            }
-            error_code::BUSY => Err(RvrError::InvalidResponse("Device is busy".to_string())),
-            code => Err(RvrError::CommandFailed(code)),
+        if state.awake == Some(false) && self.state().awake != Some(false) {
+            self.sleep()?;
         }
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Enable or disable the robot's default idle LED animation
+    ///
+    /// While idle, the RVR plays its own LED animation which can override
+    /// colors set by the application. Disable it to keep full control of
+    /// the LEDs; re-enable it to hand the LEDs back to the firmware.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - If true, the firmware idle animation is allowed to run
+    pub fn set_idle_animation(&self, enabled: bool) -> Result<()> {
+        tracing::debug!("Setting idle animation (enabled={})", enabled);
 
-    #[test]
-    fn test_build_command() {
-        let dispatcher = Dispatcher::new("/dev/null", 115200);
-        // Skip test if serial port can't open (expected on most systems)
-        if dispatcher.is_err() {
-            return;
-        }
+        let packet = self.build_command(
+            device::IO,
+            io_command::SET_IDLE_LED_ANIMATION,
+            toggle_payload(enabled),
+        );
 
-        let rvr = SpheroRvr {
-            dispatcher: dispatcher.unwrap(),
-        };
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
 
-        let packet = rvr.build_command(device::POWER, power_command::WAKE, vec![]);
+        Ok(())
+    }
 
-        assert_eq!(packet.device_id, device::POWER);
-        assert_eq!(packet.command_id, power_command::WAKE);
-        assert!(!packet.flags.is_response);
-        assert!(packet.flags.requests_response);
-        assert!(packet.payload.is_empty());
+    /// Get the battery percentage
+    ///
+    /// # Returns
+    ///
+    /// Battery state with percentage (0-100)
+    pub fn get_battery_percentage(&self) -> Result<BatteryState> {
+        tracing::debug!("Getting battery percentage");
 
-        // Verify UART routing fields are set
-        assert!(packet.flags.has_target_id);
-        assert!(packet.flags.has_source_id);
-        assert_eq!(packet.target_id, Some(routing_node::PRIMARY_PROCESSOR));
-        assert_eq!(packet.source_id, Some(routing_node::UART_PORT));
-    }
+        let packet =
+            self.build_command(device::POWER, power_command::GET_BATTERY_PERCENTAGE, vec![]);
 
-    #[test]
-    fn test_check_response_success() {
-        let dispatcher = Dispatcher::new("/dev/null", 115200);
-        if dispatcher.is_err() {
-            return;
-        }
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
 
-        let rvr = SpheroRvr {
-            dispatcher: dispatcher.unwrap(),
-        };
+        let battery = decode_battery_percentage(response_data(&response))?;
 
-        // Empty payload means success
-        let response = Packet {
+        tracing::debug!("Battery percentage: {}%", battery.percentage);
+        Ok(battery)
+    }
+
+    /// Get an aggregated view of the power system
+    ///
+    /// Battery percentage is required. Voltage state and protection state
+    /// are reported as `None` if the firmware does not implement them,
+    /// rather than failing the whole query.
+    pub fn get_power_status(&self) -> Result<PowerStatus> {
+        tracing::debug!("Getting power status");
+
+        let battery = self.get_battery_percentage()?;
+
+        let voltage_state = self
+            .optional_query(device::POWER, power_command::GET_BATTERY_VOLTAGE_STATE)?
+            .map(|response| {
+                first_data_byte(&response, "Voltage state").map(BatteryVoltageState::from_byte)
+            })
+            .transpose()?;
+
+        let protection = self
+            .optional_query(device::POWER, power_command::GET_PROTECTION_STATE)?
+            .map(|response| {
+                first_data_byte(&response, "Protection state").map(ProtectionState::from_byte)
+            })
+            .transpose()?;
+
+        Ok(PowerStatus {
+            battery,
+            voltage_state,
+            protection,
+        })
+    }
+
+    /// Estimate the remaining battery runtime at the current power draw
+    ///
+    /// Divides the remaining charge (battery percentage of the nominal pack
+    /// capacity) by the motor current measured right now. This is a rough
+    /// figure for mission planning only: it ignores the electronics' own
+    /// draw, battery ageing and the non-linear discharge curve, and it
+    /// swings with every change in driving load.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the firmware does not support current sensing, or
+    /// if the robot is drawing too little current (e.g. parked) for a
+    /// meaningful estimate.
+    pub fn estimate_runtime(&self) -> Result<Option<std::time::Duration>> {
+        let battery = self.get_battery_percentage()?;
+
+        let mut total_amps = None;
+        for amplifier in [amplifier_id::LEFT_MOTOR, amplifier_id::RIGHT_MOTOR] {
+            let response = self.optional_query_with(
+                device::POWER,
+                power_command::GET_CURRENT_SENSE_AMPLIFIER_CURRENT,
+                vec![amplifier],
+            )?;
+            let Some(response) = response else {
+                return Ok(None);
+            };
+            let amps = decode_f32(response_data(&response), "Current sense")?;
+            total_amps = Some(total_amps.unwrap_or(0.0) + amps.abs());
+        }
+
+        let estimate = total_amps.and_then(|amps| runtime_estimate(battery.percentage, amps));
+        tracing::debug!(
+            "Runtime estimate: {:?} ({}%, {:?} A)",
+            estimate,
+            battery.percentage,
+            total_amps
+        );
+        Ok(estimate)
+    }
+
+    /// Enable or disable battery voltage state change notifications
+    ///
+    /// When enabled, the robot sends a notification whenever the battery
+    /// voltage state changes. Decode them with `BatteryEvent::from_packet`.
+    pub fn enable_battery_state_notifications(&self, enabled: bool) -> Result<()> {
+        tracing::debug!("Setting battery state notifications (enabled={})", enabled);
+
+        let packet = self.build_command(
+            device::POWER,
+            power_command::ENABLE_BATTERY_VOLTAGE_STATE_CHANGE_NOTIFY,
+            toggle_payload(enabled),
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Read the latched protection state
+    ///
+    /// The robot latches into a protective state on overtemperature,
+    /// undervoltage, or overcurrent and will not drive until cleared.
+    pub fn get_protection_state(&self) -> Result<ProtectionState> {
+        tracing::debug!("Getting protection state");
+
+        let packet = self.build_command(device::POWER, power_command::GET_PROTECTION_STATE, vec![]);
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        let state = ProtectionState::from_byte(first_data_byte(&response, "Protection state")?);
+
+        tracing::debug!("Protection state: {:?}", state);
+        Ok(state)
+    }
+
+    /// Clear the latched protection state
+    ///
+    /// Only clears the latch; if the underlying condition persists
+    /// (e.g. the battery is still low) the robot will latch again.
+    pub fn clear_protection_latch(&self) -> Result<()> {
+        tracing::debug!("Clearing protection latch");
+
+        let packet =
+            self.build_command(device::POWER, power_command::CLEAR_PROTECTION_LATCH, vec![]);
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Get the application firmware version
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// println!("Firmware {}", rvr.get_firmware_version()?);
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn get_firmware_version(&self) -> Result<FirmwareVersion> {
+        tracing::debug!("Getting firmware version");
+
+        let packet = self.build_command(
+            device::SYSTEM_INFO,
+            system_info_command::GET_FIRMWARE_VERSION,
+            vec![],
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        let version = decode_firmware_version(response_data(&response))?;
+
+        tracing::debug!("Firmware version: {}", version);
+        Ok(version)
+    }
+
+    /// Get the robot's Bluetooth MAC address
+    ///
+    /// Handy for logging which robot a controller is paired with.
+    ///
+    /// # Returns
+    ///
+    /// The address as uppercase hex pairs, e.g. `"A1:B2:C3:D4:E5:F6"`
+    pub fn get_mac_address(&self) -> Result<String> {
+        tracing::debug!("Getting MAC address");
+
+        let packet = self.build_command(
+            device::SYSTEM_INFO,
+            system_info_command::GET_MAC_ADDRESS,
+            vec![],
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        format_mac_address(response_data(&response))
+    }
+
+    /// Get the API protocol (shell) version implemented by the firmware
+    ///
+    /// This is separate from the application firmware version and
+    /// identifies the packet/command format the robot speaks.
+    ///
+    /// # Returns
+    ///
+    /// `(major, minor)` protocol version
+    pub fn get_api_protocol_version(&self) -> Result<(u8, u8)> {
+        tracing::debug!("Getting API protocol version");
+
+        let packet = self.build_command(
+            device::SYSTEM_INFO,
+            system_info_command::GET_API_PROTOCOL_VERSION,
+            vec![],
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        let version = decode_api_protocol_version(response_data(&response))?;
+
+        tracing::debug!("API protocol version: {}.{}", version.0, version.1);
+        Ok(version)
+    }
+
+    /// Detect whether the robot is an RVR or an RVR+
+    ///
+    /// Reads the board revision (see `RvrModel` for how revisions map to
+    /// models), so higher-level code can branch on model-specific
+    /// capabilities.
+    pub fn detect_model(&self) -> Result<RvrModel> {
+        tracing::debug!("Detecting robot model");
+
+        let packet = self.build_command(
+            device::SYSTEM_INFO,
+            system_info_command::GET_HARDWARE_VERSION,
+            vec![],
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        let revision = first_data_byte(&response, "Hardware version")?;
+        let model = RvrModel::from_board_revision(revision);
+
+        tracing::debug!("Board revision {}: {:?}", revision, model);
+        Ok(model)
+    }
+
+    /// Read the robot's timestamp base (milliseconds since its core booted)
+    ///
+    /// Also records a `DeviceClock` mapping the device timestamp to the host
+    /// clock, available via `device_clock()`. The host reference is taken at
+    /// the midpoint of the request/response round trip.
+    pub fn get_device_timestamp(&self) -> Result<u64> {
+        tracing::debug!("Getting device timestamp");
+
+        let packet = self.build_command(
+            device::SYSTEM_INFO,
+            system_info_command::GET_CORE_UPTIME_MS,
+            vec![],
+        );
+
+        let sent_at = std::time::Instant::now();
+        let response = self.dispatcher.send_command(packet)?;
+        let round_trip = sent_at.elapsed();
+        self.check_response(&response)?;
+
+        let device_ms = decode_device_timestamp(response_data(&response))?;
+        self.state().device_clock = Some(DeviceClock::new(device_ms, sent_at + round_trip / 2));
+
+        tracing::debug!("Device timestamp: {} ms", device_ms);
+        Ok(device_ms)
+    }
+
+    /// Device-to-host clock mapping from the last `get_device_timestamp` call
+    pub fn device_clock(&self) -> Option<DeviceClock> {
+        self.state().device_clock
+    }
+
+    /// Check that the robot speaks a protocol version this crate supports
+    ///
+    /// Fails with `RvrError::Protocol` if the API protocol major version
+    /// differs from the one this crate was written against.
+    pub fn check_compatibility(&self) -> Result<()> {
+        let (major, minor) = self.get_api_protocol_version()?;
+
+        if major != SUPPORTED_API_PROTOCOL_MAJOR {
+            return Err(RvrError::Protocol(format!(
+                "Unsupported API protocol version {}.{} (expected {}.x)",
+                major, minor, SUPPORTED_API_PROTOCOL_MAJOR
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Calibrate the compass, blocking until calibration finishes
+    ///
+    /// The robot spins in place to sample the magnetic field, reporting
+    /// progress through notifications. This waits for the completion
+    /// notification, so it returns only once the compass is usable.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait for calibration to finish
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::Timeout` if calibration does not finish in time,
+    /// or `RvrError::DeviceError` with `error_code::FAILED` if the robot
+    /// reports a failed calibration.
+    pub fn calibrate_compass(&self, timeout: std::time::Duration) -> Result<()> {
+        tracing::debug!("Calibrating compass");
+
+        // Subscribe first so a fast completion cannot be missed
+        let finished = self
+            .dispatcher
+            .subscribe_notification(is_calibration_finished);
+
+        let packet = self.build_command(
+            device::SENSOR,
+            sensor_command::CALIBRATE_MAGNETOMETER,
+            vec![],
+        );
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        calibration_result(&finished.wait(timeout)?)
+    }
+
+    /// Read the robot's orientation as a unit quaternion
+    ///
+    /// Returns the IMU's filtered attitude as `[w, x, y, z]`, normalized to
+    /// unit length. Unlike Euler angles, quaternions have no gimbal lock
+    /// and compose directly in sensor fusion.
+    ///
+    /// The RVR has no one-shot orientation query, so this waits for the
+    /// next sample of a running stream that includes
+    /// `SensorType::Quaternion` (see `configure_sensor_streaming`).
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if no configured stream carries
+    /// quaternions, or `RvrError::Timeout` if streaming is not running.
+    pub fn get_orientation_quaternion(&self) -> Result<[f32; 4]> {
+        tracing::debug!("Getting orientation quaternion");
+
+        match self.next_sample(SensorType::Quaternion)? {
+            SensorReading::Quaternion { w, x, y, z } => normalize_quaternion([w, x, y, z]),
+            reading => Err(RvrError::InvalidResponse(format!(
+                "Expected a quaternion sample, got {:?}",
+                reading
+            ))),
+        }
+    }
+
+    /// Wait for the next streamed sample of `sensor`
+    ///
+    /// Streaming must be configured with `sensor` and running.
+    fn next_sample(&self, sensor: SensorType) -> Result<SensorReading> {
+        let (config, interval_ms) = {
+            let state = self.state();
+            let config = state
+                .streams
+                .iter()
+                .find(|config| config.sensors().contains(&sensor))
+                .cloned();
+            (config, state.streaming_interval_ms.unwrap_or(0))
+        };
+        let config = config.ok_or_else(|| {
+            RvrError::InvalidParameter(format!("{:?} is not being streamed", sensor))
+        })?;
+
+        let timeout =
+            DEFAULT_COMMAND_TIMEOUT + std::time::Duration::from_millis(2 * interval_ms as u64);
+        let matcher = config.clone();
+        let packet = self.wait_for_notification(move |p| matcher.decode(p).is_ok(), timeout)?;
+        config
+            .decode(&packet)?
+            .into_iter()
+            .find(|reading| reading.sensor() == sensor)
+            .ok_or_else(|| {
+                RvrError::InvalidResponse(format!("Stream sample is missing {:?}", sensor))
+            })
+    }
+
+    /// Read the robot's position from the locator
+    ///
+    /// Waits for the next sample of a running stream that includes
+    /// `SensorType::Locator` (see `configure_sensor_streaming`); the RVR has
+    /// no one-shot position query.
+    ///
+    /// # Returns
+    ///
+    /// Position in meters relative to where the locator was last reset
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if no configured stream carries
+    /// the locator, or `RvrError::Timeout` if streaming is not running.
+    pub fn get_locator_position(&self) -> Result<Locator> {
+        match self.next_sample(SensorType::Locator)? {
+            SensorReading::Locator { x, y } => Ok(Locator { x, y }),
+            reading => Err(RvrError::InvalidResponse(format!(
+                "Expected a locator sample, got {:?}",
+                reading
+            ))),
+        }
+    }
+
+    /// Turn the underside color sensor on or off
+    ///
+    /// Enabling powers the sensor (and the white LED beside it) and then
+    /// turns on color detection notifications every
+    /// `COLOR_DETECTION_INTERVAL_MS`. They arrive on the notification
+    /// channel; decode them with `DetectedColor::from_packet`, or watch for
+    /// `RvrEvent::ColorDetected`. Every reading is reported, so filter on
+    /// `DetectedColor::confidence` to skip ambiguous ones. Disabling turns
+    /// the notifications off before the sensor.
+    pub fn enable_color_detection(&self, enabled: bool) -> Result<()> {
+        tracing::debug!("Setting color detection enabled={}", enabled);
+
+        let [interval_hi, interval_lo] = COLOR_DETECTION_INTERVAL_MS.to_be_bytes();
+        let notify = self.build_command(
+            device::SENSOR,
+            sensor_command::ENABLE_COLOR_DETECTION_NOTIFY,
+            vec![enabled as u8, interval_hi, interval_lo, 0],
+        );
+        let sensor = self.build_command(
+            device::SENSOR,
+            sensor_command::ENABLE_COLOR_DETECTION,
+            vec![enabled as u8],
+        );
+
+        // The sensor must be on before it can report, and reporting should
+        // stop before the sensor goes off
+        let order = if enabled {
+            [sensor, notify]
+        } else {
+            [notify, sensor]
+        };
+        for packet in order {
+            let response = self.dispatcher.send_command(packet)?;
+            self.check_response(&response)?;
+        }
+        Ok(())
+    }
+
+    /// Send an infrared message to nearby robots
+    ///
+    /// All four emitters (front, left, right, rear) send `code` at the same
+    /// `strength`. Robots that have called `enable_ir_messages(true)`
+    /// report it as an `IrMessage` notification.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Message code (0-7)
+    /// * `strength` - Emitter strength (0-64); higher carries farther
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if `code` or `strength` is out
+    /// of range.
+    pub fn send_ir_message(&self, code: u8, strength: u8) -> Result<()> {
+        if code > MAX_IR_CODE {
+            return Err(RvrError::InvalidParameter(format!(
+                "IR code must be 0-{}, got {}",
+                MAX_IR_CODE, code
+            )));
+        }
+        if strength > MAX_IR_STRENGTH {
+            return Err(RvrError::InvalidParameter(format!(
+                "IR strength must be 0-{}, got {}",
+                MAX_IR_STRENGTH, strength
+            )));
+        }
+        tracing::debug!("Sending IR message {} (strength={})", code, strength);
+
+        let packet = self.build_command(
+            device::SENSOR,
+            sensor_command::SEND_IR_MESSAGE,
+            vec![code, strength, strength, strength, strength],
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Turn notifications for received IR messages on or off
+    ///
+    /// Messages arrive on the notification channel; decode them with
+    /// `IrMessage::from_packet`, or watch for `RvrEvent::IrMessage`.
+    pub fn enable_ir_messages(&self, enabled: bool) -> Result<()> {
+        tracing::debug!("Setting IR message notifications enabled={}", enabled);
+
+        let packet = self.build_command(
+            device::SENSOR,
+            sensor_command::ENABLE_IR_MESSAGE_NOTIFY,
+            vec![enabled as u8],
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Read the ambient light level once, in lux
+    ///
+    /// For an occasional check this is simpler than streaming
+    /// `SensorType::AmbientLight`. The sensor sits on top of the robot and
+    /// reports roughly 0 (dark) to 120,000 lux (direct sunlight); typical
+    /// indoor lighting reads in the hundreds.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidResponse` if the response is shorter than
+    /// one f32.
+    pub fn get_ambient_light(&self) -> Result<f32> {
+        tracing::debug!("Getting ambient light");
+
+        let packet = self.build_command(
+            device::SENSOR,
+            sensor_command::GET_AMBIENT_LIGHT_SENSOR_VALUE,
+            vec![],
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        decode_f32(response_data(&response), "Ambient light")
+    }
+
+    /// Set up sensor streaming, replacing any previous configuration
+    ///
+    /// Each sensor gets its own stream, so every notification can be
+    /// decoded on its own with `SensorReading::from_packet` (or arrives as
+    /// `RvrEvent::Sensor` from `run_notifications_for`). Streaming does
+    /// not begin until `start_sensor_streaming` is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `sensors` - Sensors to stream
+    /// * `interval_ms` - Time between samples in milliseconds, at least
+    ///   `MIN_STREAMING_INTERVAL_MS`
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if `interval_ms` is below
+    /// `MIN_STREAMING_INTERVAL_MS`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::{SensorReading, SensorType};
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// let rx = rvr.take_receiver().unwrap();
+    /// rvr.configure_sensor_streaming(&[SensorType::Locator, SensorType::Speed], 100)?;
+    /// rvr.start_sensor_streaming()?;
+    /// for packet in rx.iter().take(10) {
+    ///     if let Ok(reading) = SensorReading::from_packet(&packet) {
+    ///         println!("{:?}", reading);
+    ///     }
+    /// }
+    /// rvr.stop_sensor_streaming()?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn configure_sensor_streaming(
+        &self,
+        sensors: &[SensorType],
+        interval_ms: u16,
+    ) -> Result<()> {
+        check_streaming_interval(interval_ms)?;
+        tracing::debug!(
+            "Configuring sensor streaming: {:?} every {} ms",
+            sensors,
+            interval_ms
+        );
+
+        let streams = sensors.iter().map(|&sensor| SensorConfig::single(sensor));
+        self.set_streams(streams, interval_ms)
+    }
+
+    /// Set up one stream carrying several sensors, replacing any previous
+    /// configuration
+    ///
+    /// Unlike `configure_sensor_streaming`, the sensors share a token and
+    /// every notification carries a sample of each, in the order of
+    /// `config`. Decode them with `SensorConfig::decode`. Streaming does not
+    /// begin until `start_sensor_streaming` is called.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if `interval_ms` is below
+    /// `MIN_STREAMING_INTERVAL_MS`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::{SensorConfig, SensorType};
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// let config = SensorConfig::new(0x20, &[SensorType::Imu, SensorType::Accelerometer])?;
+    /// let rx = rvr.take_receiver().unwrap();
+    /// rvr.configure_sensor_stream(&config, 100)?;
+    /// rvr.start_sensor_streaming()?;
+    /// for packet in rx.iter().take(10) {
+    ///     if let Ok(readings) = config.decode(&packet) {
+    ///         println!("{:?}", readings);
+    ///     }
+    /// }
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn configure_sensor_stream(&self, config: &SensorConfig, interval_ms: u16) -> Result<()> {
+        check_streaming_interval(interval_ms)?;
+        tracing::debug!(
+            "Configuring sensor stream {}: {:?} every {} ms",
+            config.token(),
+            config.sensors(),
+            interval_ms
+        );
+
+        self.set_streams(std::iter::once(config.clone()), interval_ms)
+    }
+
+    /// Clear the streaming configuration and send `streams` in its place
+    fn set_streams(
+        &self,
+        streams: impl IntoIterator<Item = SensorConfig>,
+        interval_ms: u16,
+    ) -> Result<()> {
+        let packet = self.build_command(
+            device::SENSOR,
+            sensor_command::CLEAR_SENSOR_STREAMING,
+            vec![],
+        );
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+        {
+            let mut state = self.state();
+            state.streaming_interval_ms = None;
+            state.streams.clear();
+        }
+
+        for config in streams {
+            let packet = self.build_command(
+                device::SENSOR,
+                sensor_command::SET_SENSOR_STREAMING,
+                config.config_payload(),
+            );
+            let response = self.dispatcher.send_command(packet)?;
+            self.check_response(&response)?;
+            self.state().streams.push(config);
+        }
+
+        self.state().streaming_interval_ms = Some(interval_ms);
+        Ok(())
+    }
+
+    /// Start streaming the sensors set up by `configure_sensor_streaming` or
+    /// `configure_sensor_stream`
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if streaming has not been
+    /// configured.
+    pub fn start_sensor_streaming(&self) -> Result<()> {
+        let interval_ms = self.state().streaming_interval_ms.ok_or_else(|| {
+            RvrError::InvalidParameter(
+                "call configure_sensor_streaming before starting".to_string(),
+            )
+        })?;
+        tracing::debug!("Starting sensor streaming every {} ms", interval_ms);
+
+        let packet = self.build_command(
+            device::SENSOR,
+            sensor_command::START_SENSOR_STREAMING,
+            interval_ms.to_be_bytes().to_vec(),
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Change how often streamed sensors are sampled
+    ///
+    /// Separate from `configure_sensor_streaming`, which chooses the
+    /// sensors: this only changes the rate, and takes effect on a running
+    /// stream. Longer intervals cut UART traffic when coarse updates are
+    /// enough. The RVR cannot sample faster than about every 33 ms
+    /// (`MIN_STREAMING_INTERVAL_MS`).
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if `interval_ms` is below
+    /// `MIN_STREAMING_INTERVAL_MS`.
+    pub fn set_streaming_interval(&self, interval_ms: u16) -> Result<()> {
+        check_streaming_interval(interval_ms)?;
+        tracing::debug!("Setting streaming interval to {} ms", interval_ms);
+
+        let packet = self.build_command(
+            device::SENSOR,
+            sensor_command::SET_STREAMING_INTERVAL,
+            interval_ms.to_be_bytes().to_vec(),
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        // Restarting after a stop should keep the new rate
+        let mut state = self.state();
+        if state.streaming_interval_ms.is_some() {
+            state.streaming_interval_ms = Some(interval_ms);
+        }
+        Ok(())
+    }
+
+    /// Stop sensor streaming (the configuration is kept)
+    pub fn stop_sensor_streaming(&self) -> Result<()> {
+        tracing::debug!("Stopping sensor streaming");
+
+        let packet = self.build_command(
+            device::SENSOR,
+            sensor_command::STOP_SENSOR_STREAMING,
+            vec![],
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Zero the locator so the current position becomes (0, 0)
+    ///
+    /// Call before a run so locator readings are relative to the start.
+    /// Only the XY position is reset; heading is left alone (see
+    /// `reset_yaw`).
+    pub fn reset_locator(&self) -> Result<()> {
+        tracing::debug!("Resetting locator");
+
+        let packet = self.build_command(device::SENSOR, sensor_command::RESET_LOCATOR, vec![]);
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Reset the yaw angle to zero
+    ///
+    /// Makes the current heading 0 degrees for `drive_with_heading`. The
+    /// locator position is left alone (see `reset_locator`).
+    pub fn reset_yaw(&self) -> Result<()> {
+        tracing::debug!("Resetting yaw");
+
+        let packet = self.build_command(device::DRIVE, drive_command::RESET_YAW, vec![]);
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Stop all motors
+    ///
+    /// # Arguments
+    ///
+    /// * `brake` - If true, brake motors. If false, coast to stop.
+    pub fn stop(&self, brake: bool) -> Result<()> {
+        tracing::debug!("Stopping motors (brake={})", brake);
+
+        let response = self
+            .dispatcher
+            .send_command(self.routed(Command::Stop { brake }.to_packet()))?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Limit the maximum speed of all subsequent drive commands
+    ///
+    /// Every drive command (`drive_with_heading`, `set_raw_motors`,
+    /// `set_wheel_velocities`, `drive_control_system`) is clamped to this
+    /// limit before it is sent.
+    /// Useful in classrooms or tight spaces. Use 255 to remove the limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - Maximum speed (0-255); wheel velocities are scaled proportionally
+    pub fn set_speed_limit(&self, max: u8) {
+        tracing::debug!("Setting speed limit to {}", max);
+        self.state().max_speed = max;
+    }
+
+    /// Drive at a speed along a heading
+    ///
+    /// # Arguments
+    ///
+    /// * `speed` - Speed (0-255), clamped to the speed limit
+    /// * `heading` - Heading in degrees (0-359) relative to the yaw origin
+    /// * `flags` - Drive flags (see `drive_flags` constants or `DriveFlags::to_byte`)
+    pub fn drive_with_heading(&self, speed: u8, heading: u16, flags: u8) -> Result<()> {
+        let speed = clamp_speed(speed, self.state().max_speed);
+        tracing::debug!(
+            "Driving with heading (speed={}, heading={}, flags={:#04x})",
+            speed,
+            heading,
+            flags
+        );
+
+        let packet = self.routed(
+            Command::DriveWithHeading {
+                speed,
+                heading,
+                flags: DriveFlags::from_byte(flags),
+            }
+            .to_packet(),
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Set each motor's direction and PWM duty cycle directly
+    ///
+    /// The lowest-level drive primitive: there is no heading control or
+    /// closed-loop speed regulation. Both speeds are clamped to the speed
+    /// limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `left_mode` / `right_mode` - Direction of each motor
+    /// * `left_speed` / `right_speed` - Duty cycle (0-255)
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if a motor is `MotorMode::Off`
+    /// with a non-zero speed, since the robot would silently ignore it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::types::MotorMode;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// // Spin in place
+    /// rvr.set_raw_motors(MotorMode::Forward, 64, MotorMode::Reverse, 64)?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn set_raw_motors(
+        &self,
+        left_mode: MotorMode,
+        left_speed: u8,
+        right_mode: MotorMode,
+        right_speed: u8,
+    ) -> Result<()> {
+        let left_speed = clamp_speed(left_speed, self.state().max_speed);
+        let right_speed = clamp_speed(right_speed, self.state().max_speed);
+        tracing::debug!(
+            "Setting raw motors (left={:?} {}, right={:?} {})",
+            left_mode,
+            left_speed,
+            right_mode,
+            right_speed
+        );
+
+        let payload = raw_motors_payload(left_mode, left_speed, right_mode, right_speed)?;
+        let packet = self.build_command(device::DRIVE, drive_command::SET_RAW_MOTORS, payload);
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Drive a set distance along a heading, then brake
+    ///
+    /// Drives with `drive_with_heading` and watches the locator stream until
+    /// the robot is `distance_cm` from where it started. Streaming must be
+    /// configured with `SensorType::Locator` and running; the distance is
+    /// checked on every sample, so a shorter streaming interval stops
+    /// closer to the target.
+    ///
+    /// # Arguments
+    ///
+    /// * `heading` - Heading in degrees (0-359)
+    /// * `speed` - Speed (0-255), subject to `set_speed_limit`
+    /// * `distance_cm` - Straight-line distance to cover, in centimeters
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if `distance_cm` is not a
+    /// positive number or the locator is not being streamed, and
+    /// `RvrError::Timeout` if the robot stops making progress (stalled
+    /// against an obstacle, or `speed` is 0). The robot is braked in both
+    /// the success and the stall case.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::SensorType;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// rvr.configure_sensor_streaming(&[SensorType::Locator], 50)?;
+    /// rvr.start_sensor_streaming()?;
+    /// // Drive one meter straight ahead
+    /// rvr.drive_distance(0, 80, 100.0)?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn drive_distance(&self, heading: u16, speed: u8, distance_cm: f32) -> Result<()> {
+        if !(distance_cm.is_finite() && distance_cm > 0.0) {
+            return Err(RvrError::InvalidParameter(format!(
+                "distance must be a positive number of cm, got {}",
+                distance_cm
+            )));
+        }
+        tracing::debug!(
+            "Driving {} cm (heading={}, speed={})",
+            distance_cm,
+            heading,
+            speed
+        );
+
+        let start = self.get_locator_position()?;
+        let mut tracker = DistanceTracker::new(start, distance_cm, std::time::Instant::now());
+        self.drive_with_heading(speed, heading, 0)?;
+
+        let outcome = loop {
+            let position = match self.get_locator_position() {
+                Ok(position) => position,
+                Err(e) => break Err(e),
+            };
+            match tracker.update(position, std::time::Instant::now()) {
+                DriveProgress::Moving => {}
+                DriveProgress::Reached => break Ok(()),
+                DriveProgress::Stalled => {
+                    tracing::warn!(
+                        "Drive stalled after {:.1} of {} cm",
+                        tracker.best_cm,
+                        distance_cm
+                    );
+                    break Err(RvrError::Timeout);
+                }
+            }
+        };
+
+        self.brake_after(outcome)
+    }
+
+    /// Drive each wheel at a target velocity using closed-loop control
+    ///
+    /// Unlike raw motor commands, which set PWM duty cycle, the onboard
+    /// controller measures the wheel encoders and maintains the requested
+    /// velocities under changing load. Useful for odometry-based navigation.
+    ///
+    /// # Arguments
+    ///
+    /// * `left_cm_s` - Left wheel velocity in cm/s (negative = reverse)
+    /// * `right_cm_s` - Right wheel velocity in cm/s (negative = reverse)
+    pub fn set_wheel_velocities(&self, left_cm_s: f32, right_cm_s: f32) -> Result<()> {
+        tracing::debug!(
+            "Setting wheel velocities (left={} cm/s, right={} cm/s)",
+            left_cm_s,
+            right_cm_s
+        );
+
+        let max_speed = self.state().max_speed;
+        let packet = self.build_command(
+            device::DRIVE,
+            drive_command::SET_WHEEL_VELOCITIES,
+            wheel_velocities_payload(
+                clamp_velocity(left_cm_s, max_speed),
+                clamp_velocity(right_cm_s, max_speed),
+            ),
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Drive with normalized throttle and yaw, like a joystick
+    ///
+    /// Uses the firmware's RC control system, which smooths the inputs,
+    /// so it suits continuous joystick or gamepad input better than
+    /// `drive_with_heading` or `set_raw_motors`. Send updates regularly;
+    /// each command replaces the previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `throttle` - Forward (positive) or reverse (negative) speed, -1.0 to 1.0
+    /// * `yaw` - Turn rate, -1.0 (full left) to 1.0 (full right)
+    ///
+    /// Values outside the range are clamped, NaN is treated as 0.0, and the
+    /// throttle is scaled by the speed limit.
+    pub fn drive_control_system(&self, throttle: f32, yaw: f32) -> Result<()> {
+        let throttle = normalize_control(throttle) * self.state().max_speed as f32 / u8::MAX as f32;
+        let yaw = normalize_control(yaw);
+        tracing::debug!(
+            "Driving with control system (throttle={:.3}, yaw={:.3})",
+            throttle,
+            yaw
+        );
+
+        let packet = self.build_command(
+            device::DRIVE,
+            drive_command::DRIVE_RC_NORMALIZED,
+            rc_normalized_payload(yaw, throttle),
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Actively hold the robot at its current position
+    ///
+    /// Unlike `stop`, which only cuts (coast) or shorts (brake) the motors,
+    /// this commands the closed-loop RC controller to zero linear and yaw
+    /// velocity. The controller pushes back against external forces, so
+    /// the robot stays put on a slope where a braked robot could slowly
+    /// roll.
+    ///
+    /// The hold stays active until the next drive command.
+    pub fn hold_position(&self) -> Result<()> {
+        tracing::debug!("Holding position");
+
+        let packet = self.build_command(
+            device::DRIVE,
+            drive_command::DRIVE_RC_SI_UNITS,
+            rc_si_units_payload(0.0, 0.0),
+        );
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(())
+    }
+
+    /// Send bytes to the robot and return what it echoes back
+    ///
+    /// The robot returns the payload unchanged, so comparing the result
+    /// with `data` tests the whole link end to end. Payloads containing
+    /// the framing bytes (SOP `0x8D`, EOP `0xD8`, ESC `0xAB`) exercise the
+    /// escaping in both directions.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// let data = [0x8D, 0xD8, 0xAB, 0x00];
+    /// assert_eq!(rvr.echo(&data).unwrap(), data);
+    /// ```
+    pub fn echo(&self, data: &[u8]) -> Result<Vec<u8>> {
+        tracing::debug!("Echoing {} bytes", data.len());
+
+        let response = self
+            .dispatcher
+            .send_command(self.routed(Command::Echo(data.to_vec()).to_packet()))?;
+        self.check_response(&response)?;
+
+        Ok(response_data(&response).to_vec())
+    }
+
+    /// Check the robot is responsive and measure the round trip
+    ///
+    /// Sends an echo with a short nonce and verifies it comes back
+    /// unchanged. Echo has no side effects, so this is safe to call
+    /// periodically from a health monitor.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::Timeout` if the robot does not reply, or
+    /// `RvrError::InvalidResponse` if the echoed payload does not match
+    pub fn ping(&self) -> Result<std::time::Duration> {
+        let nonce = ping_nonce();
+        let start = std::time::Instant::now();
+        let echoed = self.echo(&nonce)?;
+        let latency = start.elapsed();
+
+        verify_echo(&nonce, &echoed)?;
+        tracing::debug!("Ping round trip: {:?}", latency);
+        Ok(latency)
+    }
+
+    /// Send an arbitrary command and return the checked response
+    ///
+    /// Device and command ids are passed through without validation, so this
+    /// can reach commands the crate has no wrapper for, or device ids outside
+    /// the documented range (e.g. a co-processor or firmware extension that
+    /// speaks the same protocol).
+    ///
+    /// # Arguments
+    ///
+    /// * `device_id` - Target device id (DID)
+    /// * `command_id` - Command id (CID)
+    /// * `payload` - Raw command payload
+    ///
+    /// # Returns
+    ///
+    /// Returns the response packet; its payload starts with the error code
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// let response = rvr.raw_command(0x42, 0x01, vec![0xAA]).unwrap();
+    /// println!("Response: {:02X?}", response.payload);
+    /// ```
+    pub fn raw_command(&self, device_id: u8, command_id: u8, payload: Vec<u8>) -> Result<Packet> {
+        tracing::debug!(
+            "Sending raw command dev={:#04x} cmd={:#04x}",
+            device_id,
+            command_id
+        );
+
+        let packet = self.build_command(device_id, command_id, payload);
+
+        let response = self.dispatcher.send_command(packet)?;
+        self.check_response(&response)?;
+
+        Ok(response)
+    }
+
+    /// Send a command, treating a missing response according to `policy`
+    ///
+    /// With `TimeoutPolicy::Success` the command is fire-and-forget as far
+    /// as timeouts go: if no response arrives within the command timeout
+    /// (or the robot has announced it is asleep) this returns `Ok(None)`.
+    /// A response that does arrive is still checked, so error codes are
+    /// reported either way. Pass `command.timeout_policy()` for the policy
+    /// the crate itself uses.
+    ///
+    /// # Returns
+    ///
+    /// Returns the response packet, or `None` if a tolerated timeout occurred
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// use sphero_rvr::api::command::{Command, TimeoutPolicy};
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// rvr.send_with_policy(&Command::Stop { brake: true }, TimeoutPolicy::Success)?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn send_with_policy(
+        &self,
+        command: &Command,
+        policy: TimeoutPolicy,
+    ) -> Result<Option<Packet>> {
+        let packet = self.routed(command.to_packet());
+        tolerate_timeout(self.dispatcher.send_command(packet), policy, command)
+    }
+
+    /// Take ownership of the notification receiver
+    ///
+    /// This allows you to receive async notifications like sensor data.
+    /// Can only be called once.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// if let Some(rx) = rvr.take_receiver() {
+    ///     std::thread::spawn(move || {
+    ///         for packet in rx {
+    ///             println!("Notification: {:?}", packet);
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    pub fn take_receiver(&self) -> Option<NotificationReceiver> {
+        self.dispatcher.take_receiver()
+    }
+
+    /// Limit outgoing commands to at most `max_per_sec` per second
+    ///
+    /// See `Dispatcher::set_command_rate_limit`. Useful for drive loops that
+    /// would otherwise flood the robot with commands. 0 removes the limit.
+    pub fn set_command_rate_limit(&self, max_per_sec: u32) {
+        self.dispatcher.set_command_rate_limit(max_per_sec);
+    }
+
+    /// Start or stop recording every command sent
+    ///
+    /// Returns the previously attached recorder, if any. Replay a recording
+    /// with `api::replay`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::types::Color;
+    /// # use sphero_rvr::transport::CommandRecorder;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// rvr.set_recorder(Some(CommandRecorder::create("show.rec").unwrap()));
+    /// rvr.set_all_leds(Color::RED).unwrap();
+    /// rvr.set_recorder(None);
+    /// ```
+    pub fn set_recorder(&self, recorder: Option<CommandRecorder>) -> Option<CommandRecorder> {
+        self.dispatcher.set_recorder(recorder)
+    }
+
+    /// Send a prebuilt packet as-is, waiting for a response if it requests one
+    pub(crate) fn send_packet(&self, packet: Packet) -> Result<()> {
+        if packet.flags.requests_response {
+            let response = self.dispatcher.send_command(packet)?;
+            self.check_response(&response)
+        } else {
+            self.dispatcher.send_packet_no_response(&packet)
+        }
+    }
+
+    /// Enable or disable per-packet trace logging for a device
+    ///
+    /// See `Dispatcher::set_command_log_filter`. Useful to silence
+    /// `device::DRIVE` when streaming drive commands.
+    pub fn set_command_logging(&self, device_id: u8, enabled: bool) {
+        self.dispatcher.set_command_log_filter(device_id, enabled);
+    }
+
+    /// Handle notifications on the calling thread for a fixed time
+    ///
+    /// Blocks for `duration`, passing each notification to `handler` as an
+    /// `RvrEvent`. This replaces the usual spawn-a-thread `recv_timeout`
+    /// loop when the program has nothing else to do while listening.
+    ///
+    /// The notification receiver is borrowed for the call and handed back
+    /// afterwards, so this can be called repeatedly.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of events handled, or an error if the receiver
+    /// has already been taken with `take_receiver`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use std::time::Duration;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// let handled = rvr
+    ///     .run_notifications_for(Duration::from_secs(10), |event| println!("{:?}", event))
+    ///     .unwrap();
+    /// println!("Handled {} events", handled);
+    /// ```
+    pub fn run_notifications_for(
+        &self,
+        duration: std::time::Duration,
+        handler: impl FnMut(RvrEvent),
+    ) -> Result<usize> {
+        let rx = self.dispatcher.take_receiver().ok_or_else(|| {
+            RvrError::Protocol("Notification receiver has already been taken".to_string())
+        })?;
+
+        let handled = pump_events(&rx, duration, handler);
+        self.dispatcher.restore_receiver(rx);
+
+        Ok(handled)
+    }
+
+    /// Take a receiver for link errors seen by the RX thread
+    ///
+    /// See `Dispatcher::take_error_receiver`. Can only be called once.
+    pub fn take_error_receiver(&self) -> Option<ErrorReceiver> {
+        self.dispatcher.take_error_receiver()
+    }
+
+    /// Block until a notification matching `pred` arrives
+    ///
+    /// See `Dispatcher::wait_for_notification`. Works whether or not the
+    /// notification receiver has been taken.
+    pub fn wait_for_notification(
+        &self,
+        pred: impl Fn(&Packet) -> bool + Send + 'static,
+        timeout: std::time::Duration,
+    ) -> Result<Packet> {
+        self.dispatcher.wait_for_notification(pred, timeout)
+    }
+
+    /// Keep the robot from sleeping by pinging it every `interval`
+    ///
+    /// The RVR sleeps after about 5 minutes without commands. This spawns a
+    /// background thread that sends an echo command each interval; it does
+    /// not wake a robot that is already asleep. Calling it again restarts
+    /// the thread with the new interval.
+    ///
+    /// Each ping takes the serial port's write lock, competing with
+    /// foreground commands and other writers (the RX thread reads through
+    /// its own handle), so keep the interval generous
+    /// (`DEFAULT_KEEP_AWAKE_INTERVAL` is 60 seconds).
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if `interval` is zero
+    pub fn start_keep_awake(&self, interval: std::time::Duration) -> Result<()> {
+        if interval.is_zero() {
+            return Err(RvrError::InvalidParameter(
+                "keep-awake interval must be greater than zero".to_string(),
+            ));
+        }
+
+        let mut keep_awake = self.keep_awake.lock().unwrap();
+        if let Some(previous) = keep_awake.take() {
+            previous.stop()?;
+        }
+        tracing::debug!("Starting keep-awake every {:?}", interval);
+        let ping = self.routed(command::echo(&[]));
+        *keep_awake = Some(self::keep_awake(
+            Arc::clone(&self.dispatcher),
+            interval,
+            ping,
+        ));
+        Ok(())
+    }
+
+    /// Stop the keep-awake thread, if running, and wait for it to exit
+    pub fn stop_keep_awake(&self) -> Result<()> {
+        match self.keep_awake.lock().unwrap().take() {
+            Some(keep_awake) => keep_awake.stop(),
+            None => Ok(()),
+        }
+    }
+
+    /// Play an LED animation on a background thread
+    ///
+    /// Frames go to every LED at `ANIMATION_FRAME_INTERVAL` as no-ack
+    /// writes, so foreground commands and `start_keep_awake` keep working
+    /// alongside. Finite animations end on their last frame; `blink` runs
+    /// until `stop_animation`, another `play_animation`, or `shutdown`.
+    /// Calling it again replaces the running animation.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` for a blink with a zero period
+    pub fn play_animation(&self, animation: Animation) -> Result<()> {
+        if let Animation::Blink { period, .. } = animation {
+            if period.is_zero() {
+                return Err(RvrError::InvalidParameter(
+                    "blink period must be greater than zero".to_string(),
+                ));
+            }
+        }
+
+        let mut current = self.animation.lock().unwrap();
+        if let Some(previous) = current.take() {
+            previous.stop()?;
+        }
+        tracing::debug!("Playing animation {:?}", animation);
+        let mask = self.state().led_capabilities.all_mask();
+        let (target_id, source_id) = (self.target_id, self.source_id);
+        let dispatcher = Arc::clone(&self.dispatcher);
+        *current = Some(animation::play(animation, move |color| {
+            let packet = leds_no_ack_packet(mask, color).with_routing(target_id, source_id);
+            dispatcher.send_packet_no_response(&packet)
+        }));
+        Ok(())
+    }
+
+    /// Stop the animation thread, if running, and wait for it to exit
+    ///
+    /// The LEDs keep the last frame shown.
+    pub fn stop_animation(&self) -> Result<()> {
+        match self.animation.lock().unwrap().take() {
+            Some(player) => player.stop(),
+            None => Ok(()),
+        }
+    }
+
+    /// Shutdown the connection gracefully
+    ///
+    /// This will stop the animation, keep-awake and background RX threads
+    /// and close the serial port. The robot will remain in its current
+    /// state (awake/asleep). The connection is shared, so every clone stops
+    /// working too.
+    ///
+    /// Every step runs even if an earlier one fails; the first error is
+    /// returned.
+    pub fn shutdown(self) -> Result<()> {
+        tracing::debug!("Shutting down SpheroRvr");
+        let animation = self.stop_animation();
+        let keep_awake = self.stop_keep_awake();
+        let dispatcher = self.dispatcher.shutdown();
+        animation.and(keep_awake).and(dispatcher)
+    }
+
+    // === Helper Methods ===
+
+    /// Brake at the end of a drive loop, even if the loop failed part way
+    ///
+    /// The loop's error wins over a failed brake, which is only logged.
+    pub(crate) fn brake_after(&self, outcome: Result<()>) -> Result<()> {
+        let stopped = self.stop(true);
+        match outcome {
+            Ok(()) => stopped,
+            Err(e) => {
+                if let Err(stop_err) = stopped {
+                    tracing::warn!("Failed to brake after error: {}", stop_err);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Lock the shared client state
+    fn state(&self) -> MutexGuard<'_, ClientState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Build a command packet with standard flags for UART board-to-board communication
+    ///
+    /// When communicating over the RVR's external UART expansion port, the internal
+    /// routing mesh requires explicit source and target node IDs:
+    /// - Target: Primary processor (Nordic MCU)
+    /// - Source: UART expansion port
+    ///
+    /// Without these, the internal router may drop packets or return routing errors.
+    /// Both IDs come from `RvrConfig`.
+    fn build_command(&self, device_id: u8, command_id: u8, payload: Vec<u8>) -> Packet {
+        self.routed(command::command(device_id, command_id, payload))
+    }
+
+    /// Address a packet with the configured routing IDs
+    fn routed(&self, packet: Packet) -> Packet {
+        packet.with_routing(self.target_id, self.source_id)
+    }
+
+    /// Send a typed command with its own timeout and timeout policy
+    fn send_typed(&self, command: &Command, timeout: std::time::Duration) -> Result<()> {
+        let packet = self.routed(command.to_packet());
+        let sent = self.dispatcher.send_command_timeout(packet, timeout);
+        tolerate_timeout(sent, command.timeout_policy(), command).map(|_| ())
+    }
+
+    /// Send a query whose command may be missing on older firmware
+    ///
+    /// Returns `Ok(None)` if the robot reports the command as not implemented
+    /// or unknown; any other error is surfaced as usual.
+    fn optional_query(&self, device_id: u8, command_id: u8) -> Result<Option<Packet>> {
+        self.optional_query_with(device_id, command_id, vec![])
+    }
+
+    /// `optional_query` with a request payload
+    fn optional_query_with(
+        &self,
+        device_id: u8,
+        command_id: u8,
+        payload: Vec<u8>,
+    ) -> Result<Option<Packet>> {
+        let packet = self.build_command(device_id, command_id, payload);
+        let response = self.dispatcher.send_command(packet)?;
+
+        if is_unsupported_command(&response) {
+            tracing::debug!(
+                "Command dev={:#04x} cmd={:#04x} not supported by firmware, skipping",
+                device_id,
+                command_id
+            );
+            return Ok(None);
+        }
+
+        self.check_response(&response)?;
+        Ok(Some(response))
+    }
+
+    /// Check if a response indicates success or error
+    fn check_response(&self, response: &Packet) -> Result<()> {
+        check_response_code(response)
+    }
+}
+
+/// Apply a timeout policy to the outcome of sending `command`
+///
+/// Responses are checked for error codes; a timeout (or `RobotAsleep`,
+/// which is how a timeout is reported once the robot announced sleep)
+/// becomes `Ok(None)` under `TimeoutPolicy::Success`.
+fn tolerate_timeout(
+    sent: Result<Packet>,
+    policy: TimeoutPolicy,
+    command: &Command,
+) -> Result<Option<Packet>> {
+    match sent {
+        Ok(response) => {
+            check_response_code(&response)?;
+            Ok(Some(response))
+        }
+        Err(RvrError::Timeout | RvrError::RobotAsleep) if policy == TimeoutPolicy::Success => {
+            tracing::debug!("No response to {:?}; treating as success", command);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// === Payload Encoding / Decoding ===
+
+/// Map a response's error code to `Ok` or the corresponding error
+fn check_response_code(response: &Packet) -> Result<()> {
+    // Response payload format: [ERROR_CODE, ...]
+    // If payload is empty, assume success
+    if response.payload.is_empty() {
+        return Ok(());
+    }
+
+    let error_code = response.payload[0];
+
+    match error_code {
+        error_code::SUCCESS => Ok(()),
+        error_code::BAD_TARGET_ID | error_code::TARGET_UNAVAILABLE => Err(
+            RvrError::InvalidResponse(routing_error_message(response, error_code)),
+        ),
+        code => Err(device_error(code)),
+    }
+}
+
+/// The `DeviceError` for a device-level error code
+fn device_error(code: u8) -> RvrError {
+    RvrError::DeviceError {
+        code,
+        name: device_error_name(code),
+    }
+}
+
+/// Human-readable name of a device-level error code
+fn device_error_name(code: u8) -> &'static str {
+    match code {
+        error_code::BAD_DEVICE_ID => "Bad device ID",
+        error_code::BAD_COMMAND_ID => "Bad command ID",
+        error_code::NOT_YET_IMPLEMENTED => "Command not yet implemented",
+        error_code::RESTRICTED => "Command is restricted",
+        error_code::BAD_DATA_LENGTH => "Bad data length",
+        error_code::FAILED => "Command failed",
+        error_code::BAD_PARAMETER_VALUE => "Bad parameter value",
+        error_code::BUSY => "Device is busy",
+        _ => "Unknown error",
+    }
+}
+
+/// Describe a router-level failure, naming the nodes involved
+///
+/// The router answers from the node it rejected, so the response's source
+/// is the unreachable node and its target is us.
+fn routing_error_message(response: &Packet, code: u8) -> String {
+    let reason = match code {
+        error_code::BAD_TARGET_ID => "Bad target ID",
+        _ => "Target unavailable",
+    };
+    let node = |id: Option<u8>| id.map_or("none".to_string(), |id| format!("{:#04x}", id));
+
+    format!(
+        "{} (dev={:#04x} cmd={:#04x}, responding node={}, target={})",
+        reason,
+        response.device_id,
+        response.command_id,
+        node(response.source_id),
+        node(response.target_id)
+    )
+}
+
+/// Get the data portion of a response (payload after the error code byte)
+fn response_data(response: &Packet) -> &[u8] {
+    response.payload.get(1..).unwrap_or(&[])
+}
+
+/// Get the first data byte of a response, or fail with a descriptive error
+fn first_data_byte(response: &Packet, what: &str) -> Result<u8> {
+    response_data(response)
+        .first()
+        .copied()
+        .ok_or_else(|| RvrError::InvalidResponse(format!("{} response has no payload", what)))
+}
+
+/// Decode the battery percentage response data: `[PERCENTAGE]`
+fn decode_battery_percentage(data: &[u8]) -> Result<BatteryState> {
+    let percentage = *data
+        .first()
+        .ok_or_else(|| RvrError::InvalidResponse("Battery response has no payload".to_string()))?;
+    Ok(BatteryState { percentage })
+}
+
+/// Encode an on/off toggle: `[ENABLED]` (0x00 = off, 0x01 = on)
+fn toggle_payload(enabled: bool) -> Vec<u8> {
+    vec![enabled as u8]
+}
+
+/// LED packet that asks for no response, for `set_all_leds_no_ack` and animations
+fn leds_no_ack_packet(mask: LedMask, color: Color) -> Packet {
+    Command::SetLeds { mask, color }
+        .to_packet()
+        .with_requests_response(false)
+}
+
+/// Clamp a drive speed (0-255) to the configured maximum
+fn clamp_speed(speed: u8, max_speed: u8) -> u8 {
+    speed.min(max_speed)
+}
+
+/// Clamp a wheel velocity (cm/s) to the velocity equivalent of the maximum speed
+fn clamp_velocity(velocity_cm_s: f32, max_speed: u8) -> f32 {
+    let limit = MAX_WHEEL_VELOCITY_CM_S * max_speed as f32 / u8::MAX as f32;
+    velocity_cm_s.clamp(-limit, limit)
+}
+
+/// Encode raw motors: `[LEFT_MODE] [LEFT_SPEED] [RIGHT_MODE] [RIGHT_SPEED]`
+///
+/// Rejects a motor that is off but given a speed.
+fn raw_motors_payload(
+    left_mode: MotorMode,
+    left_speed: u8,
+    right_mode: MotorMode,
+    right_speed: u8,
+) -> Result<Vec<u8>> {
+    for (side, mode, speed) in [
+        ("left", left_mode, left_speed),
+        ("right", right_mode, right_speed),
+    ] {
+        if mode == MotorMode::Off && speed != 0 {
+            return Err(RvrError::InvalidParameter(format!(
+                "{} motor is off but has speed {}",
+                side, speed
+            )));
+        }
+    }
+
+    Ok(vec![
+        left_mode.to_byte(),
+        left_speed,
+        right_mode.to_byte(),
+        right_speed,
+    ])
+}
+
+/// Whether a notification reports the end of compass calibration
+fn is_calibration_finished(packet: &Packet) -> bool {
+    CompassCalibrationEvent::from_packet(packet).is_some_and(|event| event.is_finished())
+}
+
+/// Map a finished compass calibration notification to a result
+fn calibration_result(packet: &Packet) -> Result<()> {
+    match CompassCalibrationEvent::from_packet(packet) {
+        Some(CompassCalibrationEvent::Complete) => {
+            tracing::debug!("Compass calibration complete");
+            Ok(())
+        }
+        Some(CompassCalibrationEvent::Failed) => {
+            tracing::warn!("Compass calibration failed");
+            Err(device_error(error_code::FAILED))
+        }
+        _ => Err(RvrError::InvalidResponse(
+            "Expected a compass calibration result notification".to_string(),
+        )),
+    }
+}
+
+/// Decode a single big-endian f32 from response data
+fn decode_f32(data: &[u8], what: &str) -> Result<f32> {
+    match data {
+        [a, b, c, d, ..] => Ok(f32::from_be_bytes([*a, *b, *c, *d])),
+        _ => Err(RvrError::InvalidResponse(format!(
+            "{} response too short: {} bytes (expected 4)",
+            what,
+            data.len()
+        ))),
+    }
+}
+
+/// Remaining runtime from battery percentage and current draw
+///
+/// Returns `None` if the current is too small to give a meaningful estimate.
+fn runtime_estimate(percentage: u8, current_amps: f32) -> Option<std::time::Duration> {
+    if current_amps.is_nan() || current_amps < MIN_ESTIMATE_CURRENT_A {
+        return None;
+    }
+
+    let remaining_ah = BATTERY_CAPACITY_MAH / 1000.0 * f32::from(percentage.min(100)) / 100.0;
+    let hours = remaining_ah / current_amps;
+    Some(std::time::Duration::from_secs_f32(hours * 3600.0))
+}
+
+/// Decode a color response: `[RED] [GREEN] [BLUE]`
+fn decode_color(data: &[u8]) -> Result<Color> {
+    match data {
+        [r, g, b, ..] => Ok(Color::new(*r, *g, *b)),
+        _ => Err(RvrError::InvalidResponse(format!(
+            "Color response too short: {} bytes",
+            data.len()
+        ))),
+    }
+}
+
+/// Encode wheel velocities: `[LEFT: f32 BE] [RIGHT: f32 BE]`
+///
+/// The firmware expects SI units (m/s), so cm/s inputs are scaled by 1/100.
+fn wheel_velocities_payload(left_cm_s: f32, right_cm_s: f32) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(8);
+    payload.extend_from_slice(&(left_cm_s / 100.0).to_be_bytes());
+    payload.extend_from_slice(&(right_cm_s / 100.0).to_be_bytes());
+    payload
+}
+
+/// Clamp a control input to -1.0..=1.0, treating NaN as no input
+fn normalize_control(value: f32) -> f32 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(-1.0, 1.0)
+    }
+}
+
+/// Encode SI-unit RC drive: `[YAW: f32 BE] [LINEAR: f32 BE] [FLAGS]`
+///
+/// Yaw rate is in degrees per second, linear velocity in m/s. No flags are set.
+fn rc_si_units_payload(yaw_deg_s: f32, linear_m_s: f32) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(9);
+    payload.extend_from_slice(&yaw_deg_s.to_be_bytes());
+    payload.extend_from_slice(&linear_m_s.to_be_bytes());
+    payload.push(0x00);
+    payload
+}
+
+/// Encode normalized RC drive: `[YAW: i8] [LINEAR: i8] [FLAGS]`
+///
+/// Inputs are in -1.0..=1.0 and are scaled to -127..=127. No flags are set.
+fn rc_normalized_payload(yaw: f32, linear: f32) -> Vec<u8> {
+    let scale = |value: f32| (value * i8::MAX as f32).round() as i8 as u8;
+    vec![scale(yaw), scale(linear), 0x00]
+}
+
+/// Decode the device timestamp response data: `[MILLISECONDS: u64 BE]`
+fn decode_device_timestamp(data: &[u8]) -> Result<u64> {
+    let bytes: [u8; 8] = data
+        .get(..8)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| {
+            RvrError::InvalidResponse(format!(
+                "Device timestamp response too short: {} bytes (expected 8)",
+                data.len()
+            ))
+        })?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// State of a `drive_distance` move after a locator update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DriveProgress {
+    /// Still short of the target and making progress
+    Moving,
+    /// Target distance covered
+    Reached,
+    /// No progress within `DRIVE_STALL_TIMEOUT`
+    Stalled,
+}
+
+/// Tracks distance covered from a start position for `drive_distance`
+#[derive(Debug)]
+struct DistanceTracker {
+    start: Locator,
+    target_cm: f32,
+    /// Furthest distance from the start seen so far
+    best_cm: f32,
+    /// When `best_cm` last grew by at least `MIN_DRIVE_PROGRESS_CM`
+    last_progress: std::time::Instant,
+    /// `best_cm` at `last_progress`
+    progress_mark_cm: f32,
+}
+
+impl DistanceTracker {
+    fn new(start: Locator, target_cm: f32, now: std::time::Instant) -> Self {
+        Self {
+            start,
+            target_cm,
+            best_cm: 0.0,
+            last_progress: now,
+            progress_mark_cm: 0.0,
+        }
+    }
+
+    /// Feed a locator reading taken at `now`
+    fn update(&mut self, position: Locator, now: std::time::Instant) -> DriveProgress {
+        let covered = self.start.distance_to(position) * 100.0;
+        self.best_cm = self.best_cm.max(covered);
+
+        if self.best_cm >= self.target_cm {
+            return DriveProgress::Reached;
+        }
+        if self.best_cm - self.progress_mark_cm >= MIN_DRIVE_PROGRESS_CM {
+            self.progress_mark_cm = self.best_cm;
+            self.last_progress = now;
+        }
+        if now.saturating_duration_since(self.last_progress) >= DRIVE_STALL_TIMEOUT {
+            DriveProgress::Stalled
+        } else {
+            DriveProgress::Moving
+        }
+    }
+}
+
+/// Reject streaming intervals faster than the RVR can sample
+fn check_streaming_interval(interval_ms: u16) -> Result<()> {
+    if interval_ms < MIN_STREAMING_INTERVAL_MS {
+        return Err(RvrError::InvalidParameter(format!(
+            "streaming interval must be at least {} ms, got {}",
+            MIN_STREAMING_INTERVAL_MS, interval_ms
+        )));
+    }
+    Ok(())
+}
+
+/// Scale a streamed quaternion `[W, X, Y, Z]` to unit length
+///
+/// Stream samples are quantized, so they are only approximately unit length.
+fn normalize_quaternion(q: [f32; 4]) -> Result<[f32; 4]> {
+    let norm = q.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if !norm.is_normal() {
+        return Err(RvrError::InvalidResponse(format!(
+            "Quaternion has invalid norm: {}",
+            norm
+        )));
+    }
+
+    Ok(q.map(|v| v / norm))
+}
+
+/// Check whether a response reports the command as unsupported by the firmware
+fn is_unsupported_command(response: &Packet) -> bool {
+    matches!(
+        response.payload.first(),
+        Some(&error_code::NOT_YET_IMPLEMENTED) | Some(&error_code::BAD_COMMAND_ID)
+    )
+}
+
+/// Decode the API protocol version response data: `[MAJOR] [MINOR]`
+fn decode_api_protocol_version(data: &[u8]) -> Result<(u8, u8)> {
+    match data {
+        [major, minor, ..] => Ok((*major, *minor)),
+        _ => Err(RvrError::InvalidResponse(format!(
+            "API protocol version response too short: {} bytes (expected 2)",
+            data.len()
+        ))),
+    }
+}
+
+/// Decode the firmware version response data
+///
+/// Three big-endian u16 fields:
+///
+/// ```text
+/// [MAJOR: u16] [MINOR: u16] [REVISION: u16]
+/// ```
+fn decode_firmware_version(data: &[u8]) -> Result<FirmwareVersion> {
+    if data.len() < 6 {
+        return Err(RvrError::InvalidResponse(format!(
+            "Firmware version response too short: {} bytes (expected 6)",
+            data.len()
+        )));
+    }
+
+    let field = |i: usize| u16::from_be_bytes([data[i * 2], data[i * 2 + 1]]);
+
+    Ok(FirmwareVersion {
+        major: field(0),
+        minor: field(1),
+        patch: field(2),
+    })
+}
+
+/// Nonce for `ping`, varying between calls so stale echoes do not match
+fn ping_nonce() -> [u8; 4] {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos()
+        .to_be_bytes()
+}
+
+/// Check an echo reply carries exactly what was sent
+fn verify_echo(sent: &[u8], echoed: &[u8]) -> Result<()> {
+    if echoed != sent {
+        return Err(RvrError::InvalidResponse(format!(
+            "Echo mismatch: sent {:02X?}, received {:02X?}",
+            sent, echoed
+        )));
+    }
+    Ok(())
+}
+
+/// Format a 6-byte MAC address response as `AA:BB:CC:DD:EE:FF`
+fn format_mac_address(data: &[u8]) -> Result<String> {
+    if data.len() != 6 {
+        return Err(RvrError::InvalidResponse(format!(
+            "MAC address response has {} bytes (expected 6)",
+            data.len()
+        )));
+    }
+
+    Ok(data
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+/// Send `ping` every `interval` on a background thread until stopped
+fn keep_awake(dispatcher: Arc<Dispatcher>, interval: std::time::Duration, ping: Packet) -> Worker {
+    Worker::spawn("keep-awake", move |stop| {
+        while stop.wait(interval) {
+            if let Err(e) = dispatcher.send_command(ping.clone()) {
+                tracing::warn!("Keep-awake ping failed: {}", e);
+            }
+        }
+    })
+}
+
+/// Puts the robot to sleep when dropped unless already disarmed
+///
+/// Used by `SpheroRvr::with_awake` so that a panicking closure still leaves
+/// the robot asleep.
+struct SleepGuard<'a> {
+    rvr: &'a SpheroRvr,
+    armed: bool,
+}
+
+impl SleepGuard<'_> {
+    /// Send sleep now and disarm the guard
+    fn sleep_now(&mut self) -> Result<()> {
+        self.armed = false;
+        self.rvr.sleep()
+    }
+}
+
+impl Drop for SleepGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            // Best-effort sleep while unwinding
+            if let Err(e) = self.rvr.sleep() {
+                tracing::warn!("Failed to sleep robot during unwind: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::command::drive_with_heading_payload;
+    use crate::api::events::tests::calibration_notification;
+    use crate::api::navigation::tests::{assert_braked, streaming_locator};
+    use crate::transport::{VirtualRvr, VirtualRvrHandle};
+
+    /// A client on a fresh `VirtualRvr`, with a handle to inspect the robot
+    pub(crate) fn virtual_rvr() -> (SpheroRvr, VirtualRvrHandle) {
+        let robot = VirtualRvr::new();
+        let handle = robot.handle();
+        (SpheroRvr::from_port(Box::new(robot)), handle)
+    }
+
+    #[test]
+    fn test_build_command() {
+        let dispatcher = Dispatcher::new("/dev/null", 115200);
+        // Skip test if serial port can't open (expected on most systems)
+        if dispatcher.is_err() {
+            return;
+        }
+
+        let rvr = SpheroRvr::with_dispatcher(dispatcher.unwrap());
+
+        let packet = rvr.build_command(device::POWER, power_command::WAKE, vec![]);
+
+        assert_eq!(packet.device_id, device::POWER);
+        assert_eq!(packet.command_id, power_command::WAKE);
+        assert!(!packet.flags.is_response);
+        assert!(packet.flags.requests_response);
+        assert!(packet.payload.is_empty());
+
+        // Verify UART routing fields are set
+        assert!(packet.flags.has_target_id);
+        assert!(packet.flags.has_source_id);
+        assert_eq!(packet.target_id, Some(routing_node::PRIMARY_PROCESSOR));
+        assert_eq!(packet.source_id, Some(routing_node::UART_PORT));
+    }
+
+    #[test]
+    fn test_config_routing_applies_to_every_command() {
+        use crate::transport::MockTransport;
+
+        let config = RvrConfig {
+            target_id: 0x11,
+            source_id: 0x03,
+            ..RvrConfig::default()
+        };
+        assert_eq!(
+            RvrConfig::default().target_id,
+            routing_node::PRIMARY_PROCESSOR
+        );
+
+        let transport = MockTransport::new();
+        let handle = transport.handle();
+        let rvr =
+            SpheroRvr::from_transport(transport).with_routing(config.target_id, config.source_id);
+
+        for packet in [
+            rvr.build_command(device::POWER, power_command::WAKE, vec![]),
+            rvr.routed(Command::Stop { brake: true }.to_packet()),
+        ] {
+            assert_eq!(packet.target_id, Some(0x11));
+            assert_eq!(packet.source_id, Some(0x03));
+        }
+
+        // Packets built from a Command on the way out are routed too
+        rvr.set_all_leds_no_ack(Color::RED).unwrap();
+        let mut parser = crate::protocol::parser::SpheroParser::new();
+        let sent = handle
+            .take_written()
+            .into_iter()
+            .find_map(|byte| parser.feed(byte).unwrap())
+            .unwrap();
+        assert_eq!((sent.target_id, sent.source_id), (Some(0x11), Some(0x03)));
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_sleep_without_ack_succeeds() {
+        use crate::transport::MockTransport;
+
+        // Nothing ever answers, as when the robot drops the UART mid-sleep
+        let rvr = SpheroRvr::from_transport(MockTransport::new());
+        rvr.state().awake = Some(true);
+        rvr.sleep().unwrap();
+        assert_eq!(rvr.state().awake, Some(false));
+
+        rvr.shutdown().unwrap();
+
+        // Commands whose policy is Error still time out
+        assert!(matches!(
+            tolerate_timeout(Err(RvrError::Timeout), TimeoutPolicy::Error, &Command::Wake),
+            Err(RvrError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn test_timeout_policy_still_checks_responses() {
+        let (rvr, _) = virtual_rvr();
+
+        let response = rvr
+            .send_with_policy(&Command::Echo(vec![7]), TimeoutPolicy::Success)
+            .unwrap();
+        assert_eq!(response.map(|packet| packet.payload), Some(vec![0x00, 7]));
+
+        let mut failed = Packet::new_command(device::POWER, power_command::SLEEP, 0, vec![]);
+        failed.payload = vec![error_code::FAILED];
+        assert!(matches!(
+            tolerate_timeout(Ok(failed), TimeoutPolicy::Success, &Command::Sleep),
+            Err(RvrError::DeviceError {
+                code: error_code::FAILED,
+                ..
+            })
+        ));
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_with_awake_returns_closure_error() {
+        let (rvr, handle) = virtual_rvr();
+
+        let result: Result<()> =
+            rvr.with_awake(|_| Err(RvrError::Protocol("closure failed".to_string())));
+
+        assert!(matches!(result, Err(RvrError::Protocol(ref msg)) if msg == "closure failed"));
+        // The robot is still put back to sleep
+        assert_eq!(
+            handle.commands().last(),
+            Some(&(device::POWER, power_command::SLEEP))
+        );
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_toggle_payload() {
+        assert_eq!(toggle_payload(true), vec![0x01]);
+        assert_eq!(toggle_payload(false), vec![0x00]);
+    }
+
+    #[test]
+    fn test_rc_si_units_payload() {
+        // Holding position is zero yaw and zero linear velocity, no flags
+        assert_eq!(rc_si_units_payload(0.0, 0.0), vec![0x00; 9]);
+
+        // 90 deg/s = 0x42B40000, -0.5 m/s = 0xBF000000
+        assert_eq!(
+            rc_si_units_payload(90.0, -0.5),
+            vec![0x42, 0xB4, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_drive_with_heading_payload() {
+        let payload = drive_with_heading_payload(128, 270, drive_flags::REVERSE);
+        assert_eq!(payload, vec![128, 0x01, 0x0E, 0x01]);
+    }
+
+    #[test]
+    fn test_raw_motors_payload() {
+        let payload = raw_motors_payload(MotorMode::Forward, 200, MotorMode::Reverse, 100).unwrap();
+        assert_eq!(payload, vec![1, 200, 2, 100]);
+
+        let payload = raw_motors_payload(MotorMode::Off, 0, MotorMode::Off, 0).unwrap();
+        assert_eq!(payload, vec![0, 0, 0, 0]);
+
+        // An off motor cannot have a speed
+        assert!(matches!(
+            raw_motors_payload(MotorMode::Off, 10, MotorMode::Forward, 10),
+            Err(RvrError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            raw_motors_payload(MotorMode::Forward, 10, MotorMode::Off, 1),
+            Err(RvrError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_speed_limit_clamps_drive_speed() {
+        let (rvr, handle) = virtual_rvr();
+        rvr.set_speed_limit(100);
+
+        rvr.drive_with_heading(255, 0, 0).unwrap();
+        // Speeds under the limit pass through untouched
+        rvr.drive_with_heading(50, 0, 0).unwrap();
+        rvr.stop(true).unwrap();
+
+        let speeds: Vec<u8> = handle
+            .packets()
+            .iter()
+            .filter(|packet| packet.command_id == drive_command::DRIVE_WITH_HEADING)
+            .map(|packet| packet.payload[0])
+            .collect();
+        assert_eq!(speeds, vec![100, 50]);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_speed_limit_clamps_velocity() {
+        let limit = MAX_WHEEL_VELOCITY_CM_S * 100.0 / 255.0;
+
+        assert!((clamp_velocity(1000.0, 100) - limit).abs() < 1e-4);
+        assert!((clamp_velocity(-1000.0, 100) + limit).abs() < 1e-4);
+        assert_eq!(clamp_velocity(10.0, 100), 10.0);
+
+        // No limit allows the full range
+        assert_eq!(
+            clamp_velocity(MAX_WHEEL_VELOCITY_CM_S, u8::MAX),
+            MAX_WHEEL_VELOCITY_CM_S
+        );
+    }
+
+    #[test]
+    fn test_wheel_velocities_payload() {
+        // 100 cm/s = 1.0 m/s = 0x3F800000, -50 cm/s = -0.5 m/s = 0xBF000000
+        let payload = wheel_velocities_payload(100.0, -50.0);
+        assert_eq!(
+            payload,
+            vec![0x3F, 0x80, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x00]
+        );
+
+        // Zero velocity encodes as all zeros
+        assert_eq!(wheel_velocities_payload(0.0, 0.0), vec![0; 8]);
+    }
+
+    #[test]
+    fn test_rc_normalized_payload() {
+        // Yaw first: -1.0 = -127 (0x81), 0.5 = 64 (0x40), no flags
+        assert_eq!(rc_normalized_payload(-1.0, 0.5), vec![0x81, 0x40, 0x00]);
+        assert_eq!(rc_normalized_payload(1.0, 0.0), vec![0x7F, 0x00, 0x00]);
+
+        // Inputs are clamped to the normalized range
+        assert_eq!(normalize_control(3.0), 1.0);
+        assert_eq!(normalize_control(-1.5), -1.0);
+        assert_eq!(normalize_control(0.25), 0.25);
+        assert_eq!(normalize_control(f32::NAN), 0.0);
+    }
+
+    #[test]
+    fn test_routing_error_names_nodes() {
+        // Router response: from the unreachable node (0x03) back to UART
+        let mut response = command::command(device::DRIVE, drive_command::STOP, vec![]);
+        response.flags.is_response = true;
+        response.target_id = Some(routing_node::UART_PORT);
+        response.source_id = Some(0x03);
+        response.payload = vec![error_code::TARGET_UNAVAILABLE];
+
+        let err = check_response_code(&response).unwrap_err().to_string();
+        assert!(err.contains("Target unavailable"), "{}", err);
+        assert!(err.contains("responding node=0x03"), "{}", err);
+        assert!(err.contains("target=0x02"), "{}", err);
+        assert!(err.contains("dev=0x16"), "{}", err);
+
+        // Missing routing ids are reported as such
+        response.payload = vec![error_code::BAD_TARGET_ID];
+        response.target_id = None;
+        response.source_id = None;
+        let err = check_response_code(&response).unwrap_err().to_string();
+        assert!(err.contains("Bad target ID"), "{}", err);
+        assert!(err.contains("responding node=none"), "{}", err);
+    }
+
+    #[test]
+    fn test_check_response_code() {
+        let mut response = command::command(device::POWER, power_command::WAKE, vec![]);
+        assert!(check_response_code(&response).is_ok());
+
+        response.payload = vec![error_code::SUCCESS, 0x42];
+        assert!(check_response_code(&response).is_ok());
+
+        // Every failure code is a device error carrying its code, so BUSY
+        // can be told apart
+        response.payload = vec![error_code::FAILED];
+        assert!(matches!(
+            check_response_code(&response),
+            Err(RvrError::DeviceError {
+                code: error_code::FAILED,
+                name: "Command failed"
+            })
+        ));
+        response.payload = vec![error_code::BUSY];
+        assert!(matches!(
+            check_response_code(&response),
+            Err(RvrError::DeviceError {
+                code: error_code::BUSY,
+                ..
+            })
+        ));
+        response.payload = vec![error_code::BAD_COMMAND_ID];
+        let err = check_response_code(&response).unwrap_err();
+        assert!(matches!(
+            err,
+            RvrError::DeviceError {
+                code: error_code::BAD_COMMAND_ID,
+                name: "Bad command ID"
+            }
+        ));
+        assert_eq!(err.to_string(), "Device error 0x02: Bad command ID");
+
+        // Codes this crate does not know are still device errors
+        response.payload = vec![0x42];
+        assert!(matches!(
+            check_response_code(&response),
+            Err(RvrError::DeviceError {
+                code: 0x42,
+                name: "Unknown error"
+            })
+        ));
+    }
+
+    #[test]
+    fn test_drive_with_heading_frame_flags() {
+        // Wire frame for drive_with_heading(speed=128, heading=180,
+        // flags=REVERSE|BOOST), seq 5, routed UART -> primary processor:
+        //   8D            SOP
+        //   32 01 02      flags (requests response, has target/source), target, source
+        //   16 07 05      DRIVE, DRIVE_WITH_HEADING, seq
+        //   80 00 B4 03   speed=128, heading=0x00B4 (BE), drive flags
+        //   71 D8         checksum, EOP
+        let frame = "8D 32 01 02 16 07 05 80 00 B4 03 71 D8";
+        let packet = Packet::from_hex_capture(frame).unwrap();
+
+        assert_eq!(packet.device_id, device::DRIVE);
+        assert_eq!(packet.command_id, drive_command::DRIVE_WITH_HEADING);
+        assert_eq!(
+            packet.payload,
+            drive_with_heading_payload(128, 180, drive_flags::REVERSE | drive_flags::BOOST)
+        );
+
+        let flags = DriveFlags::from_byte(packet.payload[3]);
+        assert_eq!(
+            flags,
+            DriveFlags {
+                reverse: true,
+                boost: true,
+                ..Default::default()
+            }
+        );
+
+        // Our encoding reproduces the exact frame
+        let mut rebuilt = command::command(
+            device::DRIVE,
+            drive_command::DRIVE_WITH_HEADING,
+            drive_with_heading_payload(128, 180, flags.to_byte()),
+        );
+        rebuilt.sequence_number = 5;
+        assert_eq!(rebuilt.to_hex_string(), frame);
+    }
+
+    #[test]
+    fn test_drive_with_heading_sends_flags_unchanged() {
+        let (rvr, handle) = virtual_rvr();
+
+        // Reserved bits 6-7 go out as given
+        rvr.drive_with_heading(0, 90, 0xC0 | drive_flags::REVERSE)
+            .unwrap();
+        let sent = handle.packets().pop().unwrap();
+        assert_eq!(sent.payload, drive_with_heading_payload(0, 90, 0xC1));
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_calibration_waits_past_progress_for_result() {
+        use crate::transport::dispatcher::{NotificationSubscription, NotificationWaiters};
+        use sensor_command::{
+            MAGNETOMETER_CALIBRATION_COMPLETE_NOTIFY as COMPLETE,
+            MAGNETOMETER_CALIBRATION_PROGRESS_NOTIFY as PROGRESS,
+        };
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let waiters = Arc::new(Mutex::new(NotificationWaiters::default()));
+        let finished = NotificationSubscription::new(&waiters, Box::new(is_calibration_finished));
+
+        let injector = {
+            let waiters = Arc::clone(&waiters);
+            std::thread::spawn(move || {
+                for (cmd, state) in [(PROGRESS, 0), (PROGRESS, 1), (COMPLETE, 1)] {
+                    std::thread::sleep(Duration::from_millis(5));
+                    waiters
+                        .lock()
+                        .unwrap()
+                        .dispatch(&calibration_notification(cmd, state));
+                }
+            })
+        };
+
+        let packet = finished.wait(Duration::from_secs(1)).unwrap();
+        injector.join().unwrap();
+
+        assert_eq!(packet.command_id, COMPLETE);
+        assert!(calibration_result(&packet).is_ok());
+    }
+
+    #[test]
+    fn test_calibration_result() {
+        use sensor_command::MAGNETOMETER_CALIBRATION_COMPLETE_NOTIFY as COMPLETE;
+
+        assert!(calibration_result(&calibration_notification(COMPLETE, 1)).is_ok());
+        assert!(matches!(
+            calibration_result(&calibration_notification(COMPLETE, 0)),
+            Err(RvrError::DeviceError {
+                code: error_code::FAILED,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_runtime_estimate() {
+        use std::time::Duration;
+
+        // 50% of 2.5 Ah = 1.25 Ah at 2.5 A = 30 minutes
+        let estimate = runtime_estimate(50, 2.5).unwrap();
+        let expected = Duration::from_secs(30 * 60);
+        assert!(
+            estimate.abs_diff(expected) < Duration::from_secs(1),
+            "{:?}",
+            estimate
+        );
+
+        // Full battery at 1 A = 2.5 hours
+        let estimate = runtime_estimate(100, 1.0).unwrap();
+        assert!(estimate.abs_diff(Duration::from_secs(9000)) < Duration::from_secs(1));
+
+        // Empty battery has no runtime left
+        assert_eq!(runtime_estimate(0, 1.0), Some(Duration::ZERO));
+
+        // Idle or invalid current gives no estimate
+        assert_eq!(runtime_estimate(80, 0.0), None);
+        assert_eq!(runtime_estimate(80, f32::NAN), None);
+    }
+
+    #[test]
+    fn test_decode_f32() {
+        assert_eq!(
+            decode_f32(&[0x3F, 0xC0, 0x00, 0x00], "Current").unwrap(),
+            1.5
+        );
+        assert!(decode_f32(&[0x3F, 0xC0], "Current").is_err());
+    }
+
+    #[test]
+    fn test_normalize_quaternion() {
+        assert_eq!(
+            normalize_quaternion([1.0, 0.0, 0.0, 0.0]).unwrap(),
+            [1.0, 0.0, 0.0, 0.0]
+        );
+
+        let q = normalize_quaternion([2.0, 0.0, 2.0, 0.0]).unwrap();
+        let norm = q.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-6);
+        assert!((q[0] - q[2]).abs() < 1e-6);
+
+        // Zero-length quaternion is rejected
+        assert!(normalize_quaternion([0.0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_distance_tracker_reaches_target() {
+        let t0 = std::time::Instant::now();
+        let step = std::time::Duration::from_millis(50);
+        let at = |x, y| Locator { x, y };
+        let mut tracker = DistanceTracker::new(at(0.10, 0.10), 30.0, t0);
+
+        // Locator updates along a diagonal heading
+        assert_eq!(
+            tracker.update(at(0.16, 0.18), t0 + step),
+            DriveProgress::Moving
+        );
+        assert_eq!(
+            tracker.update(at(0.22, 0.26), t0 + step * 2),
+            DriveProgress::Moving
+        );
+        assert_eq!(
+            tracker.update(at(0.31, 0.38), t0 + step * 3),
+            DriveProgress::Reached
+        );
+    }
+
+    #[test]
+    fn test_distance_tracker_detects_stall() {
+        let t0 = std::time::Instant::now();
+        let at = |x| Locator { x, y: 0.0 };
+        let mut tracker = DistanceTracker::new(at(0.0), 100.0, t0);
+
+        let stuck_at = t0 + std::time::Duration::from_millis(50);
+        assert_eq!(tracker.update(at(0.05), stuck_at), DriveProgress::Moving);
+        // Jitter below the progress threshold does not count as moving
+        assert_eq!(
+            tracker.update(at(0.052), stuck_at + DRIVE_STALL_TIMEOUT / 2),
+            DriveProgress::Moving
+        );
+        assert_eq!(
+            tracker.update(at(0.053), stuck_at + DRIVE_STALL_TIMEOUT),
+            DriveProgress::Stalled
+        );
+    }
+
+    #[test]
+    fn test_drive_distance_brakes_at_target() {
+        let (rvr, handle) = streaming_locator();
+
+        rvr.drive_distance(90, 64, 10.0).unwrap();
+
+        assert_braked(&handle);
+        let position = handle.position();
+        assert!(position.y.abs() < 1e-3);
+        // Stops within a couple of samples of the target
+        assert!((0.10..0.15).contains(&position.x), "{:?}", position);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_drive_distance_brakes_when_stalled() {
+        let (rvr, handle) = streaming_locator();
+
+        assert!(matches!(
+            rvr.drive_distance(0, 0, 10.0),
+            Err(RvrError::Timeout)
+        ));
+        assert_braked(&handle);
+
+        // A failed brake does not hide the stall
+        handle.set_response_code(device::DRIVE, drive_command::STOP, error_code::FAILED);
+        assert!(matches!(
+            rvr.drive_distance(0, 0, 10.0),
+            Err(RvrError::Timeout)
+        ));
+        assert_braked(&handle);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_drive_distance_needs_locator_stream() {
+        let (rvr, handle) = virtual_rvr();
+
+        assert!(matches!(
+            rvr.drive_distance(0, 64, 10.0),
+            Err(RvrError::InvalidParameter(_))
+        ));
+        // Never started driving
+        assert!(handle.commands().is_empty());
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_decode_firmware_version() {
+        let data = [0x00, 0x07, 0x00, 0x02, 0x00, 0x1F];
+        let version = decode_firmware_version(&data).unwrap();
+        assert_eq!(version.to_string(), "7.2.31");
+
+        assert!(matches!(
+            decode_firmware_version(&data[..5]),
+            Err(RvrError::InvalidResponse(_))
+        ));
+        // Fields use the full u16 range
+        let version = decode_firmware_version(&[0x01, 0x00, 0, 0, 0x12, 0x34]).unwrap();
+        assert_eq!(version.to_string(), "256.0.4660");
+    }
+
+    #[test]
+    fn test_snapshot_and_restore_state() {
+        let (rvr, handle) = virtual_rvr();
+        rvr.wake().unwrap();
+        rvr.set_leds(LedMask::ALL, Color::BLUE).unwrap();
+        rvr.set_leds(LedMask::BATTERY_DOOR_REAR, Color::RED)
+            .unwrap();
+
+        let saved = rvr.snapshot_state().unwrap();
+        assert_eq!(saved.awake, Some(true));
+        assert_eq!(saved.leds.len(), 6);
+
+        // Flash an alert, then put everything back
+        rvr.set_all_leds(Color::YELLOW).unwrap();
+        let before_restore = handle.commands().len();
+        rvr.restore_state(&saved).unwrap();
+
+        let restore_commands = &handle.commands()[before_restore..];
+        assert_eq!(restore_commands.len(), 6);
+        assert!(restore_commands
+            .iter()
+            .all(|&cmd| cmd == (device::IO, io_command::SET_ALL_LEDS)));
+        for &(position, color) in &saved.leds {
+            assert_eq!(handle.led_color(position), color, "{:?}", position);
+        }
+        assert_eq!(handle.led_color(LedPosition::BatteryDoorRear), Color::RED);
+        assert_eq!(handle.led_color(LedPosition::LeftHeadlight), Color::BLUE);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_restore_state_with_unknown_awake_status() {
+        let (rvr, handle) = virtual_rvr();
+
+        // No wake or sleep has gone through this client
+        let saved = rvr.snapshot_state().unwrap();
+        assert_eq!(saved.awake, None);
+
+        let before_restore = handle.commands().len();
+        rvr.restore_state(&saved).unwrap();
+        assert!(handle.commands()[before_restore..]
+            .iter()
+            .all(|&cmd| cmd == (device::IO, io_command::SET_ALL_LEDS)));
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_set_all_leds_no_ack() {
+        let (rvr, handle) = virtual_rvr();
+
+        rvr.set_all_leds_no_ack(Color::BLUE).unwrap();
+        // The robot applies it even though nothing is awaited
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while handle.led_color(LedPosition::LeftHeadlight) != Color::BLUE {
+            assert!(std::time::Instant::now() < deadline, "LEDs never changed");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_play_animation_alongside_keep_awake() {
+        let (rvr, handle) = virtual_rvr();
+        let duration = std::time::Duration::from_millis(100);
+
+        assert!(rvr
+            .play_animation(Animation::blink(Color::RED, std::time::Duration::ZERO))
+            .is_err());
+
+        rvr.start_keep_awake(std::time::Duration::from_millis(20))
+            .unwrap();
+        rvr.play_animation(Animation::fade(Color::BLACK, Color::GREEN, duration))
+            .unwrap();
+        // Foreground commands still get through while frames are sent
+        rvr.get_battery_percentage().unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while handle.led_color(LedPosition::RightHeadlight) != Color::GREEN {
+            assert!(std::time::Instant::now() < deadline, "fade never finished");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        // shutdown stops an endless animation
+        rvr.play_animation(Animation::blink(Color::RED, duration))
+            .unwrap();
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_set_individual_leds_one_command_per_color() {
+        let (rvr, handle) = virtual_rvr();
+
+        rvr.set_individual_leds(&[
+            (LedPosition::LeftHeadlight, Color::WHITE),
+            (LedPosition::RightHeadlight, Color::WHITE),
+            (LedPosition::BatteryDoorRear, Color::RED),
+        ])
+        .unwrap();
+
+        assert_eq!(handle.commands().len(), 2);
+        assert_eq!(handle.led_color(LedPosition::LeftHeadlight), Color::WHITE);
+        assert_eq!(handle.led_color(LedPosition::RightHeadlight), Color::WHITE);
+        assert_eq!(handle.led_color(LedPosition::BatteryDoorRear), Color::RED);
+        assert_eq!(handle.led_color(LedPosition::LeftStatus), Color::BLACK);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_set_led_gradient_one_command_per_row() {
+        let (rvr, handle) = virtual_rvr();
+
+        rvr.set_led_gradient(Color::WHITE, Color::BLACK).unwrap();
+
+        assert_eq!(handle.commands().len(), 4);
+        assert_eq!(handle.led_color(LedPosition::LeftHeadlight), Color::WHITE);
+        assert_eq!(handle.led_color(LedPosition::RightHeadlight), Color::WHITE);
+        assert_eq!(
+            handle.led_color(LedPosition::LeftStatus),
+            handle.led_color(LedPosition::RightStatus)
+        );
+        assert_eq!(handle.led_color(LedPosition::BatteryDoorRear), Color::BLACK);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_led_capabilities_fall_back_to_stock_layout() {
+        // VirtualRvr does not implement the query, like older firmware
+        let (rvr, handle) = virtual_rvr();
+
+        let caps = rvr.get_led_capabilities().unwrap();
+        assert_eq!(caps, LedCapabilities::default());
+        assert_eq!(caps.led_count as usize, crate::api::led::RVR_LED_COUNT);
+
+        rvr.set_all_leds(Color::RED).unwrap();
+        let sent = handle.packets().pop().unwrap();
+        assert_eq!(
+            sent.payload,
+            led_payload_for(LedMask::ALL.bits(), Color::RED)
+        );
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_get_led_color_reads_back_single_led() {
+        let (rvr, _) = virtual_rvr();
+        rvr.set_leds(LedMask::LEFT_STATUS, Color::GREEN).unwrap();
+
+        assert_eq!(
+            rvr.get_led_color(LedMask::LEFT_STATUS).unwrap(),
+            Color::GREEN
+        );
+        assert_eq!(
+            rvr.get_led_color(LedMask::RIGHT_STATUS).unwrap(),
+            Color::BLACK
+        );
+        assert!(matches!(
+            rvr.get_led_color(LedMask::ALL),
+            Err(RvrError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            rvr.get_led_color(LedMask::EMPTY),
+            Err(RvrError::InvalidParameter(_))
+        ));
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_keep_awake_pings_until_stopped() {
+        let (rvr, handle) = virtual_rvr();
+        let echo = (device::API_AND_SHELL, api_shell_command::ECHO);
+        let pings = || handle.commands().iter().filter(|&&cmd| cmd == echo).count();
+
+        assert!(rvr.start_keep_awake(std::time::Duration::ZERO).is_err());
+        rvr.start_keep_awake(std::time::Duration::from_millis(10))
+            .unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while pings() < 2 {
+            assert!(std::time::Instant::now() < deadline, "no keep-awake pings");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        rvr.stop_keep_awake().unwrap();
+        let stopped_at = pings();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(pings(), stopped_at);
+
+        // shutdown joins a running keep-awake thread
+        rvr.start_keep_awake(std::time::Duration::from_millis(10))
+            .unwrap();
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_continues_past_failed_animation() {
+        let (rvr, _) = virtual_rvr();
+        let other = rvr.clone();
+        rvr.start_keep_awake(std::time::Duration::from_millis(10))
+            .unwrap();
+        *rvr.animation.lock().unwrap() =
+            Some(Worker::spawn("animation", |_| panic!("animation failed")));
+
+        assert!(matches!(rvr.shutdown(), Err(RvrError::Protocol(_))));
+        // The keep-awake thread and the connection were still shut down
+        assert!(other.keep_awake.lock().unwrap().is_none());
+        let ping = command::echo(&[0x01]);
+        assert!(other
+            .dispatcher
+            .send_command_timeout(ping, std::time::Duration::from_millis(50))
+            .is_err());
+    }
+
+    #[test]
+    fn test_enable_color_detection_orders_commands() {
+        let (rvr, handle) = virtual_rvr();
+        let sensor = (device::SENSOR, sensor_command::ENABLE_COLOR_DETECTION);
+        let notify = (
+            device::SENSOR,
+            sensor_command::ENABLE_COLOR_DETECTION_NOTIFY,
+        );
+
+        rvr.enable_color_detection(true).unwrap();
+        rvr.enable_color_detection(false).unwrap();
+        assert_eq!(handle.commands(), vec![sensor, notify, notify, sensor]);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_clones_send_concurrently() {
+        let (rvr, handle) = virtual_rvr();
+
+        let threads: Vec<_> = [Color::RED, Color::BLUE]
+            .into_iter()
+            .map(|color| {
+                let rvr = rvr.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        rvr.set_all_leds(color).unwrap();
+                        rvr.get_battery_percentage().unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(handle.commands().len(), 40);
+        // Settings are shared between clones
+        rvr.clone().set_speed_limit(100);
+        assert_eq!(rvr.state().max_speed, 100);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_send_ir_message() {
+        let (rvr, handle) = virtual_rvr();
+
+        for (code, strength) in [(MAX_IR_CODE + 1, 10), (0, MAX_IR_STRENGTH + 1)] {
+            assert!(matches!(
+                rvr.send_ir_message(code, strength),
+                Err(RvrError::InvalidParameter(_))
+            ));
+        }
+        assert!(handle.commands().is_empty());
+
+        rvr.enable_ir_messages(true).unwrap();
+        rvr.send_ir_message(3, 32).unwrap();
+        assert_eq!(
+            handle.commands(),
+            vec![
+                (device::SENSOR, sensor_command::ENABLE_IR_MESSAGE_NOTIFY),
+                (device::SENSOR, sensor_command::SEND_IR_MESSAGE),
+            ]
+        );
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_get_ambient_light() {
+        let (rvr, handle) = virtual_rvr();
+        handle.set_ambient_light(412.5);
+
+        assert_eq!(rvr.get_ambient_light().unwrap(), 412.5);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_get_orientation_quaternion_reads_stream() {
+        let (rvr, handle) = virtual_rvr();
+
+        // Not streamed yet
+        assert!(matches!(
+            rvr.get_orientation_quaternion(),
+            Err(RvrError::InvalidParameter(_))
+        ));
+
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        handle.set_orientation([half, 0.0, 0.0, half]);
+        rvr.wake().unwrap();
+        rvr.configure_sensor_streaming(&[SensorType::Quaternion], 50)
+            .unwrap();
+        rvr.start_sensor_streaming().unwrap();
+
+        let q = rvr.get_orientation_quaternion().unwrap();
+        assert!((q[0] - half).abs() < 1e-3);
+        assert!(q[1].abs() < 1e-3);
+        assert!(q[2].abs() < 1e-3);
+        assert!((q[3] - half).abs() < 1e-3);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_get_locator_position_reads_stream() {
+        let (rvr, handle) = virtual_rvr();
+
+        assert!(matches!(
+            rvr.get_locator_position(),
+            Err(RvrError::InvalidParameter(_))
+        ));
+
+        handle.set_position(Locator { x: 1.25, y: -0.5 });
+        rvr.wake().unwrap();
+        rvr.configure_sensor_streaming(&[SensorType::Speed, SensorType::Locator], 50)
+            .unwrap();
+        rvr.start_sensor_streaming().unwrap();
+
+        let position = rvr.get_locator_position().unwrap();
+        assert!((position.x - 1.25).abs() < 1e-3);
+        assert!((position.y + 0.5).abs() < 1e-3);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_configure_sensor_stream() {
+        let (rvr, handle) = virtual_rvr();
+        let config =
+            SensorConfig::new(0x20, &[SensorType::Quaternion, SensorType::Locator]).unwrap();
+
+        assert!(matches!(
+            rvr.configure_sensor_stream(&config, MIN_STREAMING_INTERVAL_MS - 1),
+            Err(RvrError::InvalidParameter(_))
+        ));
+        assert!(handle.commands().is_empty());
+
+        handle.set_position(Locator { x: 0.5, y: 2.0 });
+        rvr.wake().unwrap();
+        rvr.configure_sensor_stream(&config, 50).unwrap();
+        let set = handle.packets().pop().unwrap();
+        assert_eq!(set.command_id, sensor_command::SET_SENSOR_STREAMING);
+        assert_eq!(set.payload, config.config_payload());
+
+        // Single-sensor reads pick their sample out of the combined stream
+        rvr.start_sensor_streaming().unwrap();
+        let position = rvr.get_locator_position().unwrap();
+        assert!((position.x - 0.5).abs() < 1e-3);
+        assert!((position.y - 2.0).abs() < 1e-3);
+        assert!((rvr.get_orientation_quaternion().unwrap()[0] - 1.0).abs() < 1e-3);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_power_status_skips_unimplemented_query() {
+        let (rvr, handle) = virtual_rvr();
+        handle.set_battery_percentage(64);
+
+        let status = rvr.get_power_status().unwrap();
+        assert_eq!(status.voltage_state, Some(BatteryVoltageState::Ok));
+        assert!(status.protection.is_some());
+
+        handle.set_response_code(
+            device::POWER,
+            power_command::GET_PROTECTION_STATE,
+            error_code::NOT_YET_IMPLEMENTED,
+        );
+        let status = rvr.get_power_status().unwrap();
+        assert_eq!(status.battery.percentage, 64);
+        assert_eq!(status.voltage_state, Some(BatteryVoltageState::Ok));
+        assert_eq!(status.protection, None);
+
+        // Other errors still fail the query
+        handle.set_response_code(
+            device::POWER,
+            power_command::GET_BATTERY_VOLTAGE_STATE,
+            error_code::BAD_PARAMETER_VALUE,
+        );
+        assert!(rvr.get_power_status().is_err());
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_set_streaming_interval() {
+        let (rvr, handle) = virtual_rvr();
+
+        assert!(matches!(
+            rvr.set_streaming_interval(MIN_STREAMING_INTERVAL_MS - 1),
+            Err(RvrError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            rvr.configure_sensor_streaming(&[SensorType::Speed], MIN_STREAMING_INTERVAL_MS - 1),
+            Err(RvrError::InvalidParameter(_))
+        ));
+        assert!(handle.commands().is_empty());
+
+        rvr.set_streaming_interval(500).unwrap();
+        assert_eq!(
+            handle.commands(),
+            vec![(device::SENSOR, sensor_command::SET_STREAMING_INTERVAL)]
+        );
+        assert_eq!(
+            handle.notification_interval(),
+            Some(std::time::Duration::from_millis(500))
+        );
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_ping_measures_round_trip() {
+        let (rvr, _) = virtual_rvr();
+        let latency = rvr.ping().unwrap();
+        assert!(latency < std::time::Duration::from_secs(2));
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_verify_echo() {
+        assert!(verify_echo(&[1, 2, 3, 4], &[1, 2, 3, 4]).is_ok());
+        assert!(matches!(
+            verify_echo(&[1, 2, 3, 4], &[1, 2, 3]),
+            Err(RvrError::InvalidResponse(_))
+        ));
+        assert!(matches!(
+            verify_echo(&[1, 2, 3, 4], &[4, 3, 2, 1]),
+            Err(RvrError::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_mac_address() {
+        let data = [0xA1, 0xB2, 0xC3, 0xD4, 0xE5, 0x06];
+        assert_eq!(format_mac_address(&data).unwrap(), "A1:B2:C3:D4:E5:06");
+
+        for bad in [&data[..5], &[0u8; 7][..]] {
+            match format_mac_address(bad) {
+                Err(RvrError::InvalidResponse(msg)) => {
+                    assert!(msg.contains(&bad.len().to_string()))
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_device_timestamp() {
+        let data = [0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x86, 0xA0];
+        assert_eq!(decode_device_timestamp(&data).unwrap(), 100_000);
+
+        assert!(matches!(
+            decode_device_timestamp(&data[..7]),
+            Err(RvrError::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_unsupported_command() {
+        let mut response = Packet::new_command(
+            device::POWER,
+            power_command::GET_PROTECTION_STATE,
+            1,
+            vec![],
+        );
+        response.flags.is_response = true;
+
+        response.payload = vec![error_code::NOT_YET_IMPLEMENTED];
+        assert!(is_unsupported_command(&response));
+
+        response.payload = vec![error_code::BAD_COMMAND_ID];
+        assert!(is_unsupported_command(&response));
+
+        // Success and other failures are not treated as "unsupported"
+        response.payload = vec![error_code::SUCCESS, 0x01];
+        assert!(!is_unsupported_command(&response));
+        response.payload = vec![error_code::BUSY];
+        assert!(!is_unsupported_command(&response));
+        response.payload = vec![];
+        assert!(!is_unsupported_command(&response));
+    }
+
+    #[test]
+    fn test_decode_api_protocol_version() {
+        assert_eq!(decode_api_protocol_version(&[2, 1]).unwrap(), (2, 1));
+        assert!(matches!(
+            decode_api_protocol_version(&[2]),
+            Err(RvrError::InvalidResponse(_))
+        ));
+        assert!(decode_api_protocol_version(&[]).is_err());
+    }
+
+    #[test]
+    fn test_check_response_success() {
+        let dispatcher = Dispatcher::new("/dev/null", 115200);
+        if dispatcher.is_err() {
+            return;
+        }
+
+        let rvr = SpheroRvr::with_dispatcher(dispatcher.unwrap());
+
+        // Empty payload means success
+        let response = Packet {
             flags: PacketFlags {
                 is_response: true,
                 requests_response: false,
@@ -415,9 +3708,7 @@ mod tests {
             return;
         }
 
-        let rvr = SpheroRvr {
-            dispatcher: dispatcher.unwrap(),
-        };
+        let rvr = SpheroRvr::with_dispatcher(dispatcher.unwrap());
 
         let response = Packet {
             flags: PacketFlags {
@@ -439,7 +3730,309 @@ mod tests {
 
         assert!(matches!(
             rvr.check_response(&response),
-            Err(RvrError::CommandFailed(_))
+            Err(RvrError::DeviceError {
+                code: error_code::FAILED,
+                ..
+            })
         ));
     }
 }
+
+/// Table-driven command/response pairing tests
+///
+/// Each row describes one command: the request it sends and a synthetic
+/// response. Every row is framed, parsed back through `SpheroParser`, and its
+/// response decoded, so the TX encoding and RX decoding are checked together.
+/// Adding a command means adding one row to `command_cases()`.
+#[cfg(test)]
+mod command_pairing_tests {
+    use super::*;
+    use crate::api::command::drive_with_heading_payload;
+    use crate::protocol::framing::{encode_bytes, EOP, ESC, SOP};
+    use crate::protocol::parser::SpheroParser;
+    use crate::transport::virtual_rvr::response_to;
+
+    struct CommandCase {
+        name: &'static str,
+        device_id: u8,
+        command_id: u8,
+        request_payload: Vec<u8>,
+        /// Response data (after the error code byte)
+        response_data: Vec<u8>,
+        /// Decodes the response data and asserts on the result
+        check: fn(&Packet),
+    }
+
+    fn no_data(_: &Packet) {}
+
+    fn command_cases() -> Vec<CommandCase> {
+        vec![
+            CommandCase {
+                name: "wake",
+                device_id: device::POWER,
+                command_id: power_command::WAKE,
+                request_payload: vec![],
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "sleep",
+                device_id: device::POWER,
+                command_id: power_command::SLEEP,
+                request_payload: vec![],
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "set_all_leds",
+                device_id: device::IO,
+                command_id: io_command::SET_ALL_LEDS,
+                request_payload: led_payload_for(led_bitmask::ALL, Color::new(1, 2, 3)),
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "get_rgb_led",
+                device_id: device::IO,
+                command_id: io_command::GET_RGB_LED,
+                request_payload: vec![RvrLedIndex::LeftBrakeLight.index()],
+                response_data: vec![0xFF, 0x00, 0x10],
+                check: |r| {
+                    let color = decode_color(response_data(r)).unwrap();
+                    assert_eq!(color, Color::new(0xFF, 0x00, 0x10));
+                },
+            },
+            CommandCase {
+                name: "set_idle_animation",
+                device_id: device::IO,
+                command_id: io_command::SET_IDLE_LED_ANIMATION,
+                request_payload: toggle_payload(false),
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "get_battery_percentage",
+                device_id: device::POWER,
+                command_id: power_command::GET_BATTERY_PERCENTAGE,
+                request_payload: vec![],
+                response_data: vec![87],
+                check: |r| {
+                    let battery = decode_battery_percentage(response_data(r)).unwrap();
+                    assert_eq!(battery.percentage, 87);
+                },
+            },
+            CommandCase {
+                name: "get_protection_state",
+                device_id: device::POWER,
+                command_id: power_command::GET_PROTECTION_STATE,
+                request_payload: vec![],
+                response_data: vec![0b0000_0010],
+                check: |r| {
+                    let state =
+                        ProtectionState::from_byte(first_data_byte(r, "Protection").unwrap());
+                    assert!(state.undervoltage);
+                    assert!(!state.overtemp);
+                },
+            },
+            CommandCase {
+                name: "clear_protection_latch",
+                device_id: device::POWER,
+                command_id: power_command::CLEAR_PROTECTION_LATCH,
+                request_payload: vec![],
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "enable_battery_state_notifications",
+                device_id: device::POWER,
+                command_id: power_command::ENABLE_BATTERY_VOLTAGE_STATE_CHANGE_NOTIFY,
+                request_payload: toggle_payload(true),
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "get_api_protocol_version",
+                device_id: device::SYSTEM_INFO,
+                command_id: system_info_command::GET_API_PROTOCOL_VERSION,
+                request_payload: vec![],
+                response_data: vec![2, 1],
+                check: |r| {
+                    assert_eq!(
+                        decode_api_protocol_version(response_data(r)).unwrap(),
+                        (2, 1)
+                    );
+                },
+            },
+            CommandCase {
+                name: "detect_model",
+                device_id: device::SYSTEM_INFO,
+                command_id: system_info_command::GET_HARDWARE_VERSION,
+                request_payload: vec![],
+                response_data: vec![5],
+                check: |r| {
+                    let revision = first_data_byte(r, "Hardware version").unwrap();
+                    assert_eq!(RvrModel::from_board_revision(revision), RvrModel::RvrPlus);
+                },
+            },
+            CommandCase {
+                name: "get_device_timestamp",
+                device_id: device::SYSTEM_INFO,
+                command_id: system_info_command::GET_CORE_UPTIME_MS,
+                request_payload: vec![],
+                // Contains an ESC byte to exercise escaping in the response
+                response_data: vec![0, 0, 0, 0, 0, 0, 0xAB, 0xCD],
+                check: |r| {
+                    assert_eq!(decode_device_timestamp(response_data(r)).unwrap(), 0xABCD);
+                },
+            },
+            CommandCase {
+                name: "reset_yaw",
+                device_id: device::DRIVE,
+                command_id: drive_command::RESET_YAW,
+                request_payload: vec![],
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "reset_locator",
+                device_id: device::SENSOR,
+                command_id: sensor_command::RESET_LOCATOR,
+                request_payload: vec![],
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "stop",
+                device_id: device::DRIVE,
+                command_id: drive_command::STOP,
+                request_payload: vec![drive_mode::BRAKE],
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "hold_position",
+                device_id: device::DRIVE,
+                command_id: drive_command::DRIVE_RC_SI_UNITS,
+                request_payload: vec![0x00; 9],
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "drive_with_heading",
+                device_id: device::DRIVE,
+                command_id: drive_command::DRIVE_WITH_HEADING,
+                request_payload: drive_with_heading_payload(200, 359, 0),
+                response_data: vec![],
+                check: no_data,
+            },
+            CommandCase {
+                name: "set_wheel_velocities",
+                device_id: device::DRIVE,
+                command_id: drive_command::SET_WHEEL_VELOCITIES,
+                request_payload: wheel_velocities_payload(-25.0, 25.0),
+                response_data: vec![],
+                check: no_data,
+            },
+        ]
+    }
+
+    fn frame(packet: &Packet) -> Vec<u8> {
+        packet.to_framed_bytes()
+    }
+
+    fn parse(framed: &[u8]) -> Packet {
+        let mut parser = SpheroParser::new();
+        let mut parsed = None;
+        for &byte in framed {
+            if let Some(packet) = parser.feed(byte).unwrap() {
+                parsed = Some(packet);
+            }
+        }
+        parsed.expect("frame did not parse")
+    }
+
+    /// Build the success response the robot would send for a request
+    fn synthetic_response(request: &Packet, data: &[u8]) -> Packet {
+        response_to(request, [&[error_code::SUCCESS], data].concat())
+    }
+
+    #[test]
+    fn test_command_response_pairing() {
+        for (seq, case) in command_cases().into_iter().enumerate() {
+            let mut request = command::command(
+                case.device_id,
+                case.command_id,
+                case.request_payload.clone(),
+            );
+            request.sequence_number = seq as u8;
+
+            // Request survives framing and parsing unchanged
+            let parsed = parse(&frame(&request));
+            assert_eq!(parsed.device_id, case.device_id, "{}", case.name);
+            assert_eq!(parsed.command_id, case.command_id, "{}", case.name);
+            assert_eq!(parsed.sequence_number, seq as u8, "{}", case.name);
+            assert_eq!(parsed.payload, case.request_payload, "{}", case.name);
+            assert_eq!(parsed.target_id, Some(routing_node::PRIMARY_PROCESSOR));
+            assert_eq!(parsed.source_id, Some(routing_node::UART_PORT));
+            assert!(parsed.flags.requests_response, "{}", case.name);
+
+            // Synthetic response pairs with the request and decodes
+            let response = parse(&frame(&synthetic_response(&request, &case.response_data)));
+            assert!(response.flags.is_response, "{}", case.name);
+            assert_eq!(
+                response.sequence_number, request.sequence_number,
+                "{}",
+                case.name
+            );
+            assert_eq!(response.device_id, case.device_id, "{}", case.name);
+            assert_eq!(response.command_id, case.command_id, "{}", case.name);
+            assert_eq!(
+                response_data(&response),
+                &case.response_data[..],
+                "{}",
+                case.name
+            );
+            (case.check)(&response);
+        }
+    }
+
+    #[test]
+    fn test_user_device_id_round_trip() {
+        // Device ids outside the documented set pass through unvalidated
+        let mut request = command::command(0x42, 0x01, vec![0xAA, 0x55]);
+        request.sequence_number = 9;
+
+        let parsed = parse(&frame(&request));
+        assert_eq!(parsed.device_id, 0x42);
+        assert_eq!(parsed.command_id, 0x01);
+        assert_eq!(parsed.sequence_number, 9);
+        assert_eq!(parsed.payload, vec![0xAA, 0x55]);
+
+        let response = parse(&frame(&synthetic_response(&request, &[0x10])));
+        assert_eq!(response.device_id, 0x42);
+        assert_eq!(response.command_id, 0x01);
+        assert_eq!(response_data(&response), &[0x10]);
+    }
+
+    #[test]
+    fn test_echo_escape_heavy_round_trip() {
+        // Every framing byte, escaped forms, and plain bytes around them
+        let data = vec![SOP, EOP, ESC, ESC, 0x05, SOP ^ 0x88, 0x00, EOP, 0xFF];
+
+        let mut request =
+            command::command(device::API_AND_SHELL, api_shell_command::ECHO, data.clone());
+        request.sequence_number = ESC;
+
+        let framed = frame(&request);
+        // Only the delimiters appear unescaped on the wire
+        assert_eq!(framed.iter().filter(|&&b| b == SOP).count(), 1);
+        assert_eq!(framed.iter().filter(|&&b| b == EOP).count(), 1);
+
+        let parsed = parse(&framed);
+        assert_eq!(parsed.payload, data);
+
+        let response = parse(&frame(&synthetic_response(&request, &parsed.payload)));
+        assert_eq!(response.sequence_number, ESC);
+        assert_eq!(response_data(&response), &data[..]);
+    }
+}
@@ -0,0 +1,343 @@
+//! Command packet builders
+//!
+//! Every command sent to the RVR over UART needs the same routing header
+//! (target: primary processor, source: UART port). `SpheroRvr` builds all of
+//! its packets here, and code driving a `Dispatcher` directly should too, so
+//! the two paths produce identical frames.
+//!
+//! Sequence numbers are left at 0; the dispatcher assigns them on send.
+//!
+//! `Command` names each supported command together with its parameters;
+//! the free functions are shorthands for the common ones.
+//!
+//! Most commands are answered within a few milliseconds, so a missing
+//! response means the command was lost. A few are not reliably answered
+//! even when they work; `Command::timeout_policy` lists them.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sphero_rvr::api::command;
+//! use sphero_rvr::api::types::Color;
+//! use sphero_rvr::api::LedMask;
+//! use sphero_rvr::transport::Dispatcher;
+//!
+//! let dispatcher = Dispatcher::new("/dev/serial0", 115200)?;
+//! dispatcher.send_command(command::wake())?;
+//! dispatcher.send_command(command::set_leds(LedMask::ALL, Color::GREEN))?;
+//! # Ok::<(), sphero_rvr::error::RvrError>(())
+//! ```
+
+use crate::api::constants::{
+    api_shell_command, device, drive_command, drive_mode, io_command, power_command, routing_node,
+};
+use crate::api::led::{led_payload_for, LedMask};
+use crate::api::types::{Color, DriveFlags};
+use crate::protocol::packet::Packet;
+use std::fmt;
+
+/// Payload bytes `Packet`'s `Display` shows before eliding the rest
+const DISPLAY_PAYLOAD_BYTES: usize = 8;
+
+/// A command the RVR understands, with its parameters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Wake the robot from soft sleep
+    Wake,
+    /// Put the robot into soft sleep
+    Sleep,
+    /// Ask the robot to send the data back unchanged
+    Echo(Vec<u8>),
+    /// Set every LED to one color
+    SetAllLeds(Color),
+    /// Set the LEDs selected by `mask` to `color`
+    SetLeds { mask: LedMask, color: Color },
+    /// Stop the motors, braking or coasting
+    Stop { brake: bool },
+    /// Drive at `speed` (0-255) toward `heading` (degrees, 0-359)
+    DriveWithHeading {
+        speed: u8,
+        heading: u16,
+        flags: DriveFlags,
+    },
+}
+
+/// What a missing response to a command means
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPolicy {
+    /// The command may not have arrived; report the timeout as an error
+    Error,
+    /// The command is known to work without reliably answering; treat a
+    /// timeout as success
+    Success,
+}
+
+impl Command {
+    /// How to treat a missing response to this command
+    ///
+    /// Commands known not to ack reliably:
+    ///
+    /// * `Sleep` - the robot can power down the UART before its response
+    ///   is sent, so the sleep succeeds but the caller never hears back
+    ///
+    /// Every other command uses `TimeoutPolicy::Error`.
+    pub fn timeout_policy(&self) -> TimeoutPolicy {
+        match self {
+            Self::Sleep => TimeoutPolicy::Success,
+            _ => TimeoutPolicy::Error,
+        }
+    }
+
+    /// Build the routed packet for this command
+    pub fn to_packet(&self) -> Packet {
+        match self {
+            Self::Wake => command(device::POWER, power_command::WAKE, vec![]),
+            Self::Sleep => command(device::POWER, power_command::SLEEP, vec![]),
+            Self::Echo(data) => {
+                command(device::API_AND_SHELL, api_shell_command::ECHO, data.clone())
+            }
+            Self::SetAllLeds(color) => Self::SetLeds {
+                mask: LedMask::ALL,
+                color: *color,
+            }
+            .to_packet(),
+            Self::SetLeds { mask, color } => command(
+                device::IO,
+                io_command::SET_ALL_LEDS,
+                led_payload_for(mask.bits(), *color),
+            ),
+            Self::Stop { brake } => {
+                let mode = if *brake {
+                    drive_mode::BRAKE
+                } else {
+                    drive_mode::COAST
+                };
+                command(device::DRIVE, drive_command::STOP, vec![mode])
+            }
+            Self::DriveWithHeading {
+                speed,
+                heading,
+                flags,
+            } => command(
+                device::DRIVE,
+                drive_command::DRIVE_WITH_HEADING,
+                drive_with_heading_payload(*speed, *heading, flags.to_byte()),
+            ),
+        }
+    }
+}
+
+/// Build a command packet routed from the UART port to the primary processor
+pub fn command(device_id: u8, command_id: u8, payload: Vec<u8>) -> Packet {
+    use routing_node::{PRIMARY_PROCESSOR, UART_PORT};
+
+    // Target: primary processor (Nordic MCU); source: UART expansion port.
+    // Both IDs are required for UART routing.
+    Packet::new_command(device_id, command_id, 0, payload)
+        .with_routing(PRIMARY_PROCESSOR, UART_PORT)
+}
+
+/// Wake the robot from soft sleep
+pub fn wake() -> Packet {
+    Command::Wake.to_packet()
+}
+
+/// Put the robot into soft sleep
+pub fn sleep() -> Packet {
+    Command::Sleep.to_packet()
+}
+
+/// Ask the robot to send `data` back unchanged
+pub fn echo(data: &[u8]) -> Packet {
+    Command::Echo(data.to_vec()).to_packet()
+}
+
+/// Set the LEDs selected by `mask` to `color`
+pub fn set_leds(mask: LedMask, color: Color) -> Packet {
+    Command::SetLeds { mask, color }.to_packet()
+}
+
+/// Encode drive-with-heading parameters: `[SPEED] [HEADING: u16 BE] [FLAGS]`
+pub(crate) fn drive_with_heading_payload(speed: u8, heading: u16, flags: u8) -> Vec<u8> {
+    let [heading_hi, heading_lo] = heading.to_be_bytes();
+    vec![speed, heading_hi, heading_lo, flags]
+}
+
+/// One-line summary for logs
+///
+/// Formats as `dev=0x1A(IO) cmd=0x1C seq=5 flags=[resp,req_resp]
+/// payload=4B [01 02 03 04]`. Only the first 8 payload bytes are shown,
+/// followed by `..` if there are more; routing IDs are left out. Use `{:?}`
+/// for every field.
+///
+/// Lives here rather than in `protocol` because it names devices from the
+/// API constants, so it needs the `std` feature.
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dev=0x{:02X}", self.device_id)?;
+        if let Some(name) = device::name(self.device_id) {
+            write!(f, "({})", name)?;
+        }
+        write!(
+            f,
+            " cmd=0x{:02X} seq={} flags=[",
+            self.command_id, self.sequence_number
+        )?;
+
+        let names = [
+            (self.flags.is_response, "resp"),
+            (self.flags.requests_response, "req_resp"),
+            (self.flags.requests_only_error_response, "err_only"),
+            (self.flags.is_activity, "activity"),
+        ];
+        let mut separator = "";
+        for (_, name) in names.iter().filter(|(set, _)| *set) {
+            write!(f, "{}{}", separator, name)?;
+            separator = ",";
+        }
+
+        write!(f, "] payload={}B", self.payload.len())?;
+        if !self.payload.is_empty() {
+            f.write_str(" [")?;
+            for (i, byte) in self.payload.iter().take(DISPLAY_PAYLOAD_BYTES).enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                write!(f, "{:02X}", byte)?;
+            }
+            if self.payload.len() > DISPLAY_PAYLOAD_BYTES {
+                f.write_str(" ..")?;
+            }
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::client::tests::virtual_rvr;
+    use crate::api::SpheroRvr;
+    use crate::protocol::packet::Packet;
+    use crate::transport::{CommandRecorder, SharedBuffer};
+
+    #[test]
+    fn test_command_routing() {
+        let packet = command(device::IO, io_command::SET_ALL_LEDS, vec![]);
+        assert_eq!(packet.target_id, Some(routing_node::PRIMARY_PROCESSOR));
+        assert_eq!(packet.source_id, Some(routing_node::UART_PORT));
+        assert!(packet.flags.requests_response);
+        assert_eq!(packet.sequence_number, 0);
+    }
+
+    #[test]
+    fn test_typed_commands_pick_device_and_command() {
+        let cases = [
+            (Command::Wake, device::POWER, power_command::WAKE),
+            (Command::Sleep, device::POWER, power_command::SLEEP),
+            (
+                Command::Echo(vec![1, 2]),
+                device::API_AND_SHELL,
+                api_shell_command::ECHO,
+            ),
+            (
+                Command::SetAllLeds(Color::RED),
+                device::IO,
+                io_command::SET_ALL_LEDS,
+            ),
+            (
+                Command::Stop { brake: true },
+                device::DRIVE,
+                drive_command::STOP,
+            ),
+        ];
+        for (cmd, device_id, command_id) in cases {
+            let packet = cmd.to_packet();
+            assert_eq!(
+                (packet.device_id, packet.command_id),
+                (device_id, command_id),
+                "{:?}",
+                cmd
+            );
+            assert_eq!(packet.source_id, Some(routing_node::UART_PORT));
+        }
+
+        assert_eq!(
+            Command::SetAllLeds(Color::RED).to_packet().to_bytes(),
+            set_leds(LedMask::ALL, Color::RED).to_bytes()
+        );
+        assert_eq!(
+            Command::Stop { brake: false }.to_packet().payload,
+            vec![drive_mode::COAST]
+        );
+        let drive = Command::DriveWithHeading {
+            speed: 128,
+            heading: 270,
+            flags: DriveFlags {
+                reverse: true,
+                ..DriveFlags::default()
+            },
+        };
+        assert_eq!(drive.to_packet().payload, vec![128, 0x01, 0x0E, 0x01]);
+    }
+
+    #[test]
+    fn test_timeout_policy() {
+        assert_eq!(Command::Sleep.timeout_policy(), TimeoutPolicy::Success);
+        assert_eq!(Command::Wake.timeout_policy(), TimeoutPolicy::Error);
+        assert_eq!(
+            Command::Stop { brake: true }.timeout_policy(),
+            TimeoutPolicy::Error
+        );
+    }
+
+    #[test]
+    fn test_client_and_builder_frames_match() {
+        let buffer = SharedBuffer::new();
+        let (rvr, _) = virtual_rvr();
+        rvr.set_recorder(Some(CommandRecorder::new(buffer.clone())));
+
+        rvr.set_all_leds(Color::GREEN).unwrap();
+        rvr.shutdown().unwrap();
+
+        let text = String::from_utf8(buffer.contents()).unwrap();
+        let capture = text
+            .lines()
+            .find(|line| !line.starts_with('#'))
+            .and_then(|line| line.split_once(' '))
+            .map(|(_, capture)| capture)
+            .unwrap();
+        let mut sent = Packet::from_hex_capture(capture).unwrap();
+        // The dispatcher assigns sequence numbers; compare everything else
+        sent.sequence_number = 0;
+
+        assert_eq!(
+            sent.to_framed_bytes(),
+            set_leds(LedMask::ALL, Color::GREEN).to_framed_bytes()
+        );
+    }
+
+    #[test]
+    fn test_display_summary() {
+        let mut packet = Packet::new_command(0x1A, 0x1C, 5, vec![0x01, 0x02, 0x03, 0x04]);
+        packet.flags.is_response = true;
+        assert_eq!(
+            packet.to_string(),
+            "dev=0x1A(IO) cmd=0x1C seq=5 flags=[resp,req_resp] payload=4B [01 02 03 04]"
+        );
+
+        let mut packet = Packet::new_command(0x42, 0x01, 0, (0..12).collect());
+        packet.flags.requests_response = false;
+        assert_eq!(
+            packet.to_string(),
+            "dev=0x42 cmd=0x01 seq=0 flags=[] payload=12B [00 01 02 03 04 05 06 07 ..]"
+        );
+
+        let packet = Packet::new_command(0x13, 0x0D, 1, vec![]);
+        assert_eq!(
+            packet.to_string(),
+            "dev=0x13(Power) cmd=0x0D seq=1 flags=[req_resp] payload=0B"
+        );
+    }
+}
@@ -18,6 +18,9 @@ pub mod routing_node {
 
 /// Device IDs for RVR subsystems
 pub mod device {
+    /// API and Shell device - echo, protocol diagnostics
+    pub const API_AND_SHELL: u8 = 0x10;
+
     /// Power device - controls wake, sleep, battery status
     pub const POWER: u8 = 0x13;
 
@@ -32,6 +35,25 @@ pub mod device {
 
     /// System Info device - firmware version, hardware info
     pub const SYSTEM_INFO: u8 = 0x11;
+
+    /// Short name of a device id for logs, or `None` if it is not one of the above
+    pub fn name(device_id: u8) -> Option<&'static str> {
+        match device_id {
+            API_AND_SHELL => Some("ApiShell"),
+            POWER => Some("Power"),
+            IO => Some("IO"),
+            DRIVE => Some("Drive"),
+            SENSOR => Some("Sensor"),
+            SYSTEM_INFO => Some("SystemInfo"),
+            _ => None,
+        }
+    }
+}
+
+/// Command IDs for the API and Shell device
+pub mod api_shell_command {
+    /// Echo the request payload back unchanged
+    pub const ECHO: u8 = 0x00;
 }
 
 /// Command IDs for the Power device
@@ -42,11 +64,41 @@ pub mod power_command {
     /// Put the robot to sleep
     pub const SLEEP: u8 = 0x01;
 
+    /// Async notification: the robot is about to sleep from inactivity
+    pub const WILL_SLEEP_NOTIFY: u8 = 0x19;
+
+    /// Async notification: the robot has gone to sleep
+    pub const DID_SLEEP_NOTIFY: u8 = 0x1A;
+
     /// Get battery percentage
     pub const GET_BATTERY_PERCENTAGE: u8 = 0x10;
 
     /// Get battery voltage state
     pub const GET_BATTERY_VOLTAGE_STATE: u8 = 0x17;
+
+    /// Enable/disable battery voltage state change notifications
+    pub const ENABLE_BATTERY_VOLTAGE_STATE_CHANGE_NOTIFY: u8 = 0x1B;
+
+    /// Async notification: battery voltage state changed
+    pub const BATTERY_VOLTAGE_STATE_CHANGE_NOTIFY: u8 = 0x1C;
+
+    /// Get the latched protection state (overtemp, undervoltage, overcurrent)
+    pub const GET_PROTECTION_STATE: u8 = 0x2A;
+
+    /// Clear the latched protection state so the robot can resume
+    pub const CLEAR_PROTECTION_LATCH: u8 = 0x2B;
+
+    /// Get the current measured by a current sense amplifier (amps, f32)
+    pub const GET_CURRENT_SENSE_AMPLIFIER_CURRENT: u8 = 0x27;
+}
+
+/// Current sense amplifier IDs for `GET_CURRENT_SENSE_AMPLIFIER_CURRENT`
+pub mod amplifier_id {
+    /// Left motor amplifier
+    pub const LEFT_MOTOR: u8 = 0x00;
+
+    /// Right motor amplifier
+    pub const RIGHT_MOTOR: u8 = 0x01;
 }
 
 /// Command IDs for the IO device
@@ -59,6 +111,15 @@ pub mod io_command {
 
     /// Get RGB LED values
     pub const GET_RGB_LED: u8 = 0x1C;
+
+    /// Get the number of RGB LEDs and color channels per LED
+    pub const GET_LED_CAPABILITIES: u8 = 0x1D;
+
+    /// Enable or disable the firmware's default idle LED animation
+    pub const SET_IDLE_LED_ANIMATION: u8 = 0x4D;
+
+    /// Release user LED control back to the firmware
+    pub const RELEASE_LED_REQUESTS: u8 = 0x4E;
 }
 
 /// Command IDs for the Drive device
@@ -66,7 +127,7 @@ pub mod drive_command {
     /// Set raw motors (left, right)
     pub const SET_RAW_MOTORS: u8 = 0x01;
 
-    /// Reset yaw angle
+    /// Make the current heading yaw 0 (does not move the locator origin)
     pub const RESET_YAW: u8 = 0x06;
 
     /// Drive with heading and speed
@@ -74,14 +135,61 @@ pub mod drive_command {
 
     /// Stop both motors
     pub const STOP: u8 = 0x08;
+
+    /// Set left/right wheel velocities (closed-loop, SI units: m/s floats)
+    pub const SET_WHEEL_VELOCITIES: u8 = 0x32;
+
+    /// RC-style drive in SI units: `[YAW: f32 BE] [LINEAR: f32 BE] [FLAGS]`
+    /// (yaw rate in deg/s, linear velocity in m/s)
+    pub const DRIVE_RC_SI_UNITS: u8 = 0x34;
+
+    /// RC-style drive, normalized: `[YAW: i8] [LINEAR: i8] [FLAGS]`
+    /// (each -127 to 127)
+    pub const DRIVE_RC_NORMALIZED: u8 = 0x35;
 }
 
 /// Command IDs for the Sensor device
 pub mod sensor_command {
-    /// Enable/disable sensor streaming
+    /// Start magnetometer (compass) calibration
+    pub const CALIBRATE_MAGNETOMETER: u8 = 0x25;
+
+    /// Notification: calibration finished (payload: [SUCCESS: bool, ...])
+    pub const MAGNETOMETER_CALIBRATION_COMPLETE_NOTIFY: u8 = 0x26;
+
+    /// Notification: calibration progress (payload: [0 = started, 1 = in progress])
+    pub const MAGNETOMETER_CALIBRATION_PROGRESS_NOTIFY: u8 = 0x27;
+
+    /// Reset the locator position to (0, 0) (does not change yaw)
+    pub const RESET_LOCATOR: u8 = 0x13;
+
+    /// Turn color detection notifications on or off
+    /// (payload: [ENABLED: bool, INTERVAL_MS: u16, MIN_CONFIDENCE])
+    pub const ENABLE_COLOR_DETECTION_NOTIFY: u8 = 0x35;
+
+    /// Notification: color under the robot
+    /// (payload: [R, G, B, CONFIDENCE, CLASSIFICATION_ID])
+    pub const COLOR_DETECTION_NOTIFY: u8 = 0x36;
+
+    /// Power the underside color sensor and its LED (payload: [ENABLED: bool])
+    pub const ENABLE_COLOR_DETECTION: u8 = 0x38;
+
+    /// Notification: IR message received from another robot (payload: [CODE])
+    pub const IR_MESSAGE_RECEIVED_NOTIFY: u8 = 0x2C;
+
+    /// Turn IR message notifications on or off (payload: [ENABLED: bool])
+    pub const ENABLE_IR_MESSAGE_NOTIFY: u8 = 0x3E;
+
+    /// Send an IR message
+    /// (payload: [CODE, FRONT, LEFT, RIGHT, REAR] strengths 0-64)
+    pub const SEND_IR_MESSAGE: u8 = 0x3F;
+
+    /// One-shot read of the ambient light level (lux as f32)
+    pub const GET_AMBIENT_LIGHT_SENSOR_VALUE: u8 = 0x30;
+
+    /// Configure one sensor stream (payload: [TOKEN, SERVICE_ID: u16, SAMPLE_SIZE])
     pub const SET_SENSOR_STREAMING: u8 = 0x39;
 
-    /// Start sensor streaming
+    /// Start sensor streaming (payload: [PERIOD_MS: u16])
     pub const START_SENSOR_STREAMING: u8 = 0x3A;
 
     /// Stop sensor streaming
@@ -90,6 +198,9 @@ pub mod sensor_command {
     /// Clear sensor streaming configuration
     pub const CLEAR_SENSOR_STREAMING: u8 = 0x3C;
 
+    /// Async notification: a sample of streamed sensor data
+    pub const STREAMING_SERVICE_DATA_NOTIFY: u8 = 0x3D;
+
     /// Configure sensor streaming interval
     pub const SET_STREAMING_INTERVAL: u8 = 0x46;
 }
@@ -102,8 +213,14 @@ pub mod system_info_command {
     /// Get hardware version
     pub const GET_HARDWARE_VERSION: u8 = 0x03;
 
+    /// Get API protocol (shell) version - major/minor
+    pub const GET_API_PROTOCOL_VERSION: u8 = 0x04;
+
     /// Get MAC address
     pub const GET_MAC_ADDRESS: u8 = 0x06;
+
+    /// Get milliseconds since the robot's core booted (device timestamp base)
+    pub const GET_CORE_UPTIME_MS: u8 = 0x39;
 }
 
 /// LED bitmasks for targeting specific LEDs
@@ -130,6 +247,27 @@ pub mod led_bitmask {
     pub const ALL: u8 = 0x3F;
 }
 
+/// Drive-with-heading flag bits
+pub mod drive_flags {
+    /// Drive in reverse along the heading
+    pub const REVERSE: u8 = 0x01;
+
+    /// Allow speeds above the normal limit (boost mode)
+    pub const BOOST: u8 = 0x02;
+
+    /// Turn in place faster when changing heading
+    pub const FAST_TURN: u8 = 0x04;
+
+    /// Reverse the left motor direction (tank-style driving)
+    pub const LEFT_DIRECTION: u8 = 0x08;
+
+    /// Reverse the right motor direction (tank-style driving)
+    pub const RIGHT_DIRECTION: u8 = 0x10;
+
+    /// Let the robot drift through turns instead of holding its heading
+    pub const ENABLE_DRIFT: u8 = 0x20;
+}
+
 /// Drive control modes
 pub mod drive_mode {
     /// Stop mode (0 = coast, 1 = brake)
@@ -138,34 +276,9 @@ pub mod drive_mode {
 }
 
 /// Response error codes
-pub mod error_code {
-    /// Command executed successfully
-    pub const SUCCESS: u8 = 0x00;
-
-    /// Bad device ID
-    pub const BAD_DEVICE_ID: u8 = 0x01;
-
-    /// Bad command ID
-    pub const BAD_COMMAND_ID: u8 = 0x02;
-
-    /// Not yet implemented
-    pub const NOT_YET_IMPLEMENTED: u8 = 0x03;
-
-    /// Command is restricted
-    pub const RESTRICTED: u8 = 0x04;
-
-    /// Bad data length
-    pub const BAD_DATA_LENGTH: u8 = 0x05;
-
-    /// Command failed
-    pub const FAILED: u8 = 0x06;
-
-    /// Bad parameter value
-    pub const BAD_PARAMETER_VALUE: u8 = 0x07;
-
-    /// Busy (try again later)
-    pub const BUSY: u8 = 0x08;
-}
+///
+/// Defined in the protocol layer, since `RvrError` names them.
+pub use crate::protocol::error_code;
 
 #[cfg(test)]
 mod tests {
@@ -178,6 +291,16 @@ mod tests {
         assert_eq!(device::DRIVE, 0x16);
     }
 
+    #[test]
+    fn test_protection_command_ids() {
+        assert_eq!(power_command::GET_PROTECTION_STATE, 0x2A);
+        assert_eq!(power_command::CLEAR_PROTECTION_LATCH, 0x2B);
+        assert_ne!(
+            power_command::GET_PROTECTION_STATE,
+            power_command::CLEAR_PROTECTION_LATCH
+        );
+    }
+
     #[test]
     fn test_led_bitmask() {
         // All LEDs should be the OR of individual LEDs
@@ -0,0 +1,496 @@
+//! Typed decoding of asynchronous notifications
+//!
+//! Notifications arrive on the channel returned by `take_receiver()` as raw
+//! `Packet`s. The types here classify them into meaningful events.
+
+use crate::api::constants::{device, power_command, sensor_command};
+use crate::api::sensors::SensorReading;
+use crate::api::types::{BatteryVoltageState, DetectedColor, IrMessage};
+use crate::protocol::packet::Packet;
+use crate::transport::{NotificationReceiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Any async notification, classified where possible
+#[derive(Debug, Clone)]
+pub enum RvrEvent {
+    /// Battery notification
+    Battery(BatteryEvent),
+    /// Sleep notification from the power device
+    ///
+    /// Battery voltage changes are reported as `Battery` instead.
+    Power(PowerNotification),
+    /// Compass calibration notification
+    CompassCalibration(CompassCalibrationEvent),
+    /// Sensor streaming sample
+    Sensor(SensorReading),
+    /// Color seen by the underside color sensor
+    ColorDetected(DetectedColor),
+    /// Infrared message from another robot
+    IrMessage(IrMessage),
+    /// Notification this crate does not decode (yet)
+    Other(Packet),
+}
+
+impl RvrEvent {
+    /// Classify a notification packet
+    pub fn from_packet(packet: Packet) -> Self {
+        if let Some(event) = BatteryEvent::from_packet(&packet) {
+            return Self::Battery(event);
+        }
+        if let Some(notification) = PowerNotification::from_packet(&packet) {
+            return Self::Power(notification);
+        }
+        if let Some(event) = CompassCalibrationEvent::from_packet(&packet) {
+            return Self::CompassCalibration(event);
+        }
+        if let Ok(reading) = SensorReading::from_packet(&packet) {
+            return Self::Sensor(reading);
+        }
+        if let Ok(color) = DetectedColor::from_packet(&packet) {
+            return Self::ColorDetected(color);
+        }
+        if let Ok(message) = IrMessage::from_packet(&packet) {
+            return Self::IrMessage(message);
+        }
+        Self::Other(packet)
+    }
+}
+
+/// Feed notifications from `rx` to `handler` until `duration` has elapsed
+///
+/// Returns early if the channel is closed. Returns the number of events
+/// handled.
+pub(crate) fn pump_events(
+    rx: &NotificationReceiver,
+    duration: Duration,
+    mut handler: impl FnMut(RvrEvent),
+) -> usize {
+    let deadline = Instant::now() + duration;
+    let mut handled = 0;
+
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match rx.recv_timeout(remaining) {
+            Ok(packet) => {
+                handler(RvrEvent::from_packet(packet));
+                handled += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => {
+                tracing::warn!("Notification channel closed");
+                break;
+            }
+        }
+    }
+
+    handled
+}
+
+/// Battery-related async event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryEvent {
+    /// The battery voltage crossed into a new state
+    VoltageStateChanged(BatteryVoltageState),
+}
+
+impl BatteryEvent {
+    /// Classify a notification packet as a battery event
+    ///
+    /// Returns `None` if the packet is not a battery notification or its
+    /// payload is malformed.
+    pub fn from_packet(packet: &Packet) -> Option<Self> {
+        if !packet.is_notification() || packet.device_id != device::POWER {
+            return None;
+        }
+
+        match packet.command_id {
+            power_command::BATTERY_VOLTAGE_STATE_CHANGE_NOTIFY => {
+                let state = *packet.payload.first()?;
+                Some(Self::VoltageStateChanged(BatteryVoltageState::from_byte(
+                    state,
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if the battery has reached a low or critical voltage
+    pub fn is_low_battery(&self) -> bool {
+        matches!(
+            self,
+            Self::VoltageStateChanged(BatteryVoltageState::Low | BatteryVoltageState::Critical)
+        )
+    }
+}
+
+/// Sleep notification from the power device
+///
+/// Battery voltage changes are decoded by `BatteryEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerNotification {
+    /// The robot will sleep soon from inactivity; send any command to keep it awake
+    WillSleep,
+    /// The robot has gone to sleep
+    DidSleep,
+}
+
+impl PowerNotification {
+    /// Classify a notification packet as a sleep notification
+    ///
+    /// Returns `None` if the packet is not a sleep notification.
+    pub fn from_packet(packet: &Packet) -> Option<Self> {
+        if !packet.is_notification() || packet.device_id != device::POWER {
+            return None;
+        }
+
+        match packet.command_id {
+            power_command::WILL_SLEEP_NOTIFY => Some(Self::WillSleep),
+            power_command::DID_SLEEP_NOTIFY => Some(Self::DidSleep),
+            _ => None,
+        }
+    }
+}
+
+/// Compass (magnetometer) calibration progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompassCalibrationEvent {
+    /// Calibration has begun; the robot spins in place
+    Started,
+    /// Calibration is still running
+    InProgress,
+    /// Calibration succeeded
+    Complete,
+    /// Calibration failed (e.g. magnetic interference)
+    Failed,
+}
+
+impl CompassCalibrationEvent {
+    /// Classify a notification packet as a compass calibration event
+    ///
+    /// Returns `None` if the packet is not a calibration notification or its
+    /// payload is malformed.
+    pub fn from_packet(packet: &Packet) -> Option<Self> {
+        if !packet.is_notification() || packet.device_id != device::SENSOR {
+            return None;
+        }
+
+        let state = *packet.payload.first()?;
+        match packet.command_id {
+            sensor_command::MAGNETOMETER_CALIBRATION_PROGRESS_NOTIFY => match state {
+                0 => Some(Self::Started),
+                _ => Some(Self::InProgress),
+            },
+            sensor_command::MAGNETOMETER_CALIBRATION_COMPLETE_NOTIFY => match state {
+                0 => Some(Self::Failed),
+                _ => Some(Self::Complete),
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns true if calibration has finished, successfully or not
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Complete | Self::Failed)
+    }
+}
+
+/// Suppresses repeated identical battery events
+///
+/// Near a voltage threshold the robot can flap between states and emit a
+/// burst of notifications. The debouncer passes an event through only if it
+/// differs from the last accepted event, or if the debounce window has
+/// elapsed since that event was accepted.
+///
+/// # Example
+///
+/// ```no_run
+/// # use sphero_rvr::SpheroRvr;
+/// use sphero_rvr::api::events::{BatteryEvent, BatteryEventDebouncer};
+/// use std::time::Duration;
+///
+/// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+/// let mut debouncer = BatteryEventDebouncer::new(Duration::from_secs(5));
+/// if let Some(rx) = rvr.take_receiver() {
+///     for packet in rx {
+///         if let Some(event) = BatteryEvent::from_packet(&packet) {
+///             if debouncer.accept(event) {
+///                 println!("Battery: {:?}", event);
+///             }
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BatteryEventDebouncer {
+    window: Duration,
+    last: Option<(BatteryEvent, Instant)>,
+}
+
+impl BatteryEventDebouncer {
+    /// Create a debouncer that suppresses identical events within `window`
+    pub fn new(window: Duration) -> Self {
+        Self { window, last: None }
+    }
+
+    /// Returns true if the event should be passed on to the application
+    pub fn accept(&mut self, event: BatteryEvent) -> bool {
+        self.accept_at(event, Instant::now())
+    }
+
+    /// Same as `accept`, with an explicit timestamp
+    pub fn accept_at(&mut self, event: BatteryEvent, now: Instant) -> bool {
+        if let Some((last_event, last_time)) = self.last {
+            if last_event == event && now.duration_since(last_time) < self.window {
+                return false;
+            }
+        }
+
+        self.last = Some((event, now));
+        true
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn battery_notification(state: u8) -> Packet {
+        let mut packet = Packet::new_command(
+            device::POWER,
+            power_command::BATTERY_VOLTAGE_STATE_CHANGE_NOTIFY,
+            0,
+            vec![state],
+        );
+        packet.flags.requests_response = false;
+        packet
+    }
+
+    #[test]
+    fn test_battery_event_from_packet() {
+        assert_eq!(
+            BatteryEvent::from_packet(&battery_notification(2)),
+            Some(BatteryEvent::VoltageStateChanged(BatteryVoltageState::Low))
+        );
+        let is_low = |state| BatteryEvent::VoltageStateChanged(state).is_low_battery();
+        assert!(is_low(BatteryVoltageState::Low));
+        assert!(is_low(BatteryVoltageState::Critical));
+        assert!(!is_low(BatteryVoltageState::Ok));
+
+        // Wrong device, empty payload, and responses are not battery events
+        let mut other = battery_notification(2);
+        other.device_id = device::SENSOR;
+        assert_eq!(BatteryEvent::from_packet(&other), None);
+
+        let mut empty = battery_notification(2);
+        empty.payload.clear();
+        assert_eq!(BatteryEvent::from_packet(&empty), None);
+
+        let mut response = battery_notification(2);
+        response.flags.is_response = true;
+        assert_eq!(BatteryEvent::from_packet(&response), None);
+    }
+
+    #[test]
+    fn test_rvr_event_classification() {
+        assert!(matches!(
+            RvrEvent::from_packet(battery_notification(1)),
+            RvrEvent::Battery(BatteryEvent::VoltageStateChanged(BatteryVoltageState::Ok))
+        ));
+
+        let sensor = Packet::new_command(device::SENSOR, 0x3D, 0, vec![0x01]);
+        assert!(matches!(
+            RvrEvent::from_packet(sensor),
+            RvrEvent::Other(p) if p.device_id == device::SENSOR
+        ));
+    }
+
+    #[test]
+    fn test_rvr_event_decodes_sensor_stream() {
+        let mut payload = vec![crate::api::sensors::SensorType::Speed.token()];
+        payload.extend_from_slice(&u32::MAX.to_be_bytes());
+        let mut packet = Packet::new_command(
+            device::SENSOR,
+            sensor_command::STREAMING_SERVICE_DATA_NOTIFY,
+            0,
+            payload,
+        );
+        packet.flags.requests_response = false;
+
+        assert!(matches!(
+            RvrEvent::from_packet(packet),
+            RvrEvent::Sensor(SensorReading::Speed(speed)) if speed == 5.0
+        ));
+    }
+
+    #[test]
+    fn test_rvr_event_decodes_color_detection() {
+        let mut packet = Packet::new_command(
+            device::SENSOR,
+            sensor_command::COLOR_DETECTION_NOTIFY,
+            0,
+            vec![0x10, 0x20, 0x30, 0xF0, 0x02],
+        );
+        packet.flags.requests_response = false;
+
+        assert!(matches!(
+            RvrEvent::from_packet(packet),
+            RvrEvent::ColorDetected(DetectedColor {
+                confidence: 0xF0,
+                classification: 0x02,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rvr_event_decodes_ir_message() {
+        let mut packet = Packet::new_command(
+            device::SENSOR,
+            sensor_command::IR_MESSAGE_RECEIVED_NOTIFY,
+            0,
+            vec![3],
+        );
+        packet.flags.requests_response = false;
+
+        assert!(matches!(
+            RvrEvent::from_packet(packet),
+            RvrEvent::IrMessage(IrMessage { code: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_pump_events_for_duration() {
+        let (tx, rx) = crate::transport::dispatcher::notification_channel();
+
+        let injector = std::thread::spawn(move || {
+            for state in [1, 2, 3] {
+                std::thread::sleep(Duration::from_millis(10));
+                tx.send(battery_notification(state)).unwrap();
+            }
+            // Keep the channel open past the window
+            std::thread::sleep(Duration::from_millis(300));
+        });
+
+        let start = Instant::now();
+        let mut events = Vec::new();
+        let handled = pump_events(&rx, Duration::from_millis(150), |event| events.push(event));
+        let elapsed = start.elapsed();
+
+        assert_eq!(handled, 3);
+        assert_eq!(events.len(), 3);
+        assert!(elapsed >= Duration::from_millis(150), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(300), "{:?}", elapsed);
+        injector.join().unwrap();
+    }
+
+    #[test]
+    fn test_pump_events_stops_when_channel_closes() {
+        let (tx, rx) = crate::transport::dispatcher::notification_channel();
+        tx.send(battery_notification(1)).unwrap();
+        drop(tx);
+
+        let start = Instant::now();
+        assert_eq!(pump_events(&rx, Duration::from_secs(5), |_| {}), 1);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    fn power_notification(command_id: u8, payload: Vec<u8>) -> Packet {
+        let mut packet = Packet::new_command(device::POWER, command_id, 0, payload);
+        packet.flags.requests_response = false;
+        packet
+    }
+
+    #[test]
+    fn test_power_notification_from_packet() {
+        let decode =
+            |cmd, payload| PowerNotification::from_packet(&power_notification(cmd, payload));
+
+        assert_eq!(
+            decode(power_command::WILL_SLEEP_NOTIFY, vec![]),
+            Some(PowerNotification::WillSleep)
+        );
+        assert_eq!(
+            decode(power_command::DID_SLEEP_NOTIFY, vec![]),
+            Some(PowerNotification::DidSleep)
+        );
+        // Battery notifications are decoded by BatteryEvent only
+        assert_eq!(
+            decode(power_command::BATTERY_VOLTAGE_STATE_CHANGE_NOTIFY, vec![2]),
+            None
+        );
+
+        // Responses are not notifications
+        let mut response = power_notification(power_command::DID_SLEEP_NOTIFY, vec![]);
+        response.flags.is_response = true;
+        assert_eq!(PowerNotification::from_packet(&response), None);
+    }
+
+    #[test]
+    fn test_rvr_event_classifies_sleep_notifications() {
+        assert!(matches!(
+            RvrEvent::from_packet(power_notification(power_command::WILL_SLEEP_NOTIFY, vec![])),
+            RvrEvent::Power(PowerNotification::WillSleep)
+        ));
+        // Voltage changes stay battery events
+        assert!(matches!(
+            RvrEvent::from_packet(battery_notification(2)),
+            RvrEvent::Battery(_)
+        ));
+    }
+
+    /// A compass calibration notification carrying `state`
+    pub(crate) fn calibration_notification(command_id: u8, state: u8) -> Packet {
+        let mut packet = Packet::new_command(device::SENSOR, command_id, 0, vec![state]);
+        packet.flags.requests_response = false;
+        packet
+    }
+
+    #[test]
+    fn test_compass_calibration_event_from_packet() {
+        use sensor_command::{
+            MAGNETOMETER_CALIBRATION_COMPLETE_NOTIFY as COMPLETE,
+            MAGNETOMETER_CALIBRATION_PROGRESS_NOTIFY as PROGRESS,
+        };
+        let decode = |cmd, state| {
+            CompassCalibrationEvent::from_packet(&calibration_notification(cmd, state))
+        };
+
+        assert_eq!(decode(PROGRESS, 0), Some(CompassCalibrationEvent::Started));
+        assert_eq!(
+            decode(PROGRESS, 1),
+            Some(CompassCalibrationEvent::InProgress)
+        );
+        assert_eq!(decode(COMPLETE, 1), Some(CompassCalibrationEvent::Complete));
+        assert_eq!(decode(COMPLETE, 0), Some(CompassCalibrationEvent::Failed));
+        assert_eq!(decode(0x3D, 1), None);
+
+        assert!(!CompassCalibrationEvent::Started.is_finished());
+        assert!(CompassCalibrationEvent::Failed.is_finished());
+    }
+
+    #[test]
+    fn test_debouncer_suppresses_rapid_identical_events() {
+        let mut debouncer = BatteryEventDebouncer::new(Duration::from_secs(1));
+        let low = BatteryEvent::VoltageStateChanged(BatteryVoltageState::Low);
+        let start = Instant::now();
+
+        let passed = (0..10)
+            .filter(|i| debouncer.accept_at(low, start + Duration::from_millis(i * 50)))
+            .count();
+        assert_eq!(passed, 1);
+
+        // Once the window has elapsed the same event passes again
+        assert!(debouncer.accept_at(low, start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_debouncer_passes_state_changes() {
+        let mut debouncer = BatteryEventDebouncer::new(Duration::from_secs(1));
+        let low = BatteryEvent::VoltageStateChanged(BatteryVoltageState::Low);
+        let ok = BatteryEvent::VoltageStateChanged(BatteryVoltageState::Ok);
+        let now = Instant::now();
+
+        assert!(debouncer.accept_at(low, now));
+        assert!(debouncer.accept_at(ok, now));
+        assert!(debouncer.accept_at(low, now));
+    }
+}
@@ -0,0 +1,618 @@
+//! LED payload encoding
+//!
+//! All LED commands go through this module so the byte ordering the
+//! firmware expects is defined in exactly one place.
+//!
+//! # Payload Format
+//!
+//! The set-LEDs commands take a one-byte LED bitmask followed by a single
+//! color triplet in **RGB** order (not GRB):
+//!
+//! ```text
+//! [MASK] [RED] [GREEN] [BLUE]
+//! ```
+//!
+//! Every LED selected by the mask is set to the same color.
+
+use crate::api::constants::led_bitmask;
+use crate::api::types::Color;
+use crate::error::{Result, RvrError};
+
+/// Number of RGB LEDs on the RVR
+pub const RVR_LED_COUNT: usize = 10;
+
+/// LED hardware reported by the robot
+///
+/// Firmware that does not report its LEDs is assumed to have the stock
+/// RVR layout (`LedCapabilities::default()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedCapabilities {
+    /// Number of addressable LEDs
+    pub led_count: u8,
+    /// Color channels per LED (3 for RGB)
+    pub channels_per_led: u8,
+}
+
+impl Default for LedCapabilities {
+    fn default() -> Self {
+        Self {
+            led_count: RVR_LED_COUNT as u8,
+            channels_per_led: 3,
+        }
+    }
+}
+
+impl LedCapabilities {
+    /// Decode a capabilities response: `[LED_COUNT] [CHANNELS_PER_LED]`
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        match data {
+            [led_count, channels_per_led, ..] => Ok(Self {
+                led_count: *led_count,
+                channels_per_led: *channels_per_led,
+            }),
+            _ => Err(RvrError::InvalidResponse(format!(
+                "LED capabilities response too short: {} bytes (expected 2)",
+                data.len()
+            ))),
+        }
+    }
+
+    /// Mask selecting every settable LED the robot has
+    ///
+    /// LEDs beyond the six in `led_bitmask` cannot be set, so this is
+    /// `LedMask::ALL` on a stock RVR and smaller on robots with fewer LEDs.
+    pub fn all_mask(self) -> LedMask {
+        let bits = 1u16
+            .checked_shl(self.led_count as u32)
+            .unwrap_or(0)
+            .wrapping_sub(1);
+        LedMask::from_bits_truncate(bits as u8)
+    }
+}
+
+/// Physical position of each RGB LED, by its index on the robot
+///
+/// Indices are used by `io_command::GET_RGB_LED` (0x1C). The first six
+/// also correspond to bit positions in the set-LEDs bitmask (see
+/// `led_bitmask`); the power button and brake lights can only be read.
+///
+/// | Index | LED                 | Bitmask bit |
+/// |-------|---------------------|-------------|
+/// | 0     | Right headlight     | 0x01        |
+/// | 1     | Left headlight      | 0x02        |
+/// | 2     | Left status         | 0x04        |
+/// | 3     | Right status        | 0x08        |
+/// | 4     | Battery door front  | 0x10        |
+/// | 5     | Battery door rear   | 0x20        |
+/// | 6     | Power button front  | -           |
+/// | 7     | Power button rear   | -           |
+/// | 8     | Left brake light    | -           |
+/// | 9     | Right brake light   | -           |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RvrLedIndex {
+    RightHeadlight = 0,
+    LeftHeadlight = 1,
+    LeftStatus = 2,
+    RightStatus = 3,
+    BatteryDoorFront = 4,
+    BatteryDoorRear = 5,
+    PowerButtonFront = 6,
+    PowerButtonRear = 7,
+    LeftBrakeLight = 8,
+    RightBrakeLight = 9,
+}
+
+impl RvrLedIndex {
+    /// All LEDs, in index order
+    pub const ALL: [Self; RVR_LED_COUNT] = [
+        Self::RightHeadlight,
+        Self::LeftHeadlight,
+        Self::LeftStatus,
+        Self::RightStatus,
+        Self::BatteryDoorFront,
+        Self::BatteryDoorRear,
+        Self::PowerButtonFront,
+        Self::PowerButtonRear,
+        Self::LeftBrakeLight,
+        Self::RightBrakeLight,
+    ];
+
+    /// Index of this LED on the robot
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// Look up an LED by index
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    /// Bit selecting this LED in the set-LEDs bitmask, if it has one
+    pub fn mask(self) -> Option<u8> {
+        (self.index() < 6).then(|| 1 << self.index())
+    }
+
+    /// Look up the LED selected by a set-LEDs bitmask
+    ///
+    /// Returns `None` unless `mask` selects exactly one known LED.
+    pub fn from_mask(mask: u8) -> Option<Self> {
+        if mask.count_ones() != 1 || LedMask::from_bits(mask).is_none() {
+            return None;
+        }
+        Self::from_index(mask.trailing_zeros() as u8)
+    }
+}
+
+/// A set of settable LEDs, for the set-LEDs commands
+///
+/// Wraps the `led_bitmask` bits so only known LEDs can be selected. Masks
+/// combine with `|`:
+///
+/// ```
+/// use sphero_rvr::api::LedMask;
+///
+/// let headlights = LedMask::LEFT_HEADLIGHT | LedMask::RIGHT_HEADLIGHT;
+/// assert_eq!(headlights.bits(), 0x03);
+/// assert!(LedMask::ALL.contains(headlights));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LedMask(u8);
+
+impl LedMask {
+    /// No LEDs
+    pub const EMPTY: Self = Self(0);
+    /// Right headlight
+    pub const RIGHT_HEADLIGHT: Self = Self(led_bitmask::RIGHT_HEADLIGHT);
+    /// Left headlight
+    pub const LEFT_HEADLIGHT: Self = Self(led_bitmask::LEFT_HEADLIGHT);
+    /// Left status indicator
+    pub const LEFT_STATUS: Self = Self(led_bitmask::LEFT_STATUS);
+    /// Right status indicator
+    pub const RIGHT_STATUS: Self = Self(led_bitmask::RIGHT_STATUS);
+    /// Battery door LEDs (front)
+    pub const BATTERY_DOOR_FRONT: Self = Self(led_bitmask::BATTERY_DOOR_FRONT);
+    /// Battery door LEDs (rear)
+    pub const BATTERY_DOOR_REAR: Self = Self(led_bitmask::BATTERY_DOOR_REAR);
+    /// Every settable LED
+    pub const ALL: Self = Self(led_bitmask::ALL);
+
+    /// Raw bitmask for the set-LEDs payload
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Build a mask from raw bits, or `None` if any bit is not a known LED
+    pub const fn from_bits(bits: u8) -> Option<Self> {
+        if bits & !led_bitmask::ALL == 0 {
+            Some(Self(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Build a mask from raw bits, dropping any that are not a known LED
+    pub const fn from_bits_truncate(bits: u8) -> Self {
+        Self(bits & led_bitmask::ALL)
+    }
+
+    /// Returns true if no LEDs are selected
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if every LED in `other` is also in `self`
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// LEDs in either mask
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// LEDs in both masks
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl std::ops::BitOr for LedMask {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.union(other)
+    }
+}
+
+impl std::ops::BitOrAssign for LedMask {
+    fn bitor_assign(&mut self, other: Self) {
+        *self = self.union(other);
+    }
+}
+
+impl std::ops::BitAnd for LedMask {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        self.intersection(other)
+    }
+}
+
+impl From<LedPosition> for LedMask {
+    fn from(position: LedPosition) -> Self {
+        Self(position.mask())
+    }
+}
+
+/// An individually addressable LED group on the robot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedPosition {
+    /// Right headlight
+    RightHeadlight,
+    /// Left headlight
+    LeftHeadlight,
+    /// Left status indicator
+    LeftStatus,
+    /// Right status indicator
+    RightStatus,
+    /// Battery door LEDs (front)
+    BatteryDoorFront,
+    /// Battery door LEDs (rear)
+    BatteryDoorRear,
+}
+
+impl LedPosition {
+    /// All settable LEDs, in index order
+    pub const ALL: [Self; 6] = [
+        Self::RightHeadlight,
+        Self::LeftHeadlight,
+        Self::LeftStatus,
+        Self::RightStatus,
+        Self::BatteryDoorFront,
+        Self::BatteryDoorRear,
+    ];
+
+    /// Index of this LED on the robot
+    pub fn index(self) -> RvrLedIndex {
+        match self {
+            Self::RightHeadlight => RvrLedIndex::RightHeadlight,
+            Self::LeftHeadlight => RvrLedIndex::LeftHeadlight,
+            Self::LeftStatus => RvrLedIndex::LeftStatus,
+            Self::RightStatus => RvrLedIndex::RightStatus,
+            Self::BatteryDoorFront => RvrLedIndex::BatteryDoorFront,
+            Self::BatteryDoorRear => RvrLedIndex::BatteryDoorRear,
+        }
+    }
+
+    /// Bitmask selecting this LED in the set-LEDs payload
+    pub fn mask(self) -> u8 {
+        match self {
+            Self::RightHeadlight => led_bitmask::RIGHT_HEADLIGHT,
+            Self::LeftHeadlight => led_bitmask::LEFT_HEADLIGHT,
+            Self::LeftStatus => led_bitmask::LEFT_STATUS,
+            Self::RightStatus => led_bitmask::RIGHT_STATUS,
+            Self::BatteryDoorFront => led_bitmask::BATTERY_DOOR_FRONT,
+            Self::BatteryDoorRear => led_bitmask::BATTERY_DOOR_REAR,
+        }
+    }
+}
+
+/// Settable LEDs grouped by position along the robot, front to back
+///
+/// LEDs in the same row (the pair of headlights, the pair of status
+/// indicators) get the same color in a gradient.
+const FRONT_TO_BACK_ROWS: [&[LedPosition]; 4] = [
+    &[LedPosition::LeftHeadlight, LedPosition::RightHeadlight],
+    &[LedPosition::LeftStatus, LedPosition::RightStatus],
+    &[LedPosition::BatteryDoorFront],
+    &[LedPosition::BatteryDoorRear],
+];
+
+/// Colors for a front-to-back gradient from `start` to `end`
+///
+/// The front row is `start`, the rear row is `end`, and rows in between
+/// are interpolated evenly with `Color::lerp`.
+pub fn gradient_updates(start: Color, end: Color) -> Vec<(LedPosition, Color)> {
+    let steps = (FRONT_TO_BACK_ROWS.len() - 1) as f32;
+    FRONT_TO_BACK_ROWS
+        .iter()
+        .enumerate()
+        .flat_map(|(row, positions)| {
+            let color = start.lerp(end, row as f32 / steps);
+            positions.iter().map(move |&position| (position, color))
+        })
+        .collect()
+}
+
+/// Merge per-LED colors into one mask per distinct color
+///
+/// Groups are in order of each color's first use. If an LED is listed
+/// more than once, only its last color is kept.
+pub fn group_by_color(colors: &[(LedPosition, Color)]) -> Vec<(LedMask, Color)> {
+    let mut groups: Vec<(LedMask, Color)> = Vec::new();
+    for (i, &(position, color)) in colors.iter().enumerate() {
+        let overridden = colors[i + 1..].iter().any(|&(later, _)| later == position);
+        if overridden {
+            continue;
+        }
+        match groups
+            .iter_mut()
+            .find(|(_, group_color)| *group_color == color)
+        {
+            Some((mask, _)) => *mask |= position.into(),
+            None => groups.push((position.into(), color)),
+        }
+    }
+    groups
+}
+
+/// Outcome of a batch of LED updates
+///
+/// Each update is sent as its own command, and a failed update does not
+/// stop the rest of the batch.
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    /// Updates that were applied, in request order
+    pub succeeded: Vec<LedPosition>,
+    /// Updates that failed, with the error for each
+    pub failed: Vec<(LedPosition, RvrError)>,
+}
+
+impl BatchResult {
+    /// Returns true if every update succeeded
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Apply each update with `send`, collecting per-update results
+pub(crate) fn run_batch(
+    updates: &[(LedPosition, Color)],
+    mut send: impl FnMut(LedPosition, Color) -> Result<()>,
+) -> BatchResult {
+    let mut result = BatchResult::default();
+    for &(position, color) in updates {
+        match send(position, color) {
+            Ok(()) => result.succeeded.push(position),
+            Err(e) => result.failed.push((position, e)),
+        }
+    }
+    result
+}
+
+/// Build the payload for setting the LEDs selected by `mask` to `color`
+///
+/// # Arguments
+///
+/// * `mask` - Bitmask of LEDs to set (see `led_bitmask` constants)
+/// * `color` - Color applied to every selected LED
+///
+/// # Returns
+///
+/// `[MASK, R, G, B]`
+pub fn led_payload_for(mask: u8, color: Color) -> Vec<u8> {
+    let [r, g, b] = color.to_bytes();
+    vec![mask, r, g, b]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_led_payload_all_leds() {
+        let payload = led_payload_for(led_bitmask::ALL, Color::new(0x12, 0x34, 0x56));
+        assert_eq!(payload, vec![0x3F, 0x12, 0x34, 0x56]);
+    }
+
+    #[test]
+    fn test_led_payload_headlights() {
+        let mask = led_bitmask::LEFT_HEADLIGHT | led_bitmask::RIGHT_HEADLIGHT;
+        let payload = led_payload_for(mask, Color::ORANGE);
+        assert_eq!(payload, vec![0x03, 255, 165, 0]);
+    }
+
+    #[test]
+    fn test_led_payload_is_rgb_order() {
+        // Pure green must land in the second color byte, not the first
+        let payload = led_payload_for(led_bitmask::LEFT_STATUS, Color::GREEN);
+        assert_eq!(payload, vec![0x04, 0x00, 0xFF, 0x00]);
+    }
+
+    #[test]
+    fn test_gradient_updates_front_to_back() {
+        let updates = gradient_updates(Color::new(0, 0, 0), Color::new(255, 30, 90));
+        let color_of = |position| {
+            updates
+                .iter()
+                .find(|(p, _)| *p == position)
+                .map(|&(_, color)| color)
+                .unwrap()
+        };
+
+        assert_eq!(updates.len(), 6);
+        assert_eq!(color_of(LedPosition::LeftHeadlight), Color::new(0, 0, 0));
+        assert_eq!(color_of(LedPosition::RightHeadlight), Color::new(0, 0, 0));
+        assert_eq!(color_of(LedPosition::LeftStatus), Color::new(85, 10, 30));
+        assert_eq!(color_of(LedPosition::RightStatus), Color::new(85, 10, 30));
+        assert_eq!(
+            color_of(LedPosition::BatteryDoorFront),
+            Color::new(170, 20, 60)
+        );
+        assert_eq!(
+            color_of(LedPosition::BatteryDoorRear),
+            Color::new(255, 30, 90)
+        );
+    }
+
+    #[test]
+    fn test_led_position_masks_are_distinct() {
+        let positions = [
+            LedPosition::RightHeadlight,
+            LedPosition::LeftHeadlight,
+            LedPosition::LeftStatus,
+            LedPosition::RightStatus,
+            LedPosition::BatteryDoorFront,
+            LedPosition::BatteryDoorRear,
+        ];
+        let combined = positions.iter().fold(0u8, |acc, p| {
+            assert_eq!(acc & p.mask(), 0, "{:?} overlaps", p);
+            acc | p.mask()
+        });
+        assert_eq!(combined, led_bitmask::ALL);
+    }
+
+    #[test]
+    fn test_led_mask_all_is_union_of_leds() {
+        let combined = LedMask::RIGHT_HEADLIGHT
+            | LedMask::LEFT_HEADLIGHT
+            | LedMask::LEFT_STATUS
+            | LedMask::RIGHT_STATUS
+            | LedMask::BATTERY_DOOR_FRONT
+            | LedMask::BATTERY_DOOR_REAR;
+        assert_eq!(combined, LedMask::ALL);
+        assert_eq!(LedMask::ALL.bits(), led_bitmask::ALL);
+        assert_eq!(
+            LedMask::from(LedPosition::BatteryDoorRear),
+            LedMask::BATTERY_DOOR_REAR
+        );
+    }
+
+    #[test]
+    fn test_led_mask_rejects_unknown_bits() {
+        assert_eq!(LedMask::from_bits(0x05), Some(LedMask(0x05)));
+        assert_eq!(LedMask::from_bits(0x40), None);
+        assert_eq!(LedMask::from_bits(0xFF), None);
+        assert_eq!(LedMask::from_bits_truncate(0xFF), LedMask::ALL);
+        assert!(LedMask::from_bits_truncate(0xC0).is_empty());
+    }
+
+    #[test]
+    fn test_led_capabilities_decode() {
+        let caps = LedCapabilities::from_bytes(&[4, 3]).unwrap();
+        assert_eq!(caps.led_count, 4);
+        assert_eq!(caps.channels_per_led, 3);
+        assert!(LedCapabilities::from_bytes(&[4]).is_err());
+    }
+
+    #[test]
+    fn test_all_leds_payload_sized_by_capabilities() {
+        // Stock RVR: every settable LED
+        let caps = LedCapabilities::default();
+        assert_eq!(caps.all_mask(), LedMask::ALL);
+        assert_eq!(
+            led_payload_for(caps.all_mask().bits(), Color::RED),
+            vec![0x3F, 255, 0, 0]
+        );
+
+        // A robot reporting four LEDs only gets the first four bits
+        let caps = LedCapabilities::from_bytes(&[4, 3]).unwrap();
+        assert_eq!(
+            led_payload_for(caps.all_mask().bits(), Color::RED),
+            vec![0x0F, 255, 0, 0]
+        );
+        assert!(LedCapabilities::from_bytes(&[0, 3])
+            .unwrap()
+            .all_mask()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_batch_reports_partial_failure() {
+        let updates = [
+            (LedPosition::LeftHeadlight, Color::WHITE),
+            (LedPosition::LeftStatus, Color::RED),
+            (LedPosition::RightHeadlight, Color::WHITE),
+        ];
+
+        let mut sent = Vec::new();
+        let result = run_batch(&updates, |position, color| {
+            sent.push(led_payload_for(position.mask(), color));
+            if position == LedPosition::LeftStatus {
+                Err(RvrError::Timeout)
+            } else {
+                Ok(())
+            }
+        });
+
+        // The failure does not abort the remaining updates
+        assert_eq!(sent.len(), 3);
+        assert!(!result.is_complete());
+        assert_eq!(
+            result.succeeded,
+            vec![LedPosition::LeftHeadlight, LedPosition::RightHeadlight]
+        );
+        assert_eq!(result.failed.len(), 1);
+        assert_eq!(result.failed[0].0, LedPosition::LeftStatus);
+        assert!(matches!(result.failed[0].1, RvrError::Timeout));
+    }
+
+    #[test]
+    fn test_led_index_covers_all_slots() {
+        assert_eq!(RvrLedIndex::ALL.len(), RVR_LED_COUNT);
+        for (i, led) in RvrLedIndex::ALL.iter().enumerate() {
+            assert_eq!(led.index() as usize, i);
+            assert_eq!(RvrLedIndex::from_index(i as u8), Some(*led));
+        }
+        assert_eq!(RvrLedIndex::from_index(RVR_LED_COUNT as u8), None);
+    }
+
+    #[test]
+    fn test_led_index_matches_bitmask() {
+        let positions = [
+            LedPosition::RightHeadlight,
+            LedPosition::LeftHeadlight,
+            LedPosition::LeftStatus,
+            LedPosition::RightStatus,
+            LedPosition::BatteryDoorFront,
+            LedPosition::BatteryDoorRear,
+        ];
+        for position in positions {
+            assert_eq!(
+                position.index().mask(),
+                Some(position.mask()),
+                "{:?}",
+                position
+            );
+        }
+
+        let maskable: u8 = RvrLedIndex::ALL.iter().filter_map(|led| led.mask()).sum();
+        assert_eq!(maskable, led_bitmask::ALL);
+    }
+
+    #[test]
+    fn test_group_by_color() {
+        let groups = group_by_color(&[
+            (LedPosition::LeftHeadlight, Color::WHITE),
+            (LedPosition::BatteryDoorRear, Color::RED),
+            (LedPosition::RightHeadlight, Color::WHITE),
+            // Overrides the earlier color for this LED
+            (LedPosition::BatteryDoorRear, Color::BLUE),
+        ]);
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    LedMask::LEFT_HEADLIGHT | LedMask::RIGHT_HEADLIGHT,
+                    Color::WHITE
+                ),
+                (LedMask::BATTERY_DOOR_REAR, Color::BLUE),
+            ]
+        );
+        assert!(group_by_color(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_led_index_from_mask() {
+        for led in RvrLedIndex::ALL {
+            if let Some(mask) = led.mask() {
+                assert_eq!(RvrLedIndex::from_mask(mask), Some(led));
+            }
+        }
+        // None, several, or unknown LEDs
+        assert_eq!(RvrLedIndex::from_mask(0), None);
+        assert_eq!(RvrLedIndex::from_mask(led_bitmask::ALL), None);
+        assert_eq!(RvrLedIndex::from_mask(0x40), None);
+    }
+}
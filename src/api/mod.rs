@@ -13,7 +13,7 @@
 //! use sphero_rvr::api::types::Color;
 //!
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> {
-//! let mut rvr = SpheroRvr::connect("/dev/serial0")?;
+//! let rvr = SpheroRvr::connect("/dev/serial0")?;
 //!
 //! rvr.wake()?;
 //! rvr.set_all_leds(Color::GREEN)?;
@@ -22,10 +22,44 @@
 //! # }
 //! ```
 
+// Only the constants are available without `std`
+#[cfg(feature = "std")]
+pub mod animation;
+#[cfg(feature = "std")]
 pub mod client;
+#[cfg(feature = "std")]
+pub mod command;
 pub mod constants;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+pub mod led;
+#[cfg(feature = "std")]
+pub mod navigation;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod sensors;
+#[cfg(feature = "std")]
 pub mod types;
+#[cfg(feature = "std")]
+mod worker;
 
 // Re-export main types
-pub use client::SpheroRvr;
-pub use types::{BatteryState, Color, FirmwareVersion};
+#[cfg(feature = "std")]
+pub use client::{
+    RvrConfig, SpheroRvr, COLOR_DETECTION_INTERVAL_MS, DEFAULT_KEEP_AWAKE_INTERVAL,
+    MIN_STREAMING_INTERVAL_MS,
+};
+#[cfg(feature = "std")]
+pub use led::{BatchResult, LedCapabilities, LedMask, LedPosition, RvrLedIndex};
+#[cfg(feature = "std")]
+pub use replay::replay;
+#[cfg(feature = "std")]
+pub use sensors::{SensorConfig, SensorReading, SensorType};
+#[cfg(feature = "std")]
+pub use types::{
+    AccelReading, BatteryState, BatteryVoltageState, Color, DetectedColor, DeviceClock, DriveFlags,
+    FirmwareVersion, ImuReading, IrMessage, Locator, MotorMode, PowerStatus, ProtectionState,
+    RobotState, RvrModel,
+};
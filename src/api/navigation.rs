@@ -0,0 +1,214 @@
+//! Goal-directed driving built on the locator
+//!
+//! Locator coordinates follow the RVR SDK: with yaw 0, +Y is straight ahead
+//! and +X is to the right. Headings are degrees clockwise from +Y, the same
+//! convention `SpheroRvr::drive_with_heading` uses.
+
+use crate::api::client::SpheroRvr;
+use crate::api::types::Locator;
+use crate::error::{Result, RvrError};
+use std::time::{Duration, Instant};
+
+/// Heading in degrees (0-359) that points from `from` toward `to`
+pub fn heading_to(from: Locator, to: Locator) -> u16 {
+    let degrees = (to.x - from.x).atan2(to.y - from.y).to_degrees();
+    // Rounding 359.6 up gives 360, which wraps to 0
+    (degrees.rem_euclid(360.0).round() as u16) % 360
+}
+
+impl SpheroRvr {
+    /// Drive to a locator position, then brake
+    ///
+    /// Steers toward the target with `drive_with_heading` on every locator
+    /// stream sample until the robot is within `tolerance_m` of it.
+    /// Streaming must be configured with `SensorType::Locator` and running.
+    /// Reset the locator first (`reset_locator`) to drive relative to the
+    /// current position. A tolerance that is small compared to how far the
+    /// robot travels between samples at `speed` may overshoot and circle;
+    /// lower the speed or the streaming interval for precise stops.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Target position in meters
+    /// * `speed` - Speed (0-255), subject to `set_speed_limit`
+    /// * `tolerance_m` - How close counts as arrived, in meters
+    /// * `max_duration` - Give up after this long
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if the target is not finite,
+    /// `tolerance_m` is not a positive number, or the locator is not being
+    /// streamed, and `RvrError::Timeout` if the target is not reached
+    /// within `max_duration`. The robot is braked whenever driving started.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::SpheroRvr;
+    /// # use sphero_rvr::api::{Locator, SensorType};
+    /// # use std::time::Duration;
+    /// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+    /// rvr.configure_sensor_streaming(&[SensorType::Locator], 50)?;
+    /// rvr.start_sensor_streaming()?;
+    /// rvr.reset_locator()?;
+    /// // Half a meter ahead and half a meter to the right
+    /// let target = Locator { x: 0.5, y: 0.5 };
+    /// rvr.drive_to_xy(target, 60, 0.05, Duration::from_secs(10))?;
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn drive_to_xy(
+        &self,
+        target: Locator,
+        speed: u8,
+        tolerance_m: f32,
+        max_duration: Duration,
+    ) -> Result<()> {
+        if !(target.x.is_finite() && target.y.is_finite()) {
+            return Err(RvrError::InvalidParameter(format!(
+                "target must be finite, got {:?}",
+                target
+            )));
+        }
+        if !(tolerance_m.is_finite() && tolerance_m > 0.0) {
+            return Err(RvrError::InvalidParameter(format!(
+                "tolerance must be a positive number of meters, got {}",
+                tolerance_m
+            )));
+        }
+        tracing::debug!(
+            "Driving to ({}, {}) m (speed={}, tolerance={} m)",
+            target.x,
+            target.y,
+            speed,
+            tolerance_m
+        );
+
+        // Fail before driving if the locator is not streaming
+        let mut position = self.get_locator_position()?;
+        let deadline = Instant::now() + max_duration;
+        let outcome = loop {
+            let remaining = position.distance_to(target);
+            if remaining <= tolerance_m {
+                break Ok(());
+            }
+            if Instant::now() >= deadline {
+                tracing::warn!("Gave up {:.2} m short of {:?}", remaining, target);
+                break Err(RvrError::Timeout);
+            }
+            if let Err(e) = self.drive_with_heading(speed, heading_to(position, target), 0) {
+                break Err(e);
+            }
+            position = match self.get_locator_position() {
+                Ok(position) => position,
+                Err(e) => break Err(e),
+            };
+        };
+
+        self.brake_after(outcome)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::client::tests::virtual_rvr;
+    use crate::api::constants::{device, drive_command, drive_mode, error_code};
+    use crate::api::SensorType;
+    use crate::transport::VirtualRvrHandle;
+
+    fn at(x: f32, y: f32) -> Locator {
+        Locator { x, y }
+    }
+
+    /// A client on a `VirtualRvr` streaming the locator
+    pub(crate) fn streaming_locator() -> (SpheroRvr, VirtualRvrHandle) {
+        let (rvr, handle) = virtual_rvr();
+        rvr.wake().unwrap();
+        rvr.configure_sensor_streaming(&[SensorType::Locator], 40)
+            .unwrap();
+        rvr.start_sensor_streaming().unwrap();
+        (rvr, handle)
+    }
+
+    /// Assert the last command sent was a braking stop
+    pub(crate) fn assert_braked(handle: &VirtualRvrHandle) {
+        let last = handle.packets().pop().unwrap();
+        assert_eq!(
+            (last.device_id, last.command_id),
+            (device::DRIVE, drive_command::STOP)
+        );
+        assert_eq!(last.payload, vec![drive_mode::BRAKE]);
+    }
+
+    #[test]
+    fn test_heading_to_cardinal_directions() {
+        let origin = at(0.0, 0.0);
+        assert_eq!(heading_to(origin, at(0.0, 0.1)), 0);
+        assert_eq!(heading_to(origin, at(0.1, 0.0)), 90);
+        assert_eq!(heading_to(origin, at(0.0, -0.1)), 180);
+        assert_eq!(heading_to(origin, at(-0.1, 0.0)), 270);
+        assert_eq!(heading_to(at(0.05, 0.05), at(0.15, 0.15)), 45);
+        // Just left of straight ahead rounds to 0, not 360
+        assert_eq!(heading_to(origin, at(-0.00001, 0.1)), 0);
+    }
+
+    #[test]
+    fn test_drive_to_xy_arrives_and_brakes() {
+        let (rvr, handle) = streaming_locator();
+        let target = at(0.08, 0.08);
+
+        rvr.drive_to_xy(target, 64, 0.03, Duration::from_secs(5))
+            .unwrap();
+
+        assert_braked(&handle);
+        let position = handle.position();
+        assert!(position.distance_to(target) < 0.05, "{:?}", position);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_drive_to_xy_times_out_and_brakes() {
+        let (rvr, handle) = streaming_locator();
+
+        // Speed 0 never gets there
+        assert!(matches!(
+            rvr.drive_to_xy(at(0.5, 0.0), 0, 0.05, Duration::from_millis(200)),
+            Err(RvrError::Timeout)
+        ));
+        assert_braked(&handle);
+
+        // A failed brake does not hide the timeout
+        handle.set_response_code(device::DRIVE, drive_command::STOP, error_code::FAILED);
+        assert!(matches!(
+            rvr.drive_to_xy(at(0.5, 0.0), 0, 0.05, Duration::from_millis(200)),
+            Err(RvrError::Timeout)
+        ));
+        assert_braked(&handle);
+        rvr.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_drive_to_xy_rejects_bad_parameters() {
+        let (rvr, handle) = virtual_rvr();
+        let limit = Duration::from_secs(1);
+
+        for (target, tolerance) in [
+            (at(f32::NAN, 0.0), 0.05),
+            (at(0.0, 0.0), 0.0),
+            (at(0.0, 0.0), -0.01),
+        ] {
+            assert!(matches!(
+                rvr.drive_to_xy(target, 50, tolerance, limit),
+                Err(RvrError::InvalidParameter(_))
+            ));
+        }
+        // Not streaming the locator
+        assert!(matches!(
+            rvr.drive_to_xy(at(0.5, 0.0), 50, 0.05, limit),
+            Err(RvrError::InvalidParameter(_))
+        ));
+        // Nothing was sent
+        assert!(handle.commands().is_empty());
+        rvr.shutdown().unwrap();
+    }
+}
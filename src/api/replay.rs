@@ -0,0 +1,36 @@
+//! Replay of recorded command sequences
+//!
+//! Recordings are captured with `SpheroRvr::set_recorder` (see the
+//! `transport::recorder` module for the format) and replayed here against
+//! a connected robot.
+
+use crate::api::client::SpheroRvr;
+use crate::error::Result;
+use crate::transport::recorder::{read_recording, replay_with};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Replay a recorded command sequence with its original timing
+///
+/// Each packet is re-sent with a fresh sequence number. Commands that
+/// requested a response wait for it and fail on an error response.
+///
+/// # Returns
+///
+/// Returns the number of packets sent
+///
+/// # Example
+///
+/// ```no_run
+/// # use sphero_rvr::SpheroRvr;
+/// # let rvr = SpheroRvr::connect("/dev/serial0").unwrap();
+/// let sent = sphero_rvr::api::replay("show.rec", &rvr).unwrap();
+/// println!("Replayed {} commands", sent);
+/// ```
+pub fn replay(path: impl AsRef<Path>, rvr: &SpheroRvr) -> Result<usize> {
+    let recording = read_recording(BufReader::new(File::open(path)?))?;
+
+    tracing::debug!("Replaying {} recorded packets", recording.len());
+    replay_with(&recording, |packet| rvr.send_packet(packet.clone()))
+}
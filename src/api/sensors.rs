@@ -0,0 +1,617 @@
+//! Sensor streaming configuration and decoding
+//!
+//! The RVR streams sensor data as `sensor_command::STREAMING_SERVICE_DATA_NOTIFY`
+//! notifications. Each notification starts with a one-byte token naming the
+//! stream it belongs to, followed by the stream's samples:
+//!
+//! ```text
+//! [TOKEN] [SAMPLE 0] [SAMPLE 1] ...
+//! ```
+//!
+//! This crate configures one stream per sensor and uses the sensor's
+//! `SensorType::token` as the token, so a notification can be decoded on
+//! its own with `SensorReading::from_packet`.
+//!
+//! A stream can also carry several sensors under one token. The samples
+//! are then concatenated in the order the sensors were configured, with
+//! nothing marking where one sensor ends and the next begins; a
+//! `SensorConfig` remembers that order so `SensorConfig::decode` can split
+//! the payload back up.
+//!
+//! Samples are unsigned integers (u32 BE, or u8 for color detection)
+//! scaled linearly over each sensor's range: 0 is the range minimum and the
+//! integer's maximum value is the range maximum.
+
+use crate::api::constants::{device, sensor_command};
+use crate::error::{Result, RvrError};
+use crate::protocol::packet::Packet;
+
+/// Width of each streamed sample, as encoded in the configuration payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum SampleSize {
+    /// 8-bit samples
+    U8 = 0x00,
+    /// 32-bit samples
+    U32 = 0x02,
+}
+
+impl SampleSize {
+    fn bytes(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U32 => 4,
+        }
+    }
+
+    fn max(self) -> f32 {
+        match self {
+            Self::U8 => u8::MAX as f32,
+            Self::U32 => u32::MAX as f32,
+        }
+    }
+}
+
+/// A sensor that can be streamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SensorType {
+    /// Orientation quaternion (W, X, Y, Z; -1.0 to 1.0)
+    Quaternion,
+    /// Orientation angles (pitch, roll, yaw; degrees)
+    Imu,
+    /// Acceleration (X, Y, Z; g)
+    Accelerometer,
+    /// Color under the robot (R, G, B, color index, confidence)
+    ColorDetection,
+    /// Position (X, Y; meters)
+    Locator,
+    /// Velocity (X, Y; m/s)
+    Velocity,
+    /// Ground speed (m/s)
+    Speed,
+    /// Ambient light (lux)
+    AmbientLight,
+}
+
+impl SensorType {
+    /// Every streamable sensor
+    pub const ALL: [Self; 8] = [
+        Self::Quaternion,
+        Self::Imu,
+        Self::Accelerometer,
+        Self::ColorDetection,
+        Self::Locator,
+        Self::Velocity,
+        Self::Speed,
+        Self::AmbientLight,
+    ];
+
+    /// Streaming service ID the firmware uses for this sensor
+    pub fn service_id(self) -> u16 {
+        match self {
+            Self::Quaternion => 0x0000,
+            Self::Imu => 0x0001,
+            Self::Accelerometer => 0x0002,
+            Self::ColorDetection => 0x0003,
+            Self::Locator => 0x0006,
+            Self::Velocity => 0x0007,
+            Self::Speed => 0x0008,
+            Self::AmbientLight => 0x000A,
+        }
+    }
+
+    /// Token identifying this sensor's stream in notifications
+    pub fn token(self) -> u8 {
+        self.service_id() as u8 + 1
+    }
+
+    /// Look up a sensor by its stream token
+    pub fn from_token(token: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|sensor| sensor.token() == token)
+    }
+
+    fn sample_size(self) -> SampleSize {
+        match self {
+            Self::ColorDetection => SampleSize::U8,
+            _ => SampleSize::U32,
+        }
+    }
+
+    /// Range of each sample, in the order they are streamed
+    fn ranges(self) -> &'static [(f32, f32)] {
+        match self {
+            Self::Quaternion => &[(-1.0, 1.0); 4],
+            Self::Imu => &[(-180.0, 180.0), (-90.0, 90.0), (-180.0, 180.0)],
+            Self::Accelerometer => &[(-16.0, 16.0); 3],
+            Self::ColorDetection => &[(0.0, 255.0); 5],
+            Self::Locator => &[(-16000.0, 16000.0); 2],
+            Self::Velocity => &[(-5.0, 5.0); 2],
+            Self::Speed => &[(0.0, 5.0)],
+            Self::AmbientLight => &[(0.0, 120000.0)],
+        }
+    }
+
+    /// Number of values in each sample
+    #[cfg(any(test, feature = "test-support"))]
+    pub(crate) fn sample_count(self) -> usize {
+        self.ranges().len()
+    }
+
+    /// Length of one notification's sample data (excluding the token)
+    fn data_len(self) -> usize {
+        self.ranges().len() * self.sample_size().bytes()
+    }
+}
+
+/// The sensors grouped under one stream token, in configuration order
+///
+/// The order matters: it is the order the sensors' samples appear in each
+/// notification.
+///
+/// # Example
+///
+/// ```
+/// use sphero_rvr::api::sensors::SensorConfig;
+/// use sphero_rvr::api::SensorType;
+///
+/// let config = SensorConfig::new(0x20, &[SensorType::Imu, SensorType::Accelerometer])?;
+/// assert_eq!(config.data_len(), 24);
+/// # Ok::<(), sphero_rvr::error::RvrError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensorConfig {
+    token: u8,
+    sensors: Vec<SensorType>,
+}
+
+impl SensorConfig {
+    /// Group `sensors` under `token`, in the given order
+    ///
+    /// Pick a token that no other configured stream uses; the per-sensor
+    /// tokens are `SensorType::token`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if `sensors` is empty or lists
+    /// a sensor twice.
+    pub fn new(token: u8, sensors: &[SensorType]) -> Result<Self> {
+        if sensors.is_empty() {
+            return Err(RvrError::InvalidParameter(
+                "a stream needs at least one sensor".to_string(),
+            ));
+        }
+        for (i, sensor) in sensors.iter().enumerate() {
+            if sensors[..i].contains(sensor) {
+                return Err(RvrError::InvalidParameter(format!(
+                    "{:?} is listed twice",
+                    sensor
+                )));
+            }
+        }
+        Ok(Self {
+            token,
+            sensors: sensors.to_vec(),
+        })
+    }
+
+    /// One sensor on its own token, as `configure_sensor_streaming` sets up
+    pub fn single(sensor: SensorType) -> Self {
+        Self {
+            token: sensor.token(),
+            sensors: vec![sensor],
+        }
+    }
+
+    /// Token identifying this stream in notifications
+    pub fn token(&self) -> u8 {
+        self.token
+    }
+
+    /// Sensors in the order their samples are streamed
+    pub fn sensors(&self) -> &[SensorType] {
+        &self.sensors
+    }
+
+    /// Length of one notification's sample data (excluding the token)
+    pub fn data_len(&self) -> usize {
+        self.sensors.iter().map(|sensor| sensor.data_len()).sum()
+    }
+
+    /// Configuration payload for `sensor_command::SET_SENSOR_STREAMING`
+    ///
+    /// `[TOKEN]` followed by `[SERVICE_ID: u16 BE] [SAMPLE_SIZE]` per sensor
+    pub fn config_payload(&self) -> Vec<u8> {
+        let mut payload = vec![self.token];
+        for sensor in &self.sensors {
+            payload.extend_from_slice(&sensor.service_id().to_be_bytes());
+            payload.push(sensor.sample_size() as u8);
+        }
+        payload
+    }
+
+    /// Decode one of this stream's notifications into a reading per sensor
+    ///
+    /// Readings are returned in configuration order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidResponse` if the packet is not a sensor
+    /// streaming notification, carries a different token, or its payload
+    /// is the wrong length.
+    pub fn decode(&self, packet: &Packet) -> Result<Vec<SensorReading>> {
+        let (token, data) = stream_payload(packet)?;
+        if token != self.token {
+            return Err(RvrError::InvalidResponse(format!(
+                "Sensor stream token {} does not match configured token {}",
+                token, self.token
+            )));
+        }
+        if data.len() != self.data_len() {
+            return Err(RvrError::InvalidResponse(format!(
+                "Stream {} sample data is {} bytes (expected {})",
+                token,
+                data.len(),
+                self.data_len()
+            )));
+        }
+
+        let mut rest = data;
+        Ok(self
+            .sensors
+            .iter()
+            .map(|&sensor| {
+                let (samples, tail) = rest.split_at(sensor.data_len());
+                rest = tail;
+                decode_reading(sensor, samples)
+            })
+            .collect())
+    }
+}
+
+/// A decoded sensor streaming notification
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorReading {
+    /// Orientation quaternion
+    Quaternion { w: f32, x: f32, y: f32, z: f32 },
+    /// Orientation angles in degrees
+    Imu { pitch: f32, roll: f32, yaw: f32 },
+    /// Acceleration in g
+    Accelerometer { x: f32, y: f32, z: f32 },
+    /// Detected color, its palette index, and the detector's confidence
+    ColorDetection {
+        r: u8,
+        g: u8,
+        b: u8,
+        index: u8,
+        confidence: u8,
+    },
+    /// Position in meters
+    Locator { x: f32, y: f32 },
+    /// Velocity in m/s
+    Velocity { x: f32, y: f32 },
+    /// Ground speed in m/s
+    Speed(f32),
+    /// Ambient light in lux
+    AmbientLight(f32),
+}
+
+impl SensorReading {
+    /// Decode a streaming notification
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidResponse` if the packet is not a sensor
+    /// streaming notification, its token is unknown, or its payload is the
+    /// wrong length.
+    pub fn from_packet(packet: &Packet) -> Result<Self> {
+        let (token, data) = stream_payload(packet)?;
+        let sensor = SensorType::from_token(token).ok_or_else(|| {
+            RvrError::InvalidResponse(format!("Unknown sensor stream token: {}", token))
+        })?;
+        if data.len() != sensor.data_len() {
+            return Err(RvrError::InvalidResponse(format!(
+                "{:?} sample data is {} bytes (expected {})",
+                sensor,
+                data.len(),
+                sensor.data_len()
+            )));
+        }
+
+        Ok(decode_reading(sensor, data))
+    }
+
+    /// The sensor this reading came from
+    pub fn sensor(&self) -> SensorType {
+        match self {
+            Self::Quaternion { .. } => SensorType::Quaternion,
+            Self::Imu { .. } => SensorType::Imu,
+            Self::Accelerometer { .. } => SensorType::Accelerometer,
+            Self::ColorDetection { .. } => SensorType::ColorDetection,
+            Self::Locator { .. } => SensorType::Locator,
+            Self::Velocity { .. } => SensorType::Velocity,
+            Self::Speed(_) => SensorType::Speed,
+            Self::AmbientLight(_) => SensorType::AmbientLight,
+        }
+    }
+}
+
+/// Split a streaming notification into its token and sample data
+fn stream_payload(packet: &Packet) -> Result<(u8, &[u8])> {
+    if !packet.is_notification()
+        || packet.device_id != device::SENSOR
+        || packet.command_id != sensor_command::STREAMING_SERVICE_DATA_NOTIFY
+    {
+        return Err(RvrError::InvalidResponse(
+            "Not a sensor streaming notification".to_string(),
+        ));
+    }
+
+    packet
+        .payload
+        .split_first()
+        .map(|(&token, data)| (token, data))
+        .ok_or_else(|| RvrError::InvalidResponse("Sensor notification has no token".to_string()))
+}
+
+/// Decode one sensor's samples
+///
+/// `data` must be exactly `sensor.data_len()` bytes.
+fn decode_reading(sensor: SensorType, data: &[u8]) -> SensorReading {
+    let v = decode_samples(sensor, data);
+    match sensor {
+        SensorType::Quaternion => SensorReading::Quaternion {
+            w: v[0],
+            x: v[1],
+            y: v[2],
+            z: v[3],
+        },
+        SensorType::Imu => SensorReading::Imu {
+            pitch: v[0],
+            roll: v[1],
+            yaw: v[2],
+        },
+        SensorType::Accelerometer => SensorReading::Accelerometer {
+            x: v[0],
+            y: v[1],
+            z: v[2],
+        },
+        SensorType::ColorDetection => SensorReading::ColorDetection {
+            r: data[0],
+            g: data[1],
+            b: data[2],
+            index: data[3],
+            confidence: data[4],
+        },
+        SensorType::Locator => SensorReading::Locator { x: v[0], y: v[1] },
+        SensorType::Velocity => SensorReading::Velocity { x: v[0], y: v[1] },
+        SensorType::Speed => SensorReading::Speed(v[0]),
+        SensorType::AmbientLight => SensorReading::AmbientLight(v[0]),
+    }
+}
+
+/// Scale each raw sample into its range
+///
+/// `data` must be exactly `sensor.data_len()` bytes.
+fn decode_samples(sensor: SensorType, data: &[u8]) -> Vec<f32> {
+    let size = sensor.sample_size();
+    data.chunks_exact(size.bytes())
+        .zip(sensor.ranges())
+        .map(|(raw, &(min, max))| {
+            let raw = raw.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+            // f64 keeps full u32 precision before narrowing
+            (min as f64 + (raw as f64 / size.max() as f64) * (max - min) as f64) as f32
+        })
+        .collect()
+}
+
+/// Encode values as one sensor's streamed samples, the inverse of `decode_samples`
+///
+/// Values outside a sample's range are clamped to it. Used by `VirtualRvr`
+/// to produce realistic streaming notifications.
+#[cfg(any(test, feature = "test-support"))]
+pub(crate) fn encode_samples(sensor: SensorType, values: &[f32]) -> Vec<u8> {
+    let size = sensor.sample_size();
+    values
+        .iter()
+        .zip(sensor.ranges())
+        .flat_map(|(&value, &(min, max))| {
+            let fraction = ((value - min) as f64 / (max - min) as f64).clamp(0.0, 1.0);
+            let raw = (fraction * size.max() as f64).round() as u32;
+            raw.to_be_bytes()[4 - size.bytes()..].to_vec()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(payload: Vec<u8>) -> Packet {
+        let mut packet = Packet::new_command(
+            device::SENSOR,
+            sensor_command::STREAMING_SERVICE_DATA_NOTIFY,
+            0,
+            payload,
+        );
+        packet.flags.requests_response = false;
+        packet
+    }
+
+    #[test]
+    fn test_tokens_are_unique() {
+        for sensor in SensorType::ALL {
+            assert_eq!(SensorType::from_token(sensor.token()), Some(sensor));
+        }
+        assert_eq!(SensorType::from_token(0), None);
+    }
+
+    #[test]
+    fn test_stream_config_payload() {
+        assert_eq!(
+            SensorConfig::single(SensorType::Locator).config_payload(),
+            vec![0x07, 0x00, 0x06, 0x02]
+        );
+        assert_eq!(
+            SensorConfig::single(SensorType::ColorDetection).config_payload(),
+            vec![0x04, 0x00, 0x03, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_decode_velocity() {
+        // Minimum, maximum
+        let mut payload = vec![SensorType::Velocity.token()];
+        payload.extend_from_slice(&0u32.to_be_bytes());
+        payload.extend_from_slice(&u32::MAX.to_be_bytes());
+
+        let reading = SensorReading::from_packet(&notification(payload)).unwrap();
+        assert_eq!(reading, SensorReading::Velocity { x: -5.0, y: 5.0 });
+        assert_eq!(reading.sensor(), SensorType::Velocity);
+    }
+
+    #[test]
+    fn test_decode_quaternion_midpoint() {
+        let mut payload = vec![SensorType::Quaternion.token()];
+        for _ in 0..4 {
+            payload.extend_from_slice(&(u32::MAX / 2).to_be_bytes());
+        }
+
+        match SensorReading::from_packet(&notification(payload)).unwrap() {
+            SensorReading::Quaternion { w, x, y, z } => {
+                for value in [w, x, y, z] {
+                    assert!(value.abs() < 1e-6, "{}", value);
+                }
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_encode_samples_round_trip() {
+        let mut payload = vec![SensorType::Locator.token()];
+        payload.extend(encode_samples(SensorType::Locator, &[0.25, -1.5]));
+
+        match SensorReading::from_packet(&notification(payload)).unwrap() {
+            SensorReading::Locator { x, y } => {
+                assert!((x - 0.25).abs() < 1e-4, "{}", x);
+                assert!((y + 1.5).abs() < 1e-4, "{}", y);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Out of range values clamp to the range ends
+        assert_eq!(
+            encode_samples(SensorType::Speed, &[-1.0]),
+            0u32.to_be_bytes().to_vec()
+        );
+        assert_eq!(
+            encode_samples(SensorType::ColorDetection, &[300.0]),
+            vec![255]
+        );
+    }
+
+    #[test]
+    fn test_decode_color_detection() {
+        let payload = vec![SensorType::ColorDetection.token(), 200, 100, 50, 3, 255];
+        assert_eq!(
+            SensorReading::from_packet(&notification(payload)).unwrap(),
+            SensorReading::ColorDetection {
+                r: 200,
+                g: 100,
+                b: 50,
+                index: 3,
+                confidence: 255
+            }
+        );
+    }
+
+    #[test]
+    fn test_sensor_config_decodes_combined_payload() {
+        use crate::api::types::{AccelReading, ImuReading};
+
+        let config = SensorConfig::new(
+            0x20,
+            &[
+                SensorType::Imu,
+                SensorType::Accelerometer,
+                SensorType::ColorDetection,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            config.config_payload(),
+            vec![0x20, 0x00, 0x01, 0x02, 0x00, 0x02, 0x02, 0x00, 0x03, 0x00]
+        );
+        assert_eq!(config.data_len(), 12 + 12 + 5);
+
+        // IMU: level pitch, full right roll, yaw at -180
+        let mut payload = vec![0x20];
+        for raw in [u32::MAX / 2, u32::MAX, 0] {
+            payload.extend_from_slice(&raw.to_be_bytes());
+        }
+        // Accelerometer: -16 g, +16 g, ~0 g
+        for raw in [0, u32::MAX, u32::MAX / 2] {
+            payload.extend_from_slice(&raw.to_be_bytes());
+        }
+        payload.extend_from_slice(&[10, 20, 30, 1, 200]);
+        let packet = notification(payload);
+
+        let readings = config.decode(&packet).unwrap();
+        assert_eq!(
+            readings.iter().map(|r| r.sensor()).collect::<Vec<_>>(),
+            config.sensors()
+        );
+
+        let imu = ImuReading::try_from(readings[0]).unwrap();
+        assert!(imu.pitch.abs() < 1e-4, "{}", imu.pitch);
+        assert_eq!((imu.roll, imu.yaw), (90.0, -180.0));
+
+        let accel = AccelReading::try_from(readings[1]).unwrap();
+        assert_eq!((accel.x, accel.y), (-16.0, 16.0));
+        assert!(accel.z.abs() < 1e-4, "{}", accel.z);
+        assert!(ImuReading::try_from(readings[1]).is_err());
+
+        assert_eq!(
+            readings[2],
+            SensorReading::ColorDetection {
+                r: 10,
+                g: 20,
+                b: 30,
+                index: 1,
+                confidence: 200
+            }
+        );
+
+        // A different stream, or one sample short, is rejected
+        let mut other = packet.clone();
+        other.payload[0] = 0x21;
+        assert!(config.decode(&other).is_err());
+        let mut short = packet;
+        short.payload.pop();
+        assert!(config.decode(&short).is_err());
+    }
+
+    #[test]
+    fn test_sensor_config_rejects_bad_lists() {
+        assert!(SensorConfig::new(0x20, &[]).is_err());
+        assert!(SensorConfig::new(0x20, &[SensorType::Imu, SensorType::Imu]).is_err());
+        assert_eq!(
+            SensorConfig::single(SensorType::Speed).token(),
+            SensorType::Speed.token()
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_packets() {
+        // Wrong length
+        let payload = vec![SensorType::Speed.token(), 0, 0];
+        assert!(SensorReading::from_packet(&notification(payload)).is_err());
+
+        // Unknown token
+        assert!(SensorReading::from_packet(&notification(vec![0xEE, 0, 0, 0, 0])).is_err());
+
+        // Not a streaming notification
+        let packet = Packet::new_command(device::POWER, 0x1C, 0, vec![1, 0, 0, 0, 0]);
+        assert!(SensorReading::from_packet(&packet).is_err());
+    }
+}
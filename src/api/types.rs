@@ -1,7 +1,15 @@
 //! High-level types for the Sphero RVR API
 
+use crate::api::constants::{device, drive_flags, sensor_command};
+use crate::api::led::LedPosition;
+use crate::api::sensors::SensorReading;
+use crate::error::{Result, RvrError};
+use crate::protocol::packet::Packet;
+use std::time::{Duration, Instant};
+
 /// RGB Color representation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     /// Red component (0-255)
     pub r: u8,
@@ -31,6 +39,85 @@ impl Color {
         [self.r, self.g, self.b]
     }
 
+    /// Linearly interpolate towards `other`
+    ///
+    /// `t` is clamped to 0.0..=1.0; 0.0 returns `self` and 1.0 returns `other`.
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Self::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+
+    /// Apply a gamma curve to each channel: `255 * (c / 255) ^ gamma`
+    ///
+    /// LED brightness is not perceived linearly, so fades look washed out
+    /// at the dim end. Correcting with a gamma around 2.2 makes a linear
+    /// `lerp` look linear to the eye. Black and full-scale channels are
+    /// unchanged; a non-positive or NaN `gamma` returns the color as-is.
+    pub fn gamma_correct(self, gamma: f32) -> Self {
+        if gamma.is_nan() || gamma <= 0.0 {
+            return self;
+        }
+        let correct = |c: u8| (255.0 * (c as f32 / 255.0).powf(gamma)).round() as u8;
+        Self::new(correct(self.r), correct(self.g), correct(self.b))
+    }
+
+    /// Create a color from hue, saturation and value
+    ///
+    /// `h` is in degrees and wraps around (so 360.0 is red again, which
+    /// suits rainbow loops); `s` and `v` are clamped to 0.0..=1.0. NaN
+    /// inputs are treated as 0.0.
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let unit = |x: f32| if x.is_nan() { 0.0 } else { x.clamp(0.0, 1.0) };
+        let h = if h.is_finite() {
+            h.rem_euclid(360.0)
+        } else {
+            0.0
+        };
+        let (s, v) = (unit(s), unit(v));
+
+        let chroma = v * s;
+        let sector = h / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = v - chroma;
+        let channel = |c: f32| ((c + m) * 255.0).round() as u8;
+        Self::new(channel(r), channel(g), channel(b))
+    }
+
+    /// Convert to hue (degrees, 0.0..360.0), saturation and value (0.0..=1.0)
+    ///
+    /// Grays have no hue and report 0.0.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let [r, g, b] = self.to_bytes().map(|c| c as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+        (h, s, max)
+    }
+
     // Common colors
     pub const BLACK: Self = Self::new(0, 0, 0);
     pub const WHITE: Self = Self::new(255, 255, 255);
@@ -58,20 +145,461 @@ impl From<[u8; 3]> for Color {
 
 /// Battery state information
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BatteryState {
     /// Battery percentage (0-100)
     pub percentage: u8,
 }
 
+/// Battery voltage state as classified by the firmware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryVoltageState {
+    /// State could not be determined
+    Unknown,
+    /// Voltage is in the normal range
+    Ok,
+    /// Voltage is low; charge soon
+    Low,
+    /// Voltage is critically low; the robot will shut down
+    Critical,
+}
+
+impl BatteryVoltageState {
+    /// Decode voltage state from its wire value (unknown values map to `Unknown`)
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => Self::Ok,
+            2 => Self::Low,
+            3 => Self::Critical,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Aggregated power status
+///
+/// Fields other than `battery` are `None` when the firmware does not
+/// implement the corresponding query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerStatus {
+    /// Battery charge level
+    pub battery: BatteryState,
+    /// Battery voltage classification, if supported
+    pub voltage_state: Option<BatteryVoltageState>,
+    /// Latched protection state, if supported
+    pub protection: Option<ProtectionState>,
+}
+
+/// Latched protection state reported by the power system
+///
+/// When any of these flags is set the robot refuses to drive until the
+/// latch is cleared with `SpheroRvr::clear_protection_latch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProtectionState {
+    /// Motors or battery exceeded their temperature limit
+    pub overtemp: bool,
+    /// Battery voltage dropped below the safe threshold
+    pub undervoltage: bool,
+    /// Motor current exceeded the safe limit
+    pub overcurrent: bool,
+}
+
+impl ProtectionState {
+    /// Decode protection state from the flags byte
+    ///
+    /// Bit 0 = overtemp, bit 1 = undervoltage, bit 2 = overcurrent.
+    /// Remaining bits are reserved and ignored.
+    pub fn from_byte(byte: u8) -> Self {
+        Self {
+            overtemp: byte & 0b0000_0001 != 0,     // bit 0
+            undervoltage: byte & 0b0000_0010 != 0, // bit 1
+            overcurrent: byte & 0b0000_0100 != 0,  // bit 2
+        }
+    }
+
+    /// Returns true if any protection is latched
+    pub fn is_latched(&self) -> bool {
+        self.overtemp || self.undervoltage || self.overcurrent
+    }
+}
+
+/// Visible state of the robot, captured by `SpheroRvr::snapshot_state`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RobotState {
+    /// Whether the robot was awake, if known
+    pub awake: Option<bool>,
+    /// Color of each settable LED
+    pub leds: Vec<(LedPosition, Color)>,
+}
+
+/// Direction of one motor in a raw motor command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MotorMode {
+    /// Motor off (coasting)
+    Off = 0,
+    /// Drive forward
+    Forward = 1,
+    /// Drive in reverse
+    Reverse = 2,
+}
+
+impl MotorMode {
+    /// Convert to the byte used in the raw motors payload
+    pub const fn to_byte(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Typed view of the drive-with-heading flags byte
+///
+/// | Bit | Flag              |
+/// |-----|-------------------|
+/// | 0   | reverse           |
+/// | 1   | boost             |
+/// | 2   | fast_turn         |
+/// | 3   | left_direction    |
+/// | 4   | right_direction   |
+/// | 5   | drift             |
+///
+/// Bits 6-7 are reserved and carried through as-is in `reserved`. See the
+/// `drive_flags` constants for the raw masks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DriveFlags {
+    /// Drive backwards along the heading
+    pub reverse: bool,
+    /// Allow speeds above the normal limit
+    pub boost: bool,
+    /// Turn faster when changing heading
+    pub fast_turn: bool,
+    /// Reverse the left motor direction
+    pub left_direction: bool,
+    /// Reverse the right motor direction
+    pub right_direction: bool,
+    /// Drift through turns instead of holding heading
+    pub drift: bool,
+    /// Reserved bits 6-7, shifted down (masked to 2 bits on encode)
+    pub reserved: u8,
+}
+
+impl DriveFlags {
+    /// Shift of the reserved field (bits 6-7)
+    const RESERVED_SHIFT: u8 = 6;
+    /// Mask of the reserved field after shifting down (2 bits)
+    const RESERVED_MASK: u8 = 0b11;
+
+    /// Decode drive flags from the flags byte
+    pub fn from_byte(byte: u8) -> Self {
+        Self {
+            reverse: byte & drive_flags::REVERSE != 0,
+            boost: byte & drive_flags::BOOST != 0,
+            fast_turn: byte & drive_flags::FAST_TURN != 0,
+            left_direction: byte & drive_flags::LEFT_DIRECTION != 0,
+            right_direction: byte & drive_flags::RIGHT_DIRECTION != 0,
+            drift: byte & drive_flags::ENABLE_DRIFT != 0,
+            reserved: (byte >> Self::RESERVED_SHIFT) & Self::RESERVED_MASK,
+        }
+    }
+
+    /// Encode drive flags to the flags byte
+    pub fn to_byte(self) -> u8 {
+        [
+            (self.reverse, drive_flags::REVERSE),
+            (self.boost, drive_flags::BOOST),
+            (self.fast_turn, drive_flags::FAST_TURN),
+            (self.left_direction, drive_flags::LEFT_DIRECTION),
+            (self.right_direction, drive_flags::RIGHT_DIRECTION),
+            (self.drift, drive_flags::ENABLE_DRIFT),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(
+            (self.reserved & Self::RESERVED_MASK) << Self::RESERVED_SHIFT,
+            |byte, (_, mask)| byte | mask,
+        )
+    }
+}
+
+/// Robot model, as reported by the hardware (board) revision
+///
+/// The RVR+ shipped on a revised main board, so the two models are told
+/// apart by the board revision returned from
+/// `system_info_command::GET_HARDWARE_VERSION`: revisions below
+/// `RvrModel::RVR_PLUS_MIN_BOARD_REVISION` are the original RVR, later ones
+/// are the RVR+. Revision 0 is never assigned and decodes as `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RvrModel {
+    /// Original Sphero RVR
+    Rvr,
+    /// Sphero RVR+
+    RvrPlus,
+    /// Unrecognized board revision
+    Unknown(u8),
+}
+
+impl RvrModel {
+    /// First board revision used by the RVR+
+    pub const RVR_PLUS_MIN_BOARD_REVISION: u8 = 5;
+
+    /// Decode the model from the board revision byte
+    pub fn from_board_revision(revision: u8) -> Self {
+        match revision {
+            0 => Self::Unknown(revision),
+            r if r < Self::RVR_PLUS_MIN_BOARD_REVISION => Self::Rvr,
+            _ => Self::RvrPlus,
+        }
+    }
+}
+
+/// Mapping between the robot's millisecond clock and the host clock
+///
+/// Device timestamps (e.g. in streamed sensor frames) count milliseconds
+/// since the robot's core booted. Pairing one device timestamp with the
+/// host `Instant` at which it was sampled lets any later device timestamp
+/// be converted to a host `Instant`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceClock {
+    /// Device timestamp (ms since boot) at the reference point
+    pub device_ms: u64,
+    /// Host time at the reference point
+    pub host: Instant,
+}
+
+impl DeviceClock {
+    /// Create a clock mapping from a device timestamp and the host time it was sampled
+    pub fn new(device_ms: u64, host: Instant) -> Self {
+        Self { device_ms, host }
+    }
+
+    /// Convert a device timestamp to the corresponding host `Instant`
+    ///
+    /// Timestamps before the reference point are mapped backwards in time.
+    /// Returns `None` if the result is not representable as an `Instant`;
+    /// on some platforms an `Instant` cannot precede the host's boot time.
+    pub fn to_host_instant(&self, device_ms: u64) -> Option<Instant> {
+        if device_ms >= self.device_ms {
+            self.host
+                .checked_add(Duration::from_millis(device_ms - self.device_ms))
+        } else {
+            self.host
+                .checked_sub(Duration::from_millis(self.device_ms - device_ms))
+        }
+    }
+}
+
+/// Robot position from the locator, in meters
+///
+/// The origin is where the locator was last reset (see
+/// `SpheroRvr::reset_locator`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Locator {
+    /// Distance along the X axis (meters)
+    pub x: f32,
+    /// Distance along the Y axis (meters)
+    pub y: f32,
+}
+
+impl Locator {
+    /// Decode a locator sensor streaming notification
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidResponse` if the packet is not a locator
+    /// stream sample.
+    pub fn from_packet(packet: &Packet) -> Result<Self> {
+        match SensorReading::from_packet(packet)? {
+            SensorReading::Locator { x, y } => Ok(Self { x, y }),
+            other => Err(RvrError::InvalidResponse(format!(
+                "Expected a locator sample, got {:?}",
+                other.sensor()
+            ))),
+        }
+    }
+
+    /// Straight-line distance to another position (meters)
+    pub fn distance_to(self, other: Self) -> f32 {
+        (other.x - self.x).hypot(other.y - self.y)
+    }
+}
+
+/// A color seen by the underside color sensor
+///
+/// Decoded from `sensor_command::COLOR_DETECTION_NOTIFY`, which arrives
+/// asynchronously once `SpheroRvr::enable_color_detection` is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectedColor {
+    /// Red component (0-255)
+    pub r: u8,
+    /// Green component (0-255)
+    pub g: u8,
+    /// Blue component (0-255)
+    pub b: u8,
+    /// How sure the sensor is of the color (0-255)
+    ///
+    /// Low values mean the reading is ambiguous, e.g. on the edge of a line.
+    pub confidence: u8,
+    /// Which of the sensor's known colors this was classified as
+    pub classification: u8,
+}
+
+impl DetectedColor {
+    /// Decode a color detection notification
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidResponse` if the packet is not a color
+    /// detection notification or its payload is shorter than 5 bytes.
+    pub fn from_packet(packet: &Packet) -> Result<Self> {
+        if !packet.is_notification()
+            || packet.device_id != device::SENSOR
+            || packet.command_id != sensor_command::COLOR_DETECTION_NOTIFY
+        {
+            return Err(RvrError::InvalidResponse(
+                "Not a color detection notification".to_string(),
+            ));
+        }
+        match packet.payload[..] {
+            [r, g, b, confidence, classification, ..] => Ok(Self {
+                r,
+                g,
+                b,
+                confidence,
+                classification,
+            }),
+            _ => Err(RvrError::InvalidResponse(format!(
+                "Color detection data too short: {} bytes (expected 5)",
+                packet.payload.len()
+            ))),
+        }
+    }
+
+    /// The detected color without the confidence or classification
+    pub fn color(&self) -> Color {
+        Color::new(self.r, self.g, self.b)
+    }
+}
+
+/// An infrared message received from another robot
+///
+/// Decoded from `sensor_command::IR_MESSAGE_RECEIVED_NOTIFY`, which arrives
+/// asynchronously once `SpheroRvr::enable_ir_messages` is on. The
+/// firmware reports only the code; which receiver heard it, and how
+/// strongly, is not included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrMessage {
+    /// Message code (0-7)
+    pub code: u8,
+}
+
+impl IrMessage {
+    /// Decode an IR message notification
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidResponse` if the packet is not an IR
+    /// message notification or has no payload.
+    pub fn from_packet(packet: &Packet) -> Result<Self> {
+        if !packet.is_notification()
+            || packet.device_id != device::SENSOR
+            || packet.command_id != sensor_command::IR_MESSAGE_RECEIVED_NOTIFY
+        {
+            return Err(RvrError::InvalidResponse(
+                "Not an IR message notification".to_string(),
+            ));
+        }
+        match packet.payload.first() {
+            Some(&code) => Ok(Self { code }),
+            None => Err(RvrError::InvalidResponse(
+                "IR message notification has no code".to_string(),
+            )),
+        }
+    }
+}
+
+/// Orientation angles from the IMU, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImuReading {
+    /// Rotation about the X axis (-180 to 180)
+    pub pitch: f32,
+    /// Rotation about the Y axis (-90 to 90)
+    pub roll: f32,
+    /// Rotation about the Z axis (-180 to 180)
+    pub yaw: f32,
+}
+
+impl ImuReading {
+    /// Decode an IMU sensor streaming notification
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidResponse` if the packet is not an IMU
+    /// stream sample.
+    pub fn from_packet(packet: &Packet) -> Result<Self> {
+        SensorReading::from_packet(packet)?.try_into()
+    }
+}
+
+impl TryFrom<SensorReading> for ImuReading {
+    type Error = RvrError;
+
+    fn try_from(reading: SensorReading) -> Result<Self> {
+        match reading {
+            SensorReading::Imu { pitch, roll, yaw } => Ok(Self { pitch, roll, yaw }),
+            other => Err(RvrError::InvalidResponse(format!(
+                "Expected an IMU sample, got {:?}",
+                other.sensor()
+            ))),
+        }
+    }
+}
+
+/// Acceleration from the accelerometer, in g
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccelReading {
+    /// Acceleration along the X axis
+    pub x: f32,
+    /// Acceleration along the Y axis
+    pub y: f32,
+    /// Acceleration along the Z axis
+    pub z: f32,
+}
+
+impl AccelReading {
+    /// Decode an accelerometer sensor streaming notification
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidResponse` if the packet is not an
+    /// accelerometer stream sample.
+    pub fn from_packet(packet: &Packet) -> Result<Self> {
+        SensorReading::from_packet(packet)?.try_into()
+    }
+}
+
+impl TryFrom<SensorReading> for AccelReading {
+    type Error = RvrError;
+
+    fn try_from(reading: SensorReading) -> Result<Self> {
+        match reading {
+            SensorReading::Accelerometer { x, y, z } => Ok(Self { x, y, z }),
+            other => Err(RvrError::InvalidResponse(format!(
+                "Expected an accelerometer sample, got {:?}",
+                other.sensor()
+            ))),
+        }
+    }
+}
+
 /// Firmware version information
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FirmwareVersion {
     /// Major version
-    pub major: u8,
+    pub major: u16,
     /// Minor version
-    pub minor: u8,
-    /// Patch version
-    pub patch: u8,
+    pub minor: u16,
+    /// Patch version (the firmware's revision)
+    pub patch: u16,
 }
 
 impl std::fmt::Display for FirmwareVersion {
@@ -84,6 +612,35 @@ impl std::fmt::Display for FirmwareVersion {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json_round_trip() {
+        let json = serde_json::to_string(&Color::new(255, 128, 0)).unwrap();
+        assert_eq!(json, r#"{"r":255,"g":128,"b":0}"#);
+        assert_eq!(
+            serde_json::from_str::<Color>(&json).unwrap(),
+            Color::new(255, 128, 0)
+        );
+
+        let version = FirmwareVersion {
+            major: 7,
+            minor: 2,
+            patch: 31,
+        };
+        let json = serde_json::to_string(&version).unwrap();
+        assert_eq!(
+            serde_json::from_str::<FirmwareVersion>(&json).unwrap(),
+            version
+        );
+
+        let battery = BatteryState { percentage: 80 };
+        let json = serde_json::to_string(&battery).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BatteryState>(&json).unwrap(),
+            battery
+        );
+    }
+
     #[test]
     fn test_color_new() {
         let color = Color::new(255, 128, 64);
@@ -131,6 +688,123 @@ mod tests {
         assert_eq!(color, Color::new(50, 100, 150));
     }
 
+    #[test]
+    fn test_color_lerp() {
+        let start = Color::new(0, 100, 255);
+        let end = Color::new(255, 0, 255);
+        assert_eq!(start.lerp(end, 0.0), start);
+        assert_eq!(start.lerp(end, 1.0), end);
+        assert_eq!(start.lerp(end, 0.5), Color::new(128, 50, 255));
+        // Out-of-range factors are clamped
+        assert_eq!(start.lerp(end, -1.0), start);
+        assert_eq!(start.lerp(end, 2.0), end);
+    }
+
+    #[test]
+    fn test_color_gamma_correct() {
+        let color = Color::new(0, 128, 255);
+        assert_eq!(color.gamma_correct(1.0), color);
+        // Endpoints are fixed; the midpoint is dimmed
+        assert_eq!(color.gamma_correct(2.2), Color::new(0, 56, 255));
+        assert_eq!(color.gamma_correct(0.0), color);
+        assert_eq!(color.gamma_correct(f32::NAN), color);
+    }
+
+    #[test]
+    fn test_color_from_hsv_primary_hues() {
+        let hues = [
+            (0.0, Color::RED),
+            (60.0, Color::YELLOW),
+            (120.0, Color::GREEN),
+            (180.0, Color::CYAN),
+            (240.0, Color::BLUE),
+            (300.0, Color::MAGENTA),
+        ];
+        for (hue, color) in hues {
+            assert_eq!(Color::from_hsv(hue, 1.0, 1.0), color, "hue {}", hue);
+
+            let (h, s, v) = color.to_hsv();
+            assert!((h - hue).abs() < 1e-3, "{:?} hue {}", color, h);
+            assert!((s - 1.0).abs() < 1e-6 && (v - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_color_hsv_grayscale_round_trip() {
+        for level in [0u8, 64, 128, 255] {
+            let gray = Color::new(level, level, level);
+            let (h, s, v) = gray.to_hsv();
+            assert_eq!((h, s), (0.0, 0.0));
+            assert!((v - level as f32 / 255.0).abs() < 1e-6);
+            assert_eq!(Color::from_hsv(h, s, v), gray);
+        }
+    }
+
+    #[test]
+    fn test_color_from_hsv_clamps_inputs() {
+        assert_eq!(Color::from_hsv(360.0, 1.0, 1.0), Color::RED);
+        assert_eq!(Color::from_hsv(-120.0, 1.0, 1.0), Color::BLUE);
+        assert_eq!(Color::from_hsv(0.0, 2.0, 5.0), Color::RED);
+        assert_eq!(Color::from_hsv(0.0, -1.0, -1.0), Color::BLACK);
+        assert_eq!(Color::from_hsv(f32::NAN, f32::NAN, 1.0), Color::WHITE);
+    }
+
+    #[test]
+    fn test_battery_voltage_state_from_byte() {
+        assert_eq!(
+            BatteryVoltageState::from_byte(0),
+            BatteryVoltageState::Unknown
+        );
+        assert_eq!(BatteryVoltageState::from_byte(1), BatteryVoltageState::Ok);
+        assert_eq!(BatteryVoltageState::from_byte(2), BatteryVoltageState::Low);
+        assert_eq!(
+            BatteryVoltageState::from_byte(3),
+            BatteryVoltageState::Critical
+        );
+        assert_eq!(
+            BatteryVoltageState::from_byte(0xFF),
+            BatteryVoltageState::Unknown
+        );
+    }
+
+    #[test]
+    fn test_protection_state_from_byte() {
+        let clear = ProtectionState::from_byte(0x00);
+        assert_eq!(clear, ProtectionState::default());
+        assert!(!clear.is_latched());
+
+        let overtemp = ProtectionState::from_byte(0b0000_0001);
+        assert!(overtemp.overtemp);
+        assert!(!overtemp.undervoltage);
+        assert!(!overtemp.overcurrent);
+
+        let all = ProtectionState::from_byte(0b1111_0111);
+        assert!(all.overtemp);
+        assert!(all.undervoltage);
+        assert!(all.overcurrent);
+        assert!(all.is_latched());
+
+        let undervoltage = ProtectionState::from_byte(0b0000_0010);
+        assert!(undervoltage.undervoltage);
+        assert!(!undervoltage.overcurrent);
+    }
+
+    #[test]
+    fn test_device_clock_offset() {
+        let host = Instant::now() + Duration::from_secs(10);
+        let clock = DeviceClock::new(5_000, host);
+
+        assert_eq!(clock.to_host_instant(5_000), Some(host));
+        assert_eq!(
+            clock.to_host_instant(5_250),
+            Some(host + Duration::from_millis(250))
+        );
+        assert_eq!(
+            clock.to_host_instant(4_000),
+            Some(host - Duration::from_secs(1))
+        );
+    }
+
     #[test]
     fn test_firmware_version_display() {
         let version = FirmwareVersion {
@@ -140,4 +814,181 @@ mod tests {
         };
         assert_eq!(version.to_string(), "1.2.3");
     }
+
+    #[test]
+    fn test_rvr_model_from_board_revision() {
+        assert_eq!(RvrModel::from_board_revision(0), RvrModel::Unknown(0));
+        assert_eq!(RvrModel::from_board_revision(1), RvrModel::Rvr);
+        assert_eq!(RvrModel::from_board_revision(4), RvrModel::Rvr);
+        assert_eq!(RvrModel::from_board_revision(5), RvrModel::RvrPlus);
+        assert_eq!(RvrModel::from_board_revision(7), RvrModel::RvrPlus);
+    }
+
+    #[test]
+    fn test_detected_color_from_packet() {
+        let mut packet = Packet::new_command(
+            device::SENSOR,
+            sensor_command::COLOR_DETECTION_NOTIFY,
+            0,
+            vec![200, 100, 50, 30, 0x05],
+        );
+        packet.flags.requests_response = false;
+
+        let detected = DetectedColor::from_packet(&packet).unwrap();
+        assert_eq!(detected.color(), Color::new(200, 100, 50));
+        assert_eq!(detected.confidence, 30);
+        assert_eq!(detected.classification, 0x05);
+
+        packet.payload.truncate(4);
+        assert!(DetectedColor::from_packet(&packet).is_err());
+
+        // Responses are not notifications
+        packet.flags.is_response = true;
+        packet.payload = vec![200, 100, 50, 30, 0x05];
+        assert!(DetectedColor::from_packet(&packet).is_err());
+    }
+
+    #[test]
+    fn test_ir_message_from_packet() {
+        let mut packet = Packet::new_command(
+            device::SENSOR,
+            sensor_command::IR_MESSAGE_RECEIVED_NOTIFY,
+            0,
+            vec![5],
+        );
+        packet.flags.requests_response = false;
+        assert_eq!(IrMessage::from_packet(&packet).unwrap().code, 5);
+
+        packet.payload.clear();
+        assert!(IrMessage::from_packet(&packet).is_err());
+    }
+
+    #[test]
+    fn test_imu_and_accel_from_readings() {
+        let imu = ImuReading::try_from(SensorReading::Imu {
+            pitch: 1.5,
+            roll: -2.0,
+            yaw: 90.0,
+        })
+        .unwrap();
+        assert_eq!((imu.pitch, imu.roll, imu.yaw), (1.5, -2.0, 90.0));
+        let accel = AccelReading::try_from(SensorReading::Accelerometer {
+            x: 1.5,
+            y: -2.0,
+            z: 0.5,
+        })
+        .unwrap();
+        assert_eq!((accel.x, accel.y, accel.z), (1.5, -2.0, 0.5));
+
+        assert!(ImuReading::try_from(SensorReading::Speed(1.0)).is_err());
+        assert!(AccelReading::try_from(SensorReading::AmbientLight(1.0)).is_err());
+    }
+
+    #[test]
+    fn test_locator_from_stream_notification() {
+        use crate::api::constants::{device, sensor_command};
+        use crate::api::sensors::SensorType;
+
+        // Stream samples span -16000..16000 m; the midpoint is the origin
+        let mut payload = vec![SensorType::Locator.token()];
+        payload.extend_from_slice(&(u32::MAX / 2).to_be_bytes());
+        payload.extend_from_slice(&u32::MAX.to_be_bytes());
+        let mut packet = Packet::new_command(
+            device::SENSOR,
+            sensor_command::STREAMING_SERVICE_DATA_NOTIFY,
+            0,
+            payload,
+        );
+        packet.flags.requests_response = false;
+
+        let locator = Locator::from_packet(&packet).unwrap();
+        assert!(locator.x.abs() < 0.01, "{}", locator.x);
+        assert!((locator.y - 16000.0).abs() < 0.01);
+        assert!((locator.distance_to(Locator { x: 3.0, y: 16004.0 }) - 5.0).abs() < 0.01);
+
+        // A different sensor's sample is rejected
+        packet.payload[0] = SensorType::Speed.token();
+        packet.payload.truncate(5);
+        assert!(Locator::from_packet(&packet).is_err());
+    }
+
+    #[test]
+    fn test_motor_mode_to_byte() {
+        assert_eq!(MotorMode::Off.to_byte(), 0);
+        assert_eq!(MotorMode::Forward.to_byte(), 1);
+        assert_eq!(MotorMode::Reverse.to_byte(), 2);
+    }
+
+    #[test]
+    fn test_drive_flags_bits() {
+        let cases = [
+            (
+                0x01,
+                DriveFlags {
+                    reverse: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                0x02,
+                DriveFlags {
+                    boost: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                0x04,
+                DriveFlags {
+                    fast_turn: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                0x08,
+                DriveFlags {
+                    left_direction: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                0x10,
+                DriveFlags {
+                    right_direction: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                0x20,
+                DriveFlags {
+                    drift: true,
+                    ..Default::default()
+                },
+            ),
+        ];
+        for (byte, flags) in cases {
+            assert_eq!(DriveFlags::from_byte(byte), flags, "{:#04x}", byte);
+            assert_eq!(flags.to_byte(), byte, "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn test_drive_flags_round_trip_keeps_reserved() {
+        for byte in 0..=0xFF {
+            assert_eq!(DriveFlags::from_byte(byte).to_byte(), byte);
+        }
+        assert_eq!(
+            DriveFlags::from_byte(0xC0),
+            DriveFlags {
+                reserved: 0b11,
+                ..Default::default()
+            }
+        );
+
+        // Out-of-range reserved values cannot bleed into the flags
+        let flags = DriveFlags {
+            reserved: 0xFF,
+            ..Default::default()
+        };
+        assert_eq!(flags.to_byte(), 0xC0);
+    }
 }
@@ -0,0 +1,70 @@
+//! Background threads that stop promptly when asked
+//!
+//! Used by `SpheroRvr::start_keep_awake` and `SpheroRvr::play_animation`.
+
+use crate::error::{Result, RvrError};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Lets a worker's body wait between steps while watching for `Worker::stop`
+pub(crate) struct StopSignal(mpsc::Receiver<()>);
+
+impl StopSignal {
+    /// Wait for `interval`, returning false early if the worker was stopped
+    pub(crate) fn wait(&self, interval: Duration) -> bool {
+        // Waiting on the channel (rather than sleeping) lets stop return promptly
+        self.0.recv_timeout(interval) == Err(mpsc::RecvTimeoutError::Timeout)
+    }
+}
+
+/// A named background thread
+pub(crate) struct Worker {
+    name: &'static str,
+    /// Dropping this tells the thread to exit
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl Worker {
+    /// Run `body` on a new thread
+    ///
+    /// `body` should return once `StopSignal::wait` returns false.
+    pub(crate) fn spawn(
+        name: &'static str,
+        body: impl FnOnce(StopSignal) + Send + 'static,
+    ) -> Self {
+        let (stop, stop_rx) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || body(StopSignal(stop_rx)));
+        Self { name, stop, thread }
+    }
+
+    /// Stop the thread and wait for it to exit
+    pub(crate) fn stop(self) -> Result<()> {
+        drop(self.stop);
+        self.thread
+            .join()
+            .map_err(|_| RvrError::Protocol(format!("Failed to join {} thread", self.name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_stop_interrupts_wait() {
+        let worker = Worker::spawn("test", |stop| while stop.wait(Duration::from_secs(60)) {});
+
+        let started = Instant::now();
+        worker.stop().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_panicking_body_is_reported() {
+        let worker = Worker::spawn("test", |_| panic!("worker failed"));
+        assert!(matches!(worker.stop(), Err(RvrError::Protocol(_))));
+    }
+}
@@ -1,11 +1,15 @@
+use crate::protocol::error_code;
+use alloc::string::String;
 use thiserror::Error;
 
 /// Main error type for Sphero RVR operations
 #[derive(Error, Debug)]
 pub enum RvrError {
+    #[cfg(feature = "std")]
     #[error("Serial port error: {0}")]
     Serial(#[from] serialport::Error),
 
+    #[cfg(feature = "std")]
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -18,12 +22,111 @@ pub enum RvrError {
     #[error("Timeout waiting for response")]
     Timeout,
 
+    #[error("Robot is asleep; wake it before sending commands")]
+    RobotAsleep,
+
+    #[error("Write to serial port timed out (transmit buffer full)")]
+    WriteStalled,
+
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
 
     #[error("Command failed with error code: {0:#04x}")]
     CommandFailed(u8),
+
+    /// The robot rejected a command with a non-success `error_code` value
+    #[error("Device error {code:#04x}: {name}")]
+    DeviceError { code: u8, name: &'static str },
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+
+    #[error("Too many requests in flight: all sequence numbers are pending")]
+    TooManyInFlight,
 }
 
 /// Convenience Result type
-pub type Result<T> = std::result::Result<T, RvrError>;
+pub type Result<T> = core::result::Result<T, RvrError>;
+
+impl RvrError {
+    /// Actionable guidance for an end user who hit this error
+    ///
+    /// Complements the `Display` message, which says what went wrong.
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Serial(_) => {
+                "check the port name, that the UART is enabled, and that you have permission to open it"
+            }
+            #[cfg(feature = "std")]
+            Self::Io(_) => "check the serial cable and that the robot is powered on",
+            Self::Protocol(_) | Self::InvalidResponse(_) => {
+                "check for firmware updates; if it persists, capture the traffic and report a bug"
+            }
+            Self::Checksum { .. } => {
+                "check the wiring and baud rate (115200); long or loose wires corrupt data"
+            }
+            Self::Timeout => "check that the robot is awake and wired correctly (TX to RX, RX to TX)",
+            Self::WriteStalled => "slow down the command rate or retry after a short pause",
+            Self::RobotAsleep => "wake the robot and send the command again",
+            Self::CommandFailed(_) => {
+                "check the command's parameters and that the robot supports it"
+            }
+            Self::DeviceError {
+                code: error_code::BUSY,
+                ..
+            } => "retry the command after a short pause",
+            Self::DeviceError { .. } => {
+                "check the command's parameters and that the robot supports it"
+            }
+            Self::InvalidParameter(_) => "fix the argument described in the error message",
+            Self::TooManyInFlight => "wait for outstanding commands to finish before sending more",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remediation_for_every_variant() {
+        let errors = [
+            RvrError::Serial(serialport::Error::new(
+                serialport::ErrorKind::NoDevice,
+                "no such port",
+            )),
+            RvrError::Io(std::io::ErrorKind::BrokenPipe.into()),
+            RvrError::Protocol("bad frame".to_string()),
+            RvrError::Checksum {
+                expected: 0x12,
+                actual: 0x34,
+            },
+            RvrError::Timeout,
+            RvrError::WriteStalled,
+            RvrError::RobotAsleep,
+            RvrError::InvalidResponse("short".to_string()),
+            RvrError::CommandFailed(0x06),
+            RvrError::DeviceError {
+                code: error_code::BAD_COMMAND_ID,
+                name: "Bad command ID",
+            },
+            RvrError::InvalidParameter("speed".to_string()),
+            RvrError::TooManyInFlight,
+        ];
+
+        for error in &errors {
+            assert!(!error.remediation().is_empty(), "{:?}", error);
+        }
+        assert!(RvrError::Timeout.remediation().contains("awake"));
+        assert!(errors[0].remediation().contains("UART"));
+        assert!(RvrError::RobotAsleep.remediation().starts_with("wake"));
+
+        let busy = RvrError::DeviceError {
+            code: error_code::BUSY,
+            name: "Device is busy",
+        };
+        assert!(busy.remediation().starts_with("retry"));
+        assert_eq!(busy.to_string(), "Device error 0x08: Device is busy");
+    }
+}
@@ -17,6 +17,15 @@
 //! - **Protocol Layer** (`protocol`): Pure state machines for packet parsing,
 //!   SLIP-style byte framing, and checksum calculation.
 //!
+//! # Features
+//!
+//! - `std` (default): the API and transport layers. With it disabled, the
+//!   crate is `no_std` (requiring `alloc`) and provides only `protocol`,
+//!   `error` and `api::constants`, for talking to the RVR from a
+//!   microcontroller.
+//! - `crossbeam`: crossbeam-channel notification receivers.
+//! - `test-support`: `VirtualRvr` and `MockTransport`.
+//!
 //! # Examples
 //!
 //! ```no_run
@@ -24,7 +33,7 @@
 //! use sphero_rvr::api::types::Color;
 //!
 //! fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!     let mut rvr = SpheroRvr::connect("/dev/serial0")?;
+//!     let rvr = SpheroRvr::connect("/dev/serial0")?;
 //!     rvr.wake()?;
 //!     rvr.set_all_leds(Color::GREEN)?;
 //!     rvr.sleep()?;
@@ -32,18 +41,23 @@
 //! }
 //! ```
 
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 // Allow unused code during development phases
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
+extern crate alloc;
+
 // Module declarations
 pub mod api;
 pub mod error;
 pub mod protocol;
+#[cfg(feature = "std")]
 pub mod transport;
 
 // Public API exports
 pub use error::{Result, RvrError};
 
 // High-level client
+#[cfg(feature = "std")]
 pub use api::SpheroRvr;
@@ -1,3 +1,6 @@
+use crate::error::{Result, RvrError};
+use alloc::string::ToString;
+
 /// Calculate checksum for Sphero packet
 ///
 /// Checksum is calculated as: 0xFF - (sum of all bytes & 0xFF)
@@ -12,6 +15,22 @@ pub fn verify_checksum(data: &[u8], expected: u8) -> bool {
     calculate_checksum(data) == expected
 }
 
+/// Split the trailing checksum byte off a packet and verify it
+///
+/// Returns the bytes the checksum covers, or `RvrError::Checksum` if it
+/// does not match them.
+pub fn split_checksum(data: &[u8]) -> Result<&[u8]> {
+    let (&actual, body) = data
+        .split_last()
+        .ok_or_else(|| RvrError::Protocol("Packet is empty".to_string()))?;
+
+    let expected = calculate_checksum(body);
+    if actual != expected {
+        return Err(RvrError::Checksum { expected, actual });
+    }
+    Ok(body)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -31,4 +50,18 @@ mod tests {
         assert!(verify_checksum(&data, checksum));
         assert!(!verify_checksum(&data, checksum + 1));
     }
+
+    #[test]
+    fn test_split_checksum() {
+        let data = vec![0x10, 0x20, 0x30, calculate_checksum(&[0x10, 0x20, 0x30])];
+        assert_eq!(split_checksum(&data).unwrap(), &[0x10, 0x20, 0x30]);
+
+        let mut corrupt = data.clone();
+        corrupt[1] ^= 0x01;
+        assert!(matches!(
+            split_checksum(&corrupt),
+            Err(RvrError::Checksum { actual, .. }) if actual == data[3]
+        ));
+        assert!(matches!(split_checksum(&[]), Err(RvrError::Protocol(_))));
+    }
 }
@@ -0,0 +1,36 @@
+//! Response error codes
+//!
+//! The first payload byte of every response.
+
+/// Command executed successfully
+pub const SUCCESS: u8 = 0x00;
+
+/// Bad device ID
+pub const BAD_DEVICE_ID: u8 = 0x01;
+
+/// Bad command ID
+pub const BAD_COMMAND_ID: u8 = 0x02;
+
+/// Not yet implemented
+pub const NOT_YET_IMPLEMENTED: u8 = 0x03;
+
+/// Command is restricted
+pub const RESTRICTED: u8 = 0x04;
+
+/// Bad data length
+pub const BAD_DATA_LENGTH: u8 = 0x05;
+
+/// Command failed
+pub const FAILED: u8 = 0x06;
+
+/// Bad parameter value
+pub const BAD_PARAMETER_VALUE: u8 = 0x07;
+
+/// Busy (try again later)
+pub const BUSY: u8 = 0x08;
+
+/// Router rejected the target node ID
+pub const BAD_TARGET_ID: u8 = 0x09;
+
+/// Target node exists but is not reachable
+pub const TARGET_UNAVAILABLE: u8 = 0x0A;
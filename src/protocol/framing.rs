@@ -4,6 +4,8 @@
 //! to ensure they aren't confused with framing markers.
 
 use crate::error::{Result, RvrError};
+use alloc::string::ToString;
+use alloc::vec::Vec;
 
 // Protocol constants
 pub const SOP: u8 = 0x8D;
@@ -104,6 +106,19 @@ mod tests {
         assert_eq!(decoded, original);
     }
 
+    #[test]
+    fn test_encode_consecutive_special_bytes() {
+        let original = vec![SOP, EOP, ESC, SOP, EOP, ESC];
+        let encoded = encode_bytes(&original);
+
+        // Each special byte becomes a two-byte escape sequence
+        assert_eq!(encoded.len(), original.len() * 2);
+        assert!(!encoded.contains(&SOP));
+        assert!(!encoded.contains(&EOP));
+
+        assert_eq!(decode_bytes(&encoded).unwrap(), original);
+    }
+
     #[test]
     fn test_decode_incomplete_escape() {
         let data = vec![ESC]; // Incomplete escape sequence
@@ -8,11 +8,13 @@
 //!
 //! Architecture:
 //! - `checksum`: Pure checksum calculation
+//! - `error_code`: Response status codes
 //! - `framing`: SLIP-style byte encoding/decoding
 //! - `packet`: Packet data structures and serialization
 //! - `parser`: Streaming parser state machine
 
 pub mod checksum;
+pub mod error_code;
 pub mod framing;
 pub mod packet;
 pub mod parser;
@@ -1,8 +1,44 @@
 use crate::error::{Result, RvrError};
-use crate::protocol::checksum::calculate_checksum;
+use crate::protocol::checksum::{calculate_checksum, split_checksum};
+use crate::protocol::framing::{encode_bytes, EOP, SOP};
+use crate::protocol::parser::SpheroParser;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Bit positions within the FLAGS byte
+///
+/// | Bit | Meaning                         |
+/// |-----|---------------------------------|
+/// | 0   | is_response                     |
+/// | 1   | requests_response               |
+/// | 2   | requests_only_error_response    |
+/// | 3   | is_activity                     |
+/// | 4   | has_target_id                   |
+/// | 5   | has_source_id                   |
+/// | 6-7 | reserved (carried through as-is)|
+mod flag_bits {
+    pub const IS_RESPONSE: u8 = 1 << 0;
+    pub const REQUESTS_RESPONSE: u8 = 1 << 1;
+    pub const REQUESTS_ONLY_ERROR_RESPONSE: u8 = 1 << 2;
+    pub const IS_ACTIVITY: u8 = 1 << 3;
+    pub const HAS_TARGET_ID: u8 = 1 << 4;
+    pub const HAS_SOURCE_ID: u8 = 1 << 5;
+
+    /// Shift of the reserved field (bits 6-7)
+    pub const RESERVED_SHIFT: u8 = 6;
+    /// Mask of the reserved field after shifting down (2 bits)
+    pub const RESERVED_MASK: u8 = 0b11;
+}
 
 /// Packet flags for command/response classification
+///
+/// See `flag_bits` for the bit layout. `reserved` holds the two upper bits
+/// and is masked to 2 bits on encode so it can never bleed into the flags.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PacketFlags {
     pub is_response: bool,
     pub requests_response: bool,
@@ -16,45 +52,54 @@ pub struct PacketFlags {
 impl PacketFlags {
     /// Convert flags to a byte
     pub fn to_byte(self) -> u8 {
+        use flag_bits::*;
+
         let mut byte = 0u8;
         if self.is_response {
-            byte |= 0b0000_0001; // bit 0
+            byte |= IS_RESPONSE;
         }
         if self.requests_response {
-            byte |= 0b0000_0010; // bit 1
+            byte |= REQUESTS_RESPONSE;
         }
         if self.requests_only_error_response {
-            byte |= 0b0000_0100; // bit 2
+            byte |= REQUESTS_ONLY_ERROR_RESPONSE;
         }
         if self.is_activity {
-            byte |= 0b0000_1000; // bit 3
+            byte |= IS_ACTIVITY;
         }
         if self.has_target_id {
-            byte |= 0b0001_0000; // bit 4
+            byte |= HAS_TARGET_ID;
         }
         if self.has_source_id {
-            byte |= 0b0010_0000; // bit 5
+            byte |= HAS_SOURCE_ID;
         }
-        byte |= (self.reserved & 0b11) << 6; // bits 6-7
+        byte |= (self.reserved & RESERVED_MASK) << RESERVED_SHIFT;
         byte
     }
 
     /// Create flags from a byte
     pub fn from_byte(byte: u8) -> Self {
+        use flag_bits::*;
+
         Self {
-            is_response: byte & 0b0000_0001 != 0,                 // bit 0
-            requests_response: byte & 0b0000_0010 != 0,           // bit 1
-            requests_only_error_response: byte & 0b0000_0100 != 0, // bit 2
-            is_activity: byte & 0b0000_1000 != 0,                 // bit 3
-            has_target_id: byte & 0b0001_0000 != 0,               // bit 4
-            has_source_id: byte & 0b0010_0000 != 0,               // bit 5
-            reserved: (byte >> 6) & 0b11,                         // bits 6-7
+            is_response: byte & IS_RESPONSE != 0,
+            requests_response: byte & REQUESTS_RESPONSE != 0,
+            requests_only_error_response: byte & REQUESTS_ONLY_ERROR_RESPONSE != 0,
+            is_activity: byte & IS_ACTIVITY != 0,
+            has_target_id: byte & HAS_TARGET_ID != 0,
+            has_source_id: byte & HAS_SOURCE_ID != 0,
+            reserved: (byte >> RESERVED_SHIFT) & RESERVED_MASK,
         }
     }
 }
 
 /// Represents a Sphero API packet
+///
+/// With the `serde` feature, packets serialize field by field (flags as
+/// booleans, IDs and payload as plain numbers), so JSON captures stay
+/// readable.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Packet {
     pub flags: PacketFlags,
     pub target_id: Option<u8>,
@@ -92,6 +137,69 @@ impl Packet {
         }
     }
 
+    /// Address the packet to `target_id` from `source_id`
+    ///
+    /// Commands sent over the UART expansion port must carry both IDs (and
+    /// their flags) or the robot's router may drop them. `new_command`
+    /// leaves them unset, so code building packets for `Dispatcher`
+    /// directly should add them here, as `SpheroRvr` does:
+    ///
+    /// ```
+    /// use sphero_rvr::api::constants::routing_node;
+    /// use sphero_rvr::protocol::packet::Packet;
+    ///
+    /// let wake = Packet::new_command(0x13, 0x0D, 0, vec![])
+    ///     .with_routing(routing_node::PRIMARY_PROCESSOR, routing_node::UART_PORT);
+    /// assert_eq!(wake.target_id, Some(0x01));
+    /// ```
+    pub fn with_routing(self, target_id: u8, source_id: u8) -> Self {
+        self.with_target(target_id).with_source(source_id)
+    }
+
+    /// Address the packet to `target_id`, setting its flag
+    pub fn with_target(mut self, target_id: u8) -> Self {
+        self.flags.has_target_id = true;
+        self.target_id = Some(target_id);
+        self
+    }
+
+    /// Mark the packet as sent from `source_id`, setting its flag
+    pub fn with_source(mut self, source_id: u8) -> Self {
+        self.flags.has_source_id = true;
+        self.source_id = Some(source_id);
+        self
+    }
+
+    /// Set whether the robot should answer this packet
+    ///
+    /// `new_command` requests a response. Packets sent without one should
+    /// go through `Dispatcher::send_packet_no_response`, since
+    /// `send_command` would wait for a reply that never comes.
+    pub fn with_requests_response(mut self, requests_response: bool) -> Self {
+        self.flags.requests_response = requests_response;
+        self
+    }
+
+    /// Set whether the packet counts as user activity (resets the sleep timer)
+    pub fn with_activity(mut self, is_activity: bool) -> Self {
+        self.flags.is_activity = is_activity;
+        self
+    }
+
+    /// Check whether this packet is an unsolicited notification
+    ///
+    /// Any packet that is not a response to one of our commands is treated as a
+    /// notification: activity packets (sensor streaming, events) as well as
+    /// robot-initiated commands that arrive with neither the response nor the
+    /// activity flag set.
+    ///
+    /// Ambiguous packets that set both `is_response` and `is_activity` are
+    /// treated as responses, since a caller may be blocked waiting on them and
+    /// the sequence number is the only way to route them back.
+    pub fn is_notification(&self) -> bool {
+        !self.flags.is_response
+    }
+
     /// Serialize packet to raw bytes (before SLIP encoding and framing)
     ///
     /// Returns: [FLAGS] [TARGET_ID?] [SOURCE_ID?] [DEVICE_ID] [COMMAND_ID] [SEQ] [PAYLOAD...] [CHECKSUM]
@@ -127,6 +235,20 @@ impl Packet {
         bytes
     }
 
+    /// Serialize packet to a complete wire frame
+    ///
+    /// Returns: [SOP] [SLIP-encoded `to_bytes()`] [EOP], ready to write to the UART.
+    /// This is the inverse of `SpheroParser::feed`.
+    pub fn to_framed_bytes(&self) -> Vec<u8> {
+        let escaped = encode_bytes(&self.to_bytes());
+
+        let mut framed = Vec::with_capacity(escaped.len() + 2);
+        framed.push(SOP);
+        framed.extend_from_slice(&escaped);
+        framed.push(EOP);
+        framed
+    }
+
     /// Parse packet from unescaped buffer (after SLIP decoding, without SOP/EOP)
     ///
     /// Expected format: [FLAGS] [TARGET_ID?] [SOURCE_ID?] [DEVICE_ID] [COMMAND_ID] [SEQ] [PAYLOAD...] [CHECKSUM]
@@ -139,6 +261,21 @@ impl Packet {
             )));
         }
 
+        Self::from_body(split_checksum(data)?)
+    }
+
+    /// Parse packet from a checksum-verified body (`from_bytes` without the trailing checksum)
+    ///
+    /// Expected format: [FLAGS] [TARGET_ID?] [SOURCE_ID?] [DEVICE_ID] [COMMAND_ID] [SEQ] [PAYLOAD...]
+    pub(crate) fn from_body(data: &[u8]) -> Result<Self> {
+        // Minimum body: FLAGS + DEVICE_ID + COMMAND_ID + SEQ = 4 bytes
+        if data.len() < 4 {
+            return Err(RvrError::Protocol(format!(
+                "Packet body too short: {} bytes (minimum 4)",
+                data.len()
+            )));
+        }
+
         let mut idx = 0;
 
         // Parse FLAGS
@@ -173,11 +310,11 @@ impl Packet {
             None
         };
 
-        // Parse fixed fields (need at least 4 more bytes: DEVICE_ID, COMMAND_ID, SEQ, CHECKSUM)
-        if idx + 4 > data.len() {
+        // Parse fixed fields (need at least 3 more bytes: DEVICE_ID, COMMAND_ID, SEQ)
+        if idx + 3 > data.len() {
             return Err(RvrError::Protocol(format!(
                 "Packet truncated: expected at least {} bytes, got {}",
-                idx + 4,
+                idx + 3,
                 data.len()
             )));
         }
@@ -191,20 +328,8 @@ impl Packet {
         let sequence_number = data[idx];
         idx += 1;
 
-        // Extract checksum (last byte)
-        let checksum = data[data.len() - 1];
-
-        // Extract payload (everything between SEQ and CHECKSUM)
-        let payload = data[idx..data.len() - 1].to_vec();
-
-        // Verify checksum (calculated on all bytes except the checksum itself)
-        let expected_checksum = calculate_checksum(&data[..data.len() - 1]);
-        if checksum != expected_checksum {
-            return Err(RvrError::Checksum {
-                expected: expected_checksum,
-                actual: checksum,
-            });
-        }
+        // Payload is everything after SEQ
+        let payload = data[idx..].to_vec();
 
         Ok(Self {
             flags,
@@ -216,6 +341,82 @@ impl Packet {
             payload,
         })
     }
+
+    /// Format the framed packet (SOP..EOP, escaped) as a hex capture
+    ///
+    /// Produces space-separated uppercase bytes, e.g. `8D 02 13 0D 00 DD D8`,
+    /// suitable for pasting into logs and bug reports. The inverse is
+    /// `from_hex_capture`.
+    pub fn to_hex_string(&self) -> String {
+        self.to_framed_bytes()
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Parse a hex capture of a framed packet (SOP..EOP) back into a Packet
+    ///
+    /// Bytes may be separated by whitespace, `:`, `,` or `-`, may carry a
+    /// `0x` prefix, and may be in either case. Groups of unseparated digits
+    /// (e.g. `8D3E11`) are split into bytes. The frame is unescaped and its
+    /// checksum verified, exactly as if it had arrived on the wire.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use sphero_rvr::protocol::Packet;
+    /// let packet = Packet::from_hex_capture("8d:02:13:0d:00:dd:d8").unwrap();
+    /// assert_eq!(packet.device_id, 0x13);
+    /// assert_eq!(packet.command_id, 0x0D);
+    /// ```
+    pub fn from_hex_capture(s: &str) -> Result<Self> {
+        let mut bytes = Vec::new();
+        for token in s.split(|c: char| c.is_whitespace() || matches!(c, ':' | ',' | '-')) {
+            let digits = token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token);
+            if digits.len() % 2 != 0 {
+                return Err(RvrError::Protocol(format!(
+                    "Odd number of hex digits in capture token '{}'",
+                    token
+                )));
+            }
+            for pair in digits.as_bytes().chunks(2) {
+                // A chunk splitting a non-ASCII char becomes "" and is rejected below
+                let pair = core::str::from_utf8(pair).unwrap_or("");
+                let byte = u8::from_str_radix(pair, 16).map_err(|_| {
+                    RvrError::Protocol(format!("Invalid hex byte '{}' in capture", pair))
+                })?;
+                bytes.push(byte);
+            }
+        }
+
+        let unframed = || {
+            RvrError::Protocol(
+                "Hex capture must start with SOP (8D) and end with EOP (D8)".to_string(),
+            )
+        };
+        if bytes.first() != Some(&SOP) {
+            return Err(unframed());
+        }
+
+        // Run the frame through the wire parser, so escapes and stray
+        // SOP/EOP bytes are handled exactly as the RX thread would
+        let mut parser = SpheroParser::with_max_len(bytes.len());
+        for (i, &byte) in bytes.iter().enumerate() {
+            if let Some(packet) = parser.feed(byte)? {
+                if i != bytes.len() - 1 {
+                    return Err(RvrError::Protocol(
+                        "Hex capture has bytes after EOP".to_string(),
+                    ));
+                }
+                return Ok(packet);
+            }
+        }
+        Err(unframed())
+    }
 }
 
 #[cfg(test)]
@@ -265,6 +466,67 @@ mod tests {
         assert_eq!(original, recovered);
     }
 
+    #[test]
+    fn test_packet_flags_roundtrip_all_bytes() {
+        // Every possible flags byte must survive a decode/encode cycle
+        for byte in 0..=u8::MAX {
+            assert_eq!(
+                PacketFlags::from_byte(byte).to_byte(),
+                byte,
+                "byte {byte:#010b}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_packet_flags_bits_independent() {
+        // Setting a single bit must set exactly one field
+        let fields = |f: PacketFlags| {
+            [
+                f.is_response,
+                f.requests_response,
+                f.requests_only_error_response,
+                f.is_activity,
+                f.has_target_id,
+                f.has_source_id,
+            ]
+        };
+
+        for bit in 0..6 {
+            let flags = PacketFlags::from_byte(1 << bit);
+            let set: Vec<usize> = fields(flags)
+                .iter()
+                .enumerate()
+                .filter(|(_, &v)| v)
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(set, vec![bit], "bit {bit}");
+            assert_eq!(flags.reserved, 0);
+        }
+
+        // Reserved bits only affect the reserved field
+        for bit in 6..8 {
+            let flags = PacketFlags::from_byte(1 << bit);
+            assert!(fields(flags).iter().all(|&v| !v), "bit {bit}");
+            assert_eq!(flags.reserved, 1 << (bit - 6));
+        }
+    }
+
+    #[test]
+    fn test_packet_flags_reserved_does_not_bleed() {
+        // Out-of-range reserved values are masked to 2 bits
+        let flags = PacketFlags {
+            is_response: false,
+            requests_response: false,
+            requests_only_error_response: false,
+            is_activity: false,
+            has_target_id: false,
+            has_source_id: false,
+            reserved: 0xFF,
+        };
+        assert_eq!(flags.to_byte(), 0b1100_0000);
+    }
+
     #[test]
     fn test_new_command_packet() {
         let packet = Packet::new_command(0x10, 0x20, 5, vec![0x01, 0x02]);
@@ -278,6 +540,72 @@ mod tests {
         assert!(packet.source_id.is_none());
     }
 
+    #[test]
+    fn test_with_routing_sets_ids_and_flags() {
+        let packet = Packet::new_command(0x1A, 0x1A, 5, vec![0x3F]).with_routing(0x01, 0x02);
+        assert!(packet.flags.has_target_id);
+        assert!(packet.flags.has_source_id);
+        assert_eq!(packet.target_id, Some(0x01));
+        assert_eq!(packet.source_id, Some(0x02));
+
+        // The IDs survive a round trip through the wire format
+        let decoded = Packet::from_bytes(&packet.to_bytes()).unwrap();
+        assert_eq!(decoded.target_id, Some(0x01));
+        assert_eq!(decoded.source_id, Some(0x02));
+        assert_eq!(decoded.flags.to_byte(), packet.flags.to_byte());
+    }
+
+    #[test]
+    fn test_builder_flags() {
+        let packet = Packet::new_command(0x13, 0x0D, 0, vec![])
+            .with_target(0x01)
+            .with_requests_response(false)
+            .with_activity(true);
+        assert_eq!(packet.target_id, Some(0x01));
+        assert!(packet.flags.has_target_id);
+        assert_eq!(packet.source_id, None);
+        assert!(!packet.flags.has_source_id);
+        assert!(!packet.flags.requests_response);
+        assert!(packet.flags.is_activity);
+
+        let decoded = Packet::from_bytes(&packet.to_bytes()).unwrap();
+        assert_eq!(decoded.flags.to_byte(), packet.flags.to_byte());
+        assert_eq!(decoded.target_id, Some(0x01));
+
+        let packet = packet.with_source(0x02);
+        assert_eq!(packet.source_id, Some(0x02));
+        assert!(packet.flags.has_source_id);
+    }
+
+    #[test]
+    fn test_is_notification_response() {
+        let mut packet = Packet::new_command(0x13, 0x0D, 1, vec![]);
+        packet.flags.is_response = true;
+        packet.flags.requests_response = false;
+        assert!(!packet.is_notification());
+    }
+
+    #[test]
+    fn test_is_notification_activity() {
+        let mut packet = Packet::new_command(0x18, 0x3D, 0, vec![0x01]);
+        packet.flags.requests_response = false;
+        packet.flags.is_activity = true;
+        assert!(packet.is_notification());
+    }
+
+    #[test]
+    fn test_is_notification_ambiguous_flags() {
+        // Neither response nor activity: robot-initiated command, still a notification
+        let mut packet = Packet::new_command(0x13, 0x19, 0, vec![]);
+        packet.flags.requests_response = false;
+        assert!(packet.is_notification());
+
+        // Both response and activity: routed as a response
+        packet.flags.is_response = true;
+        packet.flags.is_activity = true;
+        assert!(!packet.is_notification());
+    }
+
     #[test]
     fn test_packet_to_bytes_simple() {
         let packet = Packet::new_command(0x10, 0x20, 5, vec![]);
@@ -357,6 +685,35 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_packet_from_bytes_missing_routing_id() {
+        use flag_bits::{HAS_SOURCE_ID, HAS_TARGET_ID};
+
+        let with_checksum = |body: &[u8]| {
+            let mut bytes = body.to_vec();
+            bytes.push(calculate_checksum(body));
+            bytes
+        };
+
+        // Source flag set, but the buffer ends before the source byte
+        let data = with_checksum(&[HAS_SOURCE_ID]);
+        assert!(matches!(
+            Packet::from_bytes(&data),
+            Err(RvrError::Protocol(_))
+        ));
+
+        // Both flags set: the IDs consume the fixed fields, leaving too few bytes
+        let data = with_checksum(&[HAS_TARGET_ID | HAS_SOURCE_ID, 0x01, 0x13, 0x0D]);
+        assert!(matches!(
+            Packet::from_bytes(&data),
+            Err(RvrError::Protocol(_))
+        ));
+        assert!(matches!(
+            Packet::from_body(&[HAS_SOURCE_ID, 0x13, 0x0D, 0x01]),
+            Err(RvrError::Protocol(_))
+        ));
+    }
+
     #[test]
     fn test_packet_from_bytes_bad_checksum() {
         let packet = Packet::new_command(0x10, 0x20, 5, vec![]);
@@ -369,4 +726,95 @@ mod tests {
         let result = Packet::from_bytes(&bytes);
         assert!(matches!(result, Err(RvrError::Checksum { .. })));
     }
+
+    #[test]
+    fn test_framed_bytes_round_trip_through_parser() {
+        // Payload includes SOP, EOP and ESC to exercise escaping
+        let packet = Packet::new_command(0x1A, 0x1C, 7, vec![0x8D, 0xD8, 0xAB, 0x01])
+            .with_routing(0x01, 0x02);
+        let framed = packet.to_framed_bytes();
+        assert_eq!(framed.first(), Some(&SOP));
+        assert_eq!(framed.last(), Some(&EOP));
+
+        let mut parser = crate::protocol::parser::SpheroParser::new();
+        let parsed = framed
+            .iter()
+            .find_map(|&byte| parser.feed(byte).unwrap())
+            .unwrap();
+        assert_eq!(parsed.to_framed_bytes(), framed);
+    }
+
+    #[test]
+    fn test_hex_capture_round_trip() {
+        // Payload includes SOP, EOP and ESC to exercise escaping
+        let mut packet = Packet::new_command(0x1A, 0x1C, 0x8D, vec![0x8D, 0xD8, 0xAB, 0x01]);
+        packet.flags.has_target_id = true;
+        packet.target_id = Some(0x01);
+
+        let capture = packet.to_hex_string();
+        assert!(capture.starts_with("8D "));
+        assert!(capture.ends_with(" D8"));
+
+        let parsed = Packet::from_hex_capture(&capture).unwrap();
+        assert_eq!(parsed.flags, packet.flags);
+        assert_eq!(parsed.target_id, Some(0x01));
+        assert_eq!(parsed.device_id, 0x1A);
+        assert_eq!(parsed.command_id, 0x1C);
+        assert_eq!(parsed.sequence_number, 0x8D);
+        assert_eq!(parsed.payload, vec![0x8D, 0xD8, 0xAB, 0x01]);
+    }
+
+    #[test]
+    fn test_hex_capture_separator_tolerance() {
+        let expected = Packet::new_command(0x13, 0x0D, 0, vec![]).to_hex_string();
+        assert_eq!(expected, "8D 02 13 0D 00 DD D8");
+
+        for capture in [
+            "8d 02 13 0d 00 dd d8",
+            "8D:02:13:0D:00:DD:D8",
+            "8D-02-13-0D-00-DD-D8",
+            "0x8D, 0x02, 0x13, 0x0D, 0x00, 0xDD, 0xD8",
+            "  8D02130D  00DDD8\n",
+            "8D\t02  13\n0D 00 DD D8",
+        ] {
+            let packet = Packet::from_hex_capture(capture).unwrap();
+            assert_eq!(packet.device_id, 0x13, "{:?}", capture);
+            assert_eq!(packet.command_id, 0x0D, "{:?}", capture);
+        }
+    }
+
+    #[test]
+    fn test_hex_capture_rejects_bad_input() {
+        // Missing EOP
+        assert!(Packet::from_hex_capture("8D 02 13 0D 00 DD").is_err());
+        // Not hex
+        assert!(Packet::from_hex_capture("8D 02 13 0D 00 ZZ D8").is_err());
+        // Odd digit count
+        assert!(Packet::from_hex_capture("8D 02 13 0D 0 DD D8").is_err());
+        // Bad checksum
+        assert!(matches!(
+            Packet::from_hex_capture("8D 02 13 0D 00 DC D8"),
+            Err(RvrError::Checksum { .. })
+        ));
+        assert!(Packet::from_hex_capture("").is_err());
+        // Unescaped SOP mid-frame
+        assert!(Packet::from_hex_capture("8D 02 13 8D 0D 00 DD D8").is_err());
+        // Early EOP with trailing bytes
+        assert!(Packet::from_hex_capture("8D 02 13 0D 00 DD D8 00 D8").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_json_round_trip() {
+        let packet = Packet::new_command(0x1A, 0x1A, 7, vec![0x3F, 0xFF, 0x00, 0x00])
+            .with_routing(0x01, 0x02);
+
+        let json = serde_json::to_string(&packet).unwrap();
+        assert!(json.contains("\"device_id\":26"), "{}", json);
+        assert!(json.contains("\"payload\":[63,255,0,0]"), "{}", json);
+
+        let decoded: Packet = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.to_bytes(), packet.to_bytes());
+        assert_eq!(decoded.flags, packet.flags);
+    }
 }
@@ -1,6 +1,10 @@
 use crate::error::{Result, RvrError};
+use crate::protocol::checksum::split_checksum;
 use crate::protocol::framing::{EOP, ESC, ESC_MASK, SOP};
 use crate::protocol::packet::Packet;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
 
 /// Parser state machine for streaming UART input
 #[derive(Debug)]
@@ -17,6 +21,12 @@ enum ParserState {
     },
 }
 
+/// Default cap on an unescaped packet buffer
+///
+/// Well above any real RVR packet, but small enough that a junk stream
+/// with no EOP cannot grow the buffer without bound.
+pub const DEFAULT_MAX_PACKET_LEN: usize = 256;
+
 /// Streaming parser for Sphero RVR protocol packets
 ///
 /// This parser operates byte-by-byte on UART input streams and handles:
@@ -24,6 +34,7 @@ enum ParserState {
 /// - Variable-length payloads
 /// - Automatic resynchronization on errors
 /// - Checksum verification
+/// - A maximum packet length, so a lost EOP cannot grow the buffer forever
 ///
 /// # Example
 ///
@@ -53,13 +64,24 @@ enum ParserState {
 /// ```
 pub struct SpheroParser {
     state: ParserState,
+    /// Longest unescaped buffer accepted before resyncing
+    max_packet_len: usize,
+    /// Unescaped bytes of the last complete frame that failed to parse
+    last_error_frame: Option<Vec<u8>>,
 }
 
 impl SpheroParser {
     /// Create a new parser in the initial state
     pub fn new() -> Self {
+        Self::with_max_len(DEFAULT_MAX_PACKET_LEN)
+    }
+
+    /// Create a parser that resyncs once a packet exceeds `max_len` unescaped bytes
+    pub fn with_max_len(max_len: usize) -> Self {
         Self {
             state: ParserState::WaitingForSop,
+            max_packet_len: max_len,
+            last_error_frame: None,
         }
     }
 
@@ -76,6 +98,7 @@ impl SpheroParser {
     /// - Checksum failures: Parser resets to `WaitingForSop`
     /// - Unexpected SOP mid-packet: Parser discards corrupted buffer and starts fresh
     /// - Incomplete escape sequences: Reported as error, parser resets
+    /// - Packets longer than the maximum length: Reported as error, parser resets
     ///
     /// The caller should log errors and continue reading bytes.
     pub fn feed(&mut self, byte: u8) -> Result<Option<Packet>> {
@@ -108,7 +131,7 @@ impl SpheroParser {
                     // SLIP decoding: escaped_byte | ESC_MASK restores original value
                     buffer.push(byte | ESC_MASK);
                     *is_escaped = false;
-                    Ok(None)
+                    self.check_length()
                 } else if byte == ESC {
                     // Next byte needs unescaping
                     *is_escaped = true;
@@ -131,7 +154,7 @@ impl SpheroParser {
 
                     // CRITICAL: Extract buffer and reset state BEFORE parsing
                     // This ensures parser is in a valid state even if parse_buffer() fails
-                    let final_buffer = std::mem::take(buffer);
+                    let final_buffer = core::mem::take(buffer);
                     self.state = ParserState::WaitingForSop;
 
                     if was_escaped {
@@ -140,28 +163,55 @@ impl SpheroParser {
                         ));
                     }
 
-                    // Parse the accumulated buffer
+                    // Parse the accumulated buffer, keeping it if it is bad
                     match Self::parse_buffer(&final_buffer) {
                         Ok(packet) => Ok(Some(packet)),
-                        Err(e) => Err(e),
+                        Err(e) => {
+                            self.last_error_frame = Some(final_buffer);
+                            Err(e)
+                        }
                     }
                 } else {
                     // Normal data byte, add to buffer
                     buffer.push(byte);
-                    Ok(None)
+                    self.check_length()
                 }
             }
         }
     }
 
+    /// Resync if the packet being read has outgrown the maximum length
+    fn check_length(&mut self) -> Result<Option<Packet>> {
+        if let ParserState::ReadingPacket { buffer, .. } = &self.state {
+            if buffer.len() > self.max_packet_len {
+                self.state = ParserState::WaitingForSop;
+                return Err(RvrError::Protocol("packet too long, resyncing".to_string()));
+            }
+        }
+        Ok(None)
+    }
+
     /// Parse an unescaped buffer into a Packet
     ///
     /// Buffer format: [FLAGS] [TARGET_ID?] [SOURCE_ID?] [DEVICE_ID] [COMMAND_ID] [SEQ] [PAYLOAD...] [CHECKSUM]
     ///
-    /// This is called when EOP is received and contains all packet parsing logic.
+    /// This is called when EOP is received. The checksum is split off and
+    /// verified first, so a corrupted frame is always reported as
+    /// `RvrError::Checksum` rather than whatever structural error the
+    /// corruption happens to produce.
     fn parse_buffer(buffer: &[u8]) -> Result<Packet> {
-        // Delegate to Packet::from_bytes which handles all the parsing
-        Packet::from_bytes(buffer)
+        let body = split_checksum(buffer)?;
+        Packet::from_body(body)
+    }
+
+    /// Unescaped bytes of the last complete frame that failed to parse
+    ///
+    /// Set when a frame reaches EOP but is rejected (bad checksum or
+    /// malformed header), and kept until the next such failure or `reset`.
+    /// Excludes SOP/EOP and includes the checksum byte, so it can be lined
+    /// up with a logic-analyzer capture of the line.
+    pub fn last_error_frame(&self) -> Option<&[u8]> {
+        self.last_error_frame.as_deref()
     }
 
     /// Reset the parser to initial state
@@ -169,6 +219,7 @@ impl SpheroParser {
     /// Useful for explicit error recovery or reinitialization
     pub fn reset(&mut self) {
         self.state = ParserState::WaitingForSop;
+        self.last_error_frame = None;
     }
 }
 
@@ -366,6 +417,49 @@ mod tests {
         assert_eq!(parsed.device_id, 0x11);
     }
 
+    #[test]
+    fn test_corrupt_flags_reported_as_checksum() {
+        let mut parser = SpheroParser::new();
+
+        // Flip the routing bits so the header claims target/source IDs the
+        // short frame does not have; the checksum catches it first
+        let mut bytes = Packet::new_command(0x10, 0x20, 5, vec![]).to_bytes();
+        bytes[0] |= 0x30;
+
+        let mut stream = vec![SOP];
+        stream.extend_from_slice(&bytes);
+        stream.push(EOP);
+
+        let result = feed_bytes(&mut parser, &stream);
+        assert!(matches!(result, Err(RvrError::Checksum { .. })));
+    }
+
+    #[test]
+    fn test_last_error_frame_keeps_bad_bytes() {
+        let mut parser = SpheroParser::new();
+        assert!(parser.last_error_frame().is_none());
+
+        let mut bytes = Packet::new_command(0x10, 0x20, 5, vec![0x01, 0x02]).to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+
+        let mut stream = vec![SOP];
+        stream.extend_from_slice(&bytes);
+        stream.push(EOP);
+
+        let result = feed_bytes(&mut parser, &stream);
+        assert!(matches!(result, Err(RvrError::Checksum { .. })));
+        assert_eq!(parser.last_error_frame(), Some(&bytes[..]));
+
+        // A good frame afterwards leaves the last failure in place
+        let good = Packet::new_command(0x10, 0x20, 6, vec![]).to_framed_bytes();
+        assert!(feed_bytes(&mut parser, &good).unwrap().is_some());
+        assert_eq!(parser.last_error_frame(), Some(&bytes[..]));
+
+        parser.reset();
+        assert!(parser.last_error_frame().is_none());
+    }
+
     #[test]
     fn test_incomplete_escape_at_eop() {
         let mut parser = SpheroParser::new();
@@ -377,6 +471,59 @@ mod tests {
         assert!(matches!(result, Err(RvrError::Protocol(_))));
     }
 
+    /// Length of the buffer currently being accumulated
+    fn buffered_len(parser: &SpheroParser) -> usize {
+        match &parser.state {
+            ParserState::WaitingForSop => 0,
+            ParserState::ReadingPacket { buffer, .. } => buffer.len(),
+        }
+    }
+
+    #[test]
+    fn test_max_packet_len_resyncs() {
+        let mut parser = SpheroParser::new();
+        let mut errors = 0;
+
+        // A lost EOP followed by a junk stream that never ends a packet
+        parser.feed(SOP).unwrap();
+        for i in 0..10_000u32 {
+            let byte = (i % 0x80) as u8;
+            match parser.feed(byte) {
+                Ok(_) => {}
+                Err(RvrError::Protocol(msg)) => {
+                    assert_eq!(msg, "packet too long, resyncing");
+                    errors += 1;
+                    // Resynced; start another runaway packet
+                    parser.feed(SOP).unwrap();
+                }
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+            assert!(buffered_len(&parser) <= DEFAULT_MAX_PACKET_LEN);
+        }
+        assert!(errors > 0);
+
+        // A valid packet still parses afterwards
+        let packet = Packet::new_command(0x11, 0x21, 6, vec![]);
+        parser.reset();
+        let parsed = feed_bytes(&mut parser, &packet.to_framed_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.device_id, 0x11);
+    }
+
+    #[test]
+    fn test_with_max_len() {
+        let mut parser = SpheroParser::with_max_len(8);
+        let packet = Packet::new_command(0x10, 0x20, 5, vec![0x01; 8]);
+        let result = feed_bytes(&mut parser, &packet.to_framed_bytes());
+        assert!(matches!(result, Err(RvrError::Protocol(_))));
+
+        let short = Packet::new_command(0x10, 0x20, 5, vec![0x01]);
+        assert!(feed_bytes(&mut parser, &short.to_framed_bytes())
+            .unwrap()
+            .is_some());
+    }
+
     #[test]
     fn test_reset() {
         let mut parser = SpheroParser::new();
@@ -399,6 +546,69 @@ mod tests {
         assert_eq!(parsed.device_id, 0x13);
     }
 
+    /// Encode and frame a packet exactly as the dispatcher TX path does
+    fn frame(packet: &Packet) -> Vec<u8> {
+        let mut framed = vec![SOP];
+        framed.extend_from_slice(&encode_bytes(&packet.to_bytes()));
+        framed.push(EOP);
+        framed
+    }
+
+    /// Assert no raw framing byte appears inside the frame body
+    fn assert_no_raw_framing_bytes(framed: &[u8]) {
+        let body = &framed[1..framed.len() - 1];
+        assert!(!body.contains(&SOP), "raw SOP in body: {:02x?}", body);
+        assert!(!body.contains(&EOP), "raw EOP in body: {:02x?}", body);
+
+        // Every ESC must be followed by an escaped (non-framing) byte
+        let mut iter = body.iter();
+        while let Some(&byte) = iter.next() {
+            if byte == ESC {
+                let next = *iter.next().expect("dangling ESC");
+                assert!(next != SOP && next != EOP && next != ESC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_consecutive_framing_bytes_in_payload() {
+        let mut parser = SpheroParser::new();
+
+        let payload = vec![SOP, EOP, ESC, SOP, EOP, ESC];
+        let packet = Packet::new_command(0x1A, 0x1A, 9, payload.clone());
+        let framed = frame(&packet);
+
+        assert_no_raw_framing_bytes(&framed);
+
+        let parsed = feed_bytes(&mut parser, &framed).unwrap().unwrap();
+        assert_eq!(parsed.payload, payload);
+        assert_eq!(parsed.sequence_number, 9);
+    }
+
+    #[test]
+    fn test_checksum_equal_to_framing_byte() {
+        // Pick a trailing payload byte so the checksum itself lands on each
+        // framing byte; the checksum must be escaped like any other byte.
+        for target in [SOP, EOP, ESC] {
+            let tail = (0..=u8::MAX)
+                .find(|&b| {
+                    let packet = Packet::new_command(0x1A, 0x1A, 3, vec![SOP, EOP, ESC, b]);
+                    *packet.to_bytes().last().unwrap() == target
+                })
+                .unwrap();
+
+            let payload = vec![SOP, EOP, ESC, tail];
+            let packet = Packet::new_command(0x1A, 0x1A, 3, payload.clone());
+            let framed = frame(&packet);
+
+            assert_no_raw_framing_bytes(&framed);
+
+            let mut parser = SpheroParser::new();
+            let parsed = feed_bytes(&mut parser, &framed).unwrap().unwrap();
+            assert_eq!(parsed.payload, payload);
+        }
+    }
+
     #[test]
     fn test_integration_full_roundtrip() {
         // This test validates the entire encode -> parse pipeline
@@ -1,19 +1,650 @@
+use crate::api::constants::{device, error_code, power_command};
 use crate::error::{Result, RvrError};
 use crate::protocol::framing::{encode_bytes, EOP, SOP};
 use crate::protocol::packet::Packet;
 use crate::protocol::parser::SpheroParser;
+use crate::transport::recorder::CommandRecorder;
+use crate::transport::serial::SerialTransport;
 use serialport::SerialPort;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Read;
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Response channel for a single request
 type ResponseSender = Sender<Packet>;
 
+/// Requests awaiting a response matched by `(device_id, command_id)`
+type PendingByCommand = HashMap<(u8, u8), ResponseSender>;
+
+/// Sending half of the notification channel
+///
+/// Uses `std::sync::mpsc` by default, or `crossbeam-channel` when the
+/// `crossbeam` feature is enabled.
+#[cfg(not(feature = "crossbeam"))]
+pub type NotificationSender = mpsc::Sender<Packet>;
+
+/// Receiving half of the notification channel
+///
+/// With the `crossbeam` feature enabled, this receiver can be cloned to
+/// fan notifications out to multiple consumers and used with `select!`.
+#[cfg(not(feature = "crossbeam"))]
+pub type NotificationReceiver = mpsc::Receiver<Packet>;
+
+/// Error returned by `NotificationReceiver::recv_timeout`
+#[cfg(not(feature = "crossbeam"))]
+pub use std::sync::mpsc::RecvTimeoutError;
+
+/// Sending half of the notification channel
+///
+/// Uses `std::sync::mpsc` by default, or `crossbeam-channel` when the
+/// `crossbeam` feature is enabled.
+#[cfg(feature = "crossbeam")]
+pub type NotificationSender = crossbeam_channel::Sender<Packet>;
+
+/// Receiving half of the notification channel
+///
+/// With the `crossbeam` feature enabled, this receiver can be cloned to
+/// fan notifications out to multiple consumers and used with `select!`.
+#[cfg(feature = "crossbeam")]
+pub type NotificationReceiver = crossbeam_channel::Receiver<Packet>;
+
+/// Error returned by `NotificationReceiver::recv_timeout`
+#[cfg(feature = "crossbeam")]
+pub use crossbeam_channel::RecvTimeoutError;
+
+/// Predicate used to match a notification in `wait_for_notification`
+pub(crate) type NotificationPredicate = Box<dyn Fn(&Packet) -> bool + Send>;
+
+/// One-shot notification subscriptions
+///
+/// Callers blocked in `Dispatcher::wait_for_notification` register a
+/// predicate here. The RX thread offers every notification to the
+/// registered waiters; the first matching packet is cloned to the waiter,
+/// which is then removed.
+#[derive(Default)]
+pub(crate) struct NotificationWaiters {
+    next_id: u64,
+    waiters: Vec<(u64, NotificationPredicate, Sender<Packet>)>,
+}
+
+impl NotificationWaiters {
+    /// Register a waiter, returning its id and the receiver for the match
+    fn register(&mut self, pred: NotificationPredicate) -> (u64, Receiver<Packet>) {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let (tx, rx) = mpsc::channel();
+        self.waiters.push((id, pred, tx));
+        (id, rx)
+    }
+
+    /// Remove a waiter (after it timed out)
+    fn remove(&mut self, id: u64) {
+        self.waiters.retain(|(waiter_id, _, _)| *waiter_id != id);
+    }
+
+    /// Deliver a notification to every matching waiter and drop them
+    pub(crate) fn dispatch(&mut self, packet: &Packet) {
+        self.waiters.retain(|(_, pred, tx)| {
+            if pred(packet) {
+                // Receiver may already be gone if the waiter just timed out
+                let _ = tx.send(packet.clone());
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+/// A registered one-shot notification waiter
+///
+/// Created by `Dispatcher::subscribe_notification`. Subscribing before
+/// sending a command guarantees a notification triggered by that command
+/// cannot be missed. Dropping the subscription unregisters it.
+pub struct NotificationSubscription<'a> {
+    waiters: &'a Mutex<NotificationWaiters>,
+    id: u64,
+    rx: Receiver<Packet>,
+}
+
+impl<'a> NotificationSubscription<'a> {
+    /// Register a waiter for the first notification matching `pred`
+    pub(crate) fn new(
+        waiters: &'a Mutex<NotificationWaiters>,
+        pred: NotificationPredicate,
+    ) -> Self {
+        let (id, rx) = waiters.lock().unwrap().register(pred);
+        Self { waiters, id, rx }
+    }
+
+    /// Block until the matching notification arrives, or time out
+    ///
+    /// Returns immediately if it already arrived after subscribing.
+    pub fn wait(self, timeout: Duration) -> Result<Packet> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(packet) => Ok(packet),
+            Err(_) => {
+                self.waiters.lock().unwrap().remove(self.id);
+                // A match may have been delivered between the timeout and removal
+                self.rx.try_recv().map_err(|_| RvrError::Timeout)
+            }
+        }
+    }
+}
+
+impl Drop for NotificationSubscription<'_> {
+    fn drop(&mut self) {
+        // No-op if the waiter already matched or timed out
+        self.waiters.lock().unwrap().remove(self.id);
+    }
+}
+
+/// Block until a notification matching `pred` is dispatched, or time out
+fn wait_for_matching(
+    waiters: &Mutex<NotificationWaiters>,
+    pred: NotificationPredicate,
+    timeout: Duration,
+) -> Result<Packet> {
+    NotificationSubscription::new(waiters, pred).wait(timeout)
+}
+
+/// How long to wait for the OS to open the serial port
+const OPEN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A port ready for the dispatcher, split into read and write handles if possible
+struct OpenedPort {
+    /// Used for writes, and for reads too when `reader` is `None`
+    port: Box<dyn SerialTransport>,
+    /// Separate handle the RX thread reads from, so reads never hold up writes
+    reader: Option<Box<dyn SerialTransport>>,
+}
+
+impl OpenedPort {
+    /// Give the RX thread its own handle on `port` via `try_clone`
+    ///
+    /// Ports that cannot be cloned are shared between reads and writes.
+    fn split(port: Box<dyn SerialPort>) -> Self {
+        let reader = match port.try_clone() {
+            Ok(reader) => Some(Box::new(reader) as Box<dyn SerialTransport>),
+            Err(e) => {
+                tracing::debug!("Serial port cannot be cloned ({}); sharing it", e);
+                None
+            }
+        };
+        Self {
+            port: Box::new(port),
+            reader,
+        }
+    }
+
+    /// Use one handle for both reads and writes
+    fn shared(port: Box<dyn SerialTransport>) -> Self {
+        Self { port, reader: None }
+    }
+}
+
+/// Open the serial port with the settings the dispatcher uses
+///
+/// Fails fast with a clear message if the device path does not exist, and
+/// gives up if the open itself hangs for longer than `OPEN_TIMEOUT`.
+fn open_port(port_name: &str, baud_rate: u32) -> Result<OpenedPort> {
+    // Device paths are only checkable on Unix; Windows names (COM3) are not files
+    if cfg!(unix) && !std::path::Path::new(port_name).exists() {
+        return Err(RvrError::Serial(serialport::Error::new(
+            serialport::ErrorKind::NoDevice,
+            format!("serial port {} not found", port_name),
+        )));
+    }
+
+    // Open on a helper thread so a wedged driver cannot hang the caller;
+    // if it times out, the thread is left to finish and drop the port
+    let (tx, rx) = mpsc::channel();
+    let builder = serialport::new(port_name, baud_rate).timeout(Duration::from_millis(100));
+    thread::spawn(move || {
+        let _ = tx.send(builder.open());
+    });
+    match rx.recv_timeout(OPEN_TIMEOUT) {
+        Ok(port) => Ok(OpenedPort::split(port?)),
+        Err(_) => Err(RvrError::Serial(serialport::Error::new(
+            serialport::ErrorKind::Io(std::io::ErrorKind::TimedOut),
+            format!("timed out opening serial port {}", port_name),
+        ))),
+    }
+}
+
+/// Write a framed packet and flush it to the port
+///
+/// A write that times out means the transmit buffer is full rather than
+/// the port being gone, so it is reported as `RvrError::WriteStalled`
+/// instead of a generic I/O error.
+fn write_frame<W: std::io::Write + ?Sized>(port: &mut W, framed: &[u8]) -> Result<()> {
+    port.write_all(framed)
+        .and_then(|()| port.flush())
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::TimedOut => RvrError::WriteStalled,
+            _ => RvrError::Io(e),
+        })
+}
+
+/// Reopen the serial port after a failure
+///
+/// Makes up to `attempts` tries, backing off between them as
+/// `send_command_with_retries` does, and stops early on shutdown. The new
+/// port is handed to `install`. Leaves `connection` as `Connected` on
+/// success and `Disconnected` otherwise.
+fn reconnect<P>(
+    reopen: impl Fn() -> Result<P>,
+    install: impl FnOnce(P),
+    attempts: u32,
+    connection: &Mutex<ConnectionState>,
+    shutdown: &AtomicBool,
+) -> Result<()> {
+    *connection.lock().unwrap() = ConnectionState::Reconnecting;
+
+    let mut last_error = RvrError::Protocol("Reconnect gave up before trying".to_string());
+    for attempt in 0..attempts {
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+        if attempt > 0 {
+            thread::sleep(retry_delay(attempt - 1));
+        }
+
+        match reopen() {
+            Ok(port) => {
+                install(port);
+                *connection.lock().unwrap() = ConnectionState::Connected;
+                tracing::info!("Serial port reopened");
+                return Ok(());
+            }
+            Err(e) => {
+                tracing::warn!("Reopen attempt {}/{} failed: {}", attempt + 1, attempts, e);
+                last_error = e;
+            }
+        }
+    }
+
+    *connection.lock().unwrap() = ConnectionState::Disconnected;
+    Err(last_error)
+}
+
+/// Reopen function for ports the dispatcher did not open itself
+fn reopen_unsupported() -> Result<OpenedPort> {
+    Err(RvrError::Protocol(
+        "Cannot reopen a port supplied by the caller".to_string(),
+    ))
+}
+
+/// Error for a command that got no response
+///
+/// If the robot announced it was going to sleep (and has not answered
+/// anything since) the timeout is reported as `RvrError::RobotAsleep`, so
+/// callers know to wake it rather than suspect the link.
+fn timeout_error(robot_asleep: &AtomicBool) -> RvrError {
+    if robot_asleep.load(Ordering::SeqCst) {
+        RvrError::RobotAsleep
+    } else {
+        RvrError::Timeout
+    }
+}
+
+/// Pick the next sequence number that has no request pending
+///
+/// Advances the counter (wrapping at 255) past numbers still waiting for a
+/// response, so a late response can never be routed to the wrong caller.
+/// Fails with `TooManyInFlight` if all 256 numbers are pending.
+fn allocate_sequence(
+    next_sequence: &AtomicU8,
+    pending: &HashMap<u8, ResponseSender>,
+) -> Result<u8> {
+    for _ in 0..=u8::MAX as usize {
+        let seq = next_sequence.fetch_add(1, Ordering::SeqCst);
+        if !pending.contains_key(&seq) {
+            return Ok(seq);
+        }
+    }
+    Err(RvrError::TooManyInFlight)
+}
+
+/// Create an unbounded notification channel of the configured type
+pub(crate) fn notification_channel() -> (NotificationSender, NotificationReceiver) {
+    #[cfg(not(feature = "crossbeam"))]
+    {
+        mpsc::channel()
+    }
+    #[cfg(feature = "crossbeam")]
+    {
+        crossbeam_channel::unbounded()
+    }
+}
+
+/// How often a paused RX thread checks whether it has been resumed
+const RX_PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long `send_command` waits for a response unless configured otherwise
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delay before the first retry in `send_command_with_retries` (doubles each retry)
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+
+/// Times the dispatcher tries to reopen a failed serial port before giving up
+const RECONNECT_ATTEMPTS: u32 = 5;
+
+/// Whether a send outcome is transient and worth retrying
+///
+/// Only a missing response or a BUSY error code qualifies. Other error
+/// codes (e.g. BAD_COMMAND_ID) are definitive and returned as-is.
+fn is_retryable(result: &Result<Packet>) -> bool {
+    match result {
+        Ok(response) => response.payload.first() == Some(&error_code::BUSY),
+        Err(RvrError::Timeout | RvrError::RobotAsleep) => true,
+        Err(_) => false,
+    }
+}
+
+/// Backoff before retry number `retry` (0-based): 50 ms, 100 ms, 200 ms, ...
+fn retry_delay(retry: u32) -> Duration {
+    RETRY_BASE_DELAY.saturating_mul(1u32.checked_shl(retry).unwrap_or(u32::MAX))
+}
+
+/// Device ids whose per-packet TX/RX trace lines are suppressed
+///
+/// Set via `Dispatcher::set_command_log_filter`. Lets high-rate traffic such
+/// as drive commands be silenced while other devices keep logging.
+#[derive(Default)]
+struct CommandLogFilter {
+    silenced: Mutex<HashSet<u8>>,
+}
+
+impl CommandLogFilter {
+    /// Enable or disable logging for a device id
+    fn set(&self, device_id: u8, enabled: bool) {
+        let mut silenced = self.silenced.lock().unwrap();
+        if enabled {
+            silenced.remove(&device_id);
+        } else {
+            silenced.insert(device_id);
+        }
+    }
+
+    /// Whether packets for this device id should be logged
+    fn enabled(&self, device_id: u8) -> bool {
+        !self.silenced.lock().unwrap().contains(&device_id)
+    }
+}
+
+/// Spaces outgoing packets to at most a fixed rate
+///
+/// Tracks the earliest time the next packet may go out. Callers that arrive
+/// earlier are told how long to wait; callers that arrive later go
+/// immediately (no burst credit is accumulated while idle).
+#[derive(Debug, Default)]
+struct RateLimiter {
+    /// Minimum spacing between packets (None = unlimited)
+    min_interval: Option<Duration>,
+    /// Earliest time the next packet may be sent
+    next_slot: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Limit to `max_per_sec` packets per second (0 = unlimited)
+    fn set_rate(&mut self, max_per_sec: u32) {
+        self.min_interval = (max_per_sec > 0).then(|| Duration::from_secs(1) / max_per_sec);
+        self.next_slot = None;
+    }
+
+    /// Reserve a send slot, returning how long to wait before sending
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let Some(interval) = self.min_interval else {
+            return Duration::ZERO;
+        };
+
+        let slot = self.next_slot.map_or(now, |next| next.max(now));
+        self.next_slot = Some(slot + interval);
+        slot - now
+    }
+}
+
+/// Receiver for errors encountered by the RX thread
+pub type ErrorReceiver = Receiver<RvrError>;
+
+/// Which way a packet crossed the link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent to the robot
+    Tx,
+    /// Received from the robot
+    Rx,
+}
+
+/// Observer for every packet the dispatcher sends or receives
+///
+/// Called on the sending thread for `Direction::Tx` and on the RX thread
+/// for `Direction::Rx`, so it should return quickly.
+pub type PacketTap = Box<dyn Fn(Direction, &Packet) + Send + Sync>;
+
+/// How a command's response is matched back to the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResponseMatch {
+    /// Match on the sequence number (default)
+    #[default]
+    Sequence,
+    /// Match on device and command ID, ignoring the sequence number
+    ///
+    /// For firmware that does not echo the sequence number faithfully.
+    /// Only one request per device/command pair may be outstanding in
+    /// this mode, and it also claims a response meant for a
+    /// sequence-matched request with the same pair.
+    Command,
+}
+
+/// State of the serial link, as seen by the dispatcher
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The port is open and working
+    Connected,
+    /// The port failed and is being reopened
+    Reconnecting,
+    /// Reopening failed; the next failed write triggers another attempt
+    Disconnected,
+}
+
+/// What the RX thread does when a received frame fails its checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumFailurePolicy {
+    /// Count the failure and carry on; failures are not sent to the error channel
+    LogAndContinue,
+    /// Reopen the serial port after this many consecutive failures
+    ReconnectAfter(u32),
+    /// Send every failure to the error channel, if taken (default)
+    #[default]
+    ErrorChannel,
+}
+
+/// Action the RX thread takes for one checksum failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChecksumAction {
+    /// Count only
+    Ignore,
+    /// Forward to the error channel
+    Report,
+    /// Reopen the serial port
+    Reconnect,
+}
+
+/// Checksum failure counters and the policy applied to them
+#[derive(Debug, Default)]
+struct ChecksumMonitor {
+    policy: ChecksumFailurePolicy,
+    /// Failures since the last good packet
+    consecutive: u32,
+    /// Failures since the dispatcher was created
+    total: u64,
+}
+
+impl ChecksumMonitor {
+    /// Record a failed frame and decide what to do about it
+    fn on_failure(&mut self) -> ChecksumAction {
+        self.consecutive += 1;
+        self.total += 1;
+
+        match self.policy {
+            ChecksumFailurePolicy::LogAndContinue => ChecksumAction::Ignore,
+            ChecksumFailurePolicy::ErrorChannel => ChecksumAction::Report,
+            ChecksumFailurePolicy::ReconnectAfter(threshold) => {
+                if self.consecutive >= threshold.max(1) {
+                    self.consecutive = 0;
+                    ChecksumAction::Reconnect
+                } else {
+                    ChecksumAction::Ignore
+                }
+            }
+        }
+    }
+
+    /// Record a good packet, ending any run of failures
+    fn on_success(&mut self) {
+        self.consecutive = 0;
+    }
+}
+
+/// Destinations the RX thread routes incoming data to
+///
+/// Bundles the shared state the RX thread needs so the routing logic can be
+/// exercised without a serial port.
+struct RxRoutes {
+    pending_requests: Arc<Mutex<HashMap<u8, ResponseSender>>>,
+    pending_by_command: Arc<Mutex<PendingByCommand>>,
+    notification_waiters: Arc<Mutex<NotificationWaiters>>,
+    notification_tx: NotificationSender,
+    /// Only set once the application has taken the error receiver
+    error_tx: Arc<Mutex<Option<Sender<RvrError>>>>,
+    log_filter: Arc<CommandLogFilter>,
+    checksum: Arc<Mutex<ChecksumMonitor>>,
+    /// Set when a fatal I/O error or the checksum policy asks the RX thread
+    /// to reopen the port
+    reconnect_requested: Arc<AtomicBool>,
+    /// Current link state, updated by the RX thread while reconnecting
+    connection: Arc<Mutex<ConnectionState>>,
+    /// Set by a did-sleep notification, cleared by the next response
+    robot_asleep: Arc<AtomicBool>,
+    /// Observer for received packets (see Dispatcher::set_tap)
+    tap: Arc<RwLock<Option<PacketTap>>>,
+}
+
+impl RxRoutes {
+    /// Feed a chunk of raw bytes through the parser and route the results
+    fn process_bytes(&self, parser: &mut SpheroParser, bytes: &[u8]) {
+        for &byte in bytes {
+            match parser.feed(byte) {
+                Ok(Some(packet)) => {
+                    self.checksum.lock().unwrap().on_success();
+                    self.route_packet(packet);
+                }
+                Ok(None) => {
+                    // Still accumulating bytes
+                }
+                Err(e @ RvrError::Checksum { .. }) => {
+                    tracing::debug!("Corrupt frame: {:02X?}", parser.last_error_frame());
+                    self.handle_checksum_failure(e)
+                }
+                Err(e) => {
+                    // Parser error (invalid escape, resync, etc.)
+                    // This is expected on noisy lines - log, report and continue
+                    tracing::warn!("Parser error: {}", e);
+                    self.report_error(e);
+                }
+            }
+        }
+    }
+
+    /// Route a complete packet to a pending request or the notification channel
+    fn route_packet(&self, packet: Packet) {
+        if let Some(tap) = self.tap.read().unwrap().as_ref() {
+            tap(Direction::Rx, &packet);
+        }
+
+        if self.log_filter.enabled(packet.device_id) {
+            tracing::trace!("RX: {}", packet);
+        }
+
+        // Track sleep state so timeouts can be explained
+        if packet.is_notification()
+            && packet.device_id == device::POWER
+            && packet.command_id == power_command::DID_SLEEP_NOTIFY
+        {
+            tracing::info!("Robot went to sleep");
+            self.robot_asleep.store(true, Ordering::SeqCst);
+        } else if !packet.is_notification() {
+            // Anything answering a command is awake
+            self.robot_asleep.store(false, Ordering::SeqCst);
+        }
+
+        // Route packet based on type
+        if packet.is_notification() {
+            // This is an async notification (sensor data, event)
+            // Offer it to any blocked waiters first; they get a copy
+            self.notification_waiters.lock().unwrap().dispatch(&packet);
+            if self.notification_tx.send(packet).is_err() {
+                tracing::warn!("Notification channel closed");
+            }
+        } else {
+            // This is a response to a command - route to pending request,
+            // preferring one that matches on device/command
+            let key = (packet.device_id, packet.command_id);
+            if let Some(sender) = self.pending_by_command.lock().unwrap().remove(&key) {
+                if sender.send(packet).is_err() {
+                    tracing::warn!(
+                        "Failed to send response for dev={:#04x} cmd={:#04x}",
+                        key.0,
+                        key.1
+                    );
+                }
+                return;
+            }
+
+            let seq = packet.sequence_number;
+            let mut pending = self.pending_requests.lock().unwrap();
+            if let Some(sender) = pending.remove(&seq) {
+                if sender.send(packet).is_err() {
+                    tracing::warn!("Failed to send response for seq={}", seq);
+                }
+            } else {
+                tracing::warn!("Received response for unknown sequence: {}", seq);
+            }
+        }
+    }
+
+    /// Apply the checksum failure policy to a failed frame
+    fn handle_checksum_failure(&self, error: RvrError) {
+        let action = self.checksum.lock().unwrap().on_failure();
+        match action {
+            ChecksumAction::Ignore => tracing::debug!("Parser error: {}", error),
+            ChecksumAction::Report => {
+                tracing::warn!("Parser error: {}", error);
+                self.report_error(error);
+            }
+            ChecksumAction::Reconnect => {
+                tracing::warn!("Parser error: {}; too many checksum failures", error);
+                self.reconnect_requested.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Forward an error to the application, if it asked for them
+    fn report_error(&self, error: RvrError) {
+        if let Some(tx) = self.error_tx.lock().unwrap().as_ref() {
+            // The application may have dropped the receiver; that's fine
+            let _ = tx.send(error);
+        }
+    }
+}
+
 /// Dispatcher manages serial communication and routes messages
 ///
 /// Architecture:
@@ -25,6 +656,7 @@ type ResponseSender = Sender<Packet>;
 ///   - Feeds to SpheroParser
 ///   - Routes responses to pending request channels
 ///   - Routes async notifications to notification channel
+///   - Forwards parser errors to the error channel, if taken
 ///
 /// # Thread Safety
 ///
@@ -33,9 +665,18 @@ type ResponseSender = Sender<Packet>;
 /// - Sequence counter uses AtomicU8
 /// - Pending requests map is protected by Mutex
 /// - RX thread owns the read half of the serial port
+///
+/// Ports opened by `new`, or passed to `from_port`, are split with
+/// `SerialPort::try_clone` so the RX thread's read (which waits up to the
+/// 100 ms read timeout) never holds up a write. On Unix the clone is a
+/// `dup` of the file descriptor and on Windows a duplicated handle; both
+/// share the OS port, so settings changed through one apply to the other.
+/// Some drivers and in-memory ports cannot be cloned; those, and every
+/// `from_transport` transport, are shared between reads and writes
+/// through the mutex, as before.
 pub struct Dispatcher {
-    /// Shared serial port (for writing)
-    serial_port: Arc<Mutex<Box<dyn SerialPort>>>,
+    /// Shared serial port (for writing, and reading if it could not be split)
+    serial_port: Arc<Mutex<Box<dyn SerialTransport>>>,
 
     /// Sequence number counter (wraps at 255)
     next_sequence: AtomicU8,
@@ -44,16 +685,55 @@ pub struct Dispatcher {
     /// Maps sequence_number -> oneshot sender
     pending_requests: Arc<Mutex<HashMap<u8, ResponseSender>>>,
 
+    /// Requests matched on device/command instead of sequence number
+    pending_by_command: Arc<Mutex<PendingByCommand>>,
+
     /// Channel for async notifications (sensor data, events)
-    notification_tx: Sender<Packet>,
+    notification_tx: NotificationSender,
 
     /// Receiver for async notifications (exposed to API layer via take_receiver)
     /// Wrapped in Option to allow transfer of ownership
-    notification_rx: Mutex<Option<Receiver<Packet>>>,
+    notification_rx: Mutex<Option<NotificationReceiver>>,
+
+    /// Callers blocked waiting for a specific notification
+    notification_waiters: Arc<Mutex<NotificationWaiters>>,
+
+    /// Sender for RX errors, installed by take_error_receiver
+    error_tx: Arc<Mutex<Option<Sender<RvrError>>>>,
+
+    /// Devices whose TX/RX trace lines are suppressed
+    log_filter: Arc<CommandLogFilter>,
+
+    /// Whether the robot reported going to sleep (shared with the RX thread)
+    robot_asleep: Arc<AtomicBool>,
+
+    /// Checksum failure policy and counters, shared with the RX thread
+    checksum: Arc<Mutex<ChecksumMonitor>>,
+
+    /// How long send_command waits for a response (see with_timeout)
+    command_timeout: Duration,
+
+    /// Asks the RX thread to reopen the port (shared with the RX thread)
+    reconnect_requested: Arc<AtomicBool>,
+
+    /// Current link state (see state)
+    connection: Arc<Mutex<ConnectionState>>,
 
     /// RX thread handle
     rx_thread: Mutex<Option<JoinHandle<()>>>,
 
+    /// Outgoing packet rate limit (see set_command_rate_limit)
+    rate_limiter: Mutex<RateLimiter>,
+
+    /// Optional recorder for every packet sent
+    recorder: Mutex<Option<CommandRecorder>>,
+
+    /// Optional observer for every packet sent or received
+    tap: Arc<RwLock<Option<PacketTap>>>,
+
+    /// Pause flag for RX thread (see pause_rx)
+    rx_paused: Arc<AtomicBool>,
+
     /// Shutdown flag for RX thread
     shutdown: Arc<AtomicBool>,
 }
@@ -71,37 +751,120 @@ impl Dispatcher {
     /// Returns `Dispatcher` instance with RX thread running
     pub fn new(port_name: &str, baud_rate: u32) -> Result<Self> {
         // Open serial port
-        let port = serialport::new(port_name, baud_rate)
-            .timeout(Duration::from_millis(100))
-            .open()?;
+        let port = open_port(port_name, baud_rate)?;
+
+        let reopen = {
+            let port_name = port_name.to_string();
+            move || open_port(&port_name, baud_rate)
+        };
+        Ok(Self::start(port, reopen))
+    }
+
+    /// Create a Dispatcher on a port the caller has already opened
+    ///
+    /// Useful for ports that need settings `new` does not expose, and for
+    /// in-memory ports in tests. The port should use a short read timeout
+    /// (around 100 ms) so the RX thread can notice shutdown.
+    ///
+    /// The dispatcher cannot reopen a port it did not open, so a fatal I/O
+    /// error or `ChecksumFailurePolicy::ReconnectAfter` leaves it
+    /// `ConnectionState::Disconnected` and reports an error.
+    pub fn from_port(port: Box<dyn SerialPort>) -> Self {
+        Self::start(OpenedPort::split(port), reopen_unsupported)
+    }
+
+    /// Create a Dispatcher over any byte transport
+    ///
+    /// Like `from_port`, but accepts anything implementing `SerialTransport`
+    /// (see its read timeout requirement), such as `MockTransport` in tests.
+    pub fn from_transport(transport: impl SerialTransport + 'static) -> Self {
+        Self::start(OpenedPort::shared(Box::new(transport)), reopen_unsupported)
+    }
 
+    /// Start the RX thread on `port` and build the dispatcher around it
+    fn start(
+        port: OpenedPort,
+        rx_reopen: impl Fn() -> Result<OpenedPort> + Send + 'static,
+    ) -> Self {
+        let OpenedPort { port, reader } = port;
         let serial_port = Arc::new(Mutex::new(port));
         let pending_requests = Arc::new(Mutex::new(HashMap::new()));
+        let pending_by_command = Arc::new(Mutex::new(HashMap::new()));
+        let notification_waiters = Arc::new(Mutex::new(NotificationWaiters::default()));
+        let error_tx = Arc::new(Mutex::new(None));
+        let log_filter = Arc::new(CommandLogFilter::default());
+        let checksum = Arc::new(Mutex::new(ChecksumMonitor::default()));
+        let robot_asleep = Arc::new(AtomicBool::new(false));
+        let tap = Arc::new(RwLock::new(None));
+        let reconnect_requested = Arc::new(AtomicBool::new(false));
+        let connection = Arc::new(Mutex::new(ConnectionState::Connected));
+        let rx_paused = Arc::new(AtomicBool::new(false));
         let shutdown = Arc::new(AtomicBool::new(false));
 
         // Create notification channel
-        let (notification_tx, notification_rx) = mpsc::channel();
+        let (notification_tx, notification_rx) = notification_channel();
 
         // Clone serial port for RX thread
         let rx_serial = Arc::clone(&serial_port);
-        let rx_pending = Arc::clone(&pending_requests);
+        let rx_routes = RxRoutes {
+            pending_requests: Arc::clone(&pending_requests),
+            pending_by_command: Arc::clone(&pending_by_command),
+            notification_waiters: Arc::clone(&notification_waiters),
+            notification_tx: notification_tx.clone(),
+            error_tx: Arc::clone(&error_tx),
+            log_filter: Arc::clone(&log_filter),
+            checksum: Arc::clone(&checksum),
+            reconnect_requested: Arc::clone(&reconnect_requested),
+            connection: Arc::clone(&connection),
+            robot_asleep: Arc::clone(&robot_asleep),
+            tap: Arc::clone(&tap),
+        };
+        let rx_pause = Arc::clone(&rx_paused);
         let rx_shutdown = Arc::clone(&shutdown);
-        let rx_notif_tx = notification_tx.clone();
 
         // Spawn RX thread
         let rx_thread = thread::spawn(move || {
-            Self::rx_thread_loop(rx_serial, rx_pending, rx_notif_tx, rx_shutdown);
+            Self::rx_thread_loop(
+                rx_serial,
+                reader,
+                rx_routes,
+                rx_reopen,
+                rx_pause,
+                rx_shutdown,
+            );
         });
 
-        Ok(Self {
+        Self {
             serial_port,
             next_sequence: AtomicU8::new(0),
             pending_requests,
+            pending_by_command,
             notification_tx,
             notification_rx: Mutex::new(Some(notification_rx)),
+            notification_waiters,
+            error_tx,
+            log_filter,
+            robot_asleep,
+            checksum,
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            reconnect_requested,
+            connection,
             rx_thread: Mutex::new(Some(rx_thread)),
+            rate_limiter: Mutex::new(RateLimiter::default()),
+            recorder: Mutex::new(None),
+            tap,
+            rx_paused,
             shutdown,
-        })
+        }
+    }
+
+    /// Set how long `send_command` waits for a response
+    ///
+    /// Defaults to `DEFAULT_COMMAND_TIMEOUT` (2 seconds). Individual calls
+    /// can override it with `send_command_timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = timeout;
+        self
     }
 
     /// Send a command packet and wait for response
@@ -119,31 +882,124 @@ impl Dispatcher {
     /// # Returns
     ///
     /// Returns the response packet or timeout error
-    pub fn send_command(&self, mut packet: Packet) -> Result<Packet> {
-        // Assign sequence number
-        let seq = self.next_sequence.fetch_add(1, Ordering::SeqCst);
-        packet.sequence_number = seq;
+    pub fn send_command(&self, packet: Packet) -> Result<Packet> {
+        self.send_command_matching(packet, ResponseMatch::Sequence)
+    }
+
+    /// Send a command packet, retrying transient failures
+    ///
+    /// Re-sends (with a fresh sequence number) up to `max_retries` times
+    /// when no response arrives in time or the robot answers BUSY, waiting
+    /// 50 ms before the first retry and doubling the wait each time.
+    /// Definitive error codes are not retried.
+    ///
+    /// # Returns
+    ///
+    /// Returns the last response received (which may still carry an error
+    /// code) or the last error
+    pub fn send_command_with_retries(&self, packet: Packet, max_retries: u32) -> Result<Packet> {
+        let mut result = self.send_command(packet.clone());
+        for retry in 0..max_retries {
+            if !is_retryable(&result) {
+                break;
+            }
+            let delay = retry_delay(retry);
+            tracing::debug!(
+                "Retrying dev={:#04x} cmd={:#04x} in {:?} (retry {}/{})",
+                packet.device_id,
+                packet.command_id,
+                delay,
+                retry + 1,
+                max_retries
+            );
+            thread::sleep(delay);
+            result = self.send_command(packet.clone());
+        }
+        result
+    }
+
+    /// Send a command packet and wait up to `timeout` for its response
+    ///
+    /// Like `send_command`, but overrides the dispatcher's command timeout
+    /// for this call only.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` without sending anything if
+    /// `timeout` is zero.
+    pub fn send_command_timeout(&self, packet: Packet, timeout: Duration) -> Result<Packet> {
+        self.send_and_wait(packet, ResponseMatch::Sequence, timeout)
+    }
+
+    /// Send a command packet and wait for a response matched by `matching`
+    ///
+    /// Like `send_command`, but with `ResponseMatch::Command` the response
+    /// is accepted whatever sequence number it carries. Use this only for
+    /// commands whose firmware mangles the sequence number, and keep at
+    /// most one such command per device/command pair in flight.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RvrError::InvalidParameter` if a `ResponseMatch::Command`
+    /// request for the same device/command pair is already outstanding.
+    pub fn send_command_matching(&self, packet: Packet, matching: ResponseMatch) -> Result<Packet> {
+        self.send_and_wait(packet, matching, self.command_timeout)
+    }
+
+    /// Send a command and wait up to `timeout` for the response `matching` it
+    fn send_and_wait(
+        &self,
+        mut packet: Packet,
+        matching: ResponseMatch,
+        timeout: Duration,
+    ) -> Result<Packet> {
+        // A zero timeout could never see a response; fail before sending
+        if timeout.is_zero() {
+            return Err(RvrError::InvalidParameter(
+                "command timeout must be greater than zero".to_string(),
+            ));
+        }
 
         // Create response channel
         let (tx, rx) = mpsc::channel();
+        let key = (packet.device_id, packet.command_id);
 
-        // Register pending request
-        {
+        // Assign a free sequence number and register the pending request
+        // (under one lock, so no other caller can claim the same number)
+        let seq = {
             let mut pending = self.pending_requests.lock().unwrap();
-            pending.insert(seq, tx);
-        }
+            let seq = allocate_sequence(&self.next_sequence, &pending)?;
+            match matching {
+                ResponseMatch::Sequence => {
+                    pending.insert(seq, tx);
+                }
+                ResponseMatch::Command => {
+                    let mut by_command = self.pending_by_command.lock().unwrap();
+                    if by_command.contains_key(&key) {
+                        return Err(RvrError::InvalidParameter(format!(
+                            "a command-matched request for dev={:#04x} cmd={:#04x} is already in flight",
+                            key.0, key.1
+                        )));
+                    }
+                    by_command.insert(key, tx);
+                }
+            }
+            seq
+        };
+        packet.sequence_number = seq;
 
-        // Send packet
-        self.send_packet_internal(&packet)?;
+        // Send packet; if it never left, no response will come to clean up
+        if let Err(e) = self.send_packet_internal(&packet) {
+            self.forget_request(matching, seq, key);
+            return Err(e);
+        }
 
         // Wait for response (with timeout)
-        match rx.recv_timeout(Duration::from_secs(2)) {
+        match rx.recv_timeout(timeout) {
             Ok(response) => Ok(response),
             Err(mpsc::RecvTimeoutError::Timeout) => {
-                // Clean up pending request
-                let mut pending = self.pending_requests.lock().unwrap();
-                pending.remove(&seq);
-                Err(RvrError::Timeout)
+                self.forget_request(matching, seq, key);
+                Err(timeout_error(&self.robot_asleep))
             }
             Err(mpsc::RecvTimeoutError::Disconnected) => Err(RvrError::Protocol(
                 "Response channel disconnected".to_string(),
@@ -151,6 +1007,18 @@ impl Dispatcher {
         }
     }
 
+    /// Remove a pending request that will not be answered
+    fn forget_request(&self, matching: ResponseMatch, seq: u8, key: (u8, u8)) {
+        match matching {
+            ResponseMatch::Sequence => {
+                self.pending_requests.lock().unwrap().remove(&seq);
+            }
+            ResponseMatch::Command => {
+                self.pending_by_command.lock().unwrap().remove(&key);
+            }
+        }
+    }
+
     /// Send a packet without waiting for response
     ///
     /// Useful for packets that don't expect a response
@@ -162,47 +1030,61 @@ impl Dispatcher {
     ///
     /// Serializes packet, applies SLIP encoding, adds framing, and writes to serial port
     fn send_packet_internal(&self, packet: &Packet) -> Result<()> {
-        // Serialize packet to unescaped bytes
-        let unescaped = packet.to_bytes();
-
-        // Apply SLIP encoding
-        let escaped = encode_bytes(&unescaped);
+        // Wait for our slot under the rate limit (lock released before sleeping)
+        let delay = self.rate_limiter.lock().unwrap().reserve(Instant::now());
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
 
         // Build framed packet: SOP + escaped + EOP
-        let mut framed = Vec::with_capacity(escaped.len() + 2);
-        framed.push(SOP);
-        framed.extend_from_slice(&escaped);
-        framed.push(EOP);
-
-        // Write to serial port
-        let mut port = self.serial_port.lock().unwrap();
-        port.write_all(&framed)?;
-        port.flush()?;
-
-        tracing::trace!(
-            "TX: seq={} dev={:#04x} cmd={:#04x} len={}",
-            packet.sequence_number,
-            packet.device_id,
-            packet.command_id,
-            framed.len()
-        );
+        let framed = packet.to_framed_bytes();
 
-        Ok(())
-    }
+        // Write to serial port; a hard I/O failure means the port is gone
+        let written = write_frame(&mut *self.serial_port.lock().unwrap(), &framed);
+        if let Err(RvrError::Io(e)) = &written {
+            tracing::error!("Serial write error: {}", e);
+            self.reconnect_requested.store(true, Ordering::SeqCst);
+        }
+        written?;
 
-    /// Background RX thread loop
-    ///
-    /// Continuously reads bytes from serial port, parses packets, and routes them
-    ///
+        if let Some(tap) = self.tap.read().unwrap().as_ref() {
+            tap(Direction::Tx, packet);
+        }
+
+        if let Some(recorder) = self.recorder.lock().unwrap().as_mut() {
+            // A failing recording must not break the robot connection
+            if let Err(e) = recorder.record(packet) {
+                tracing::warn!("Failed to record packet: {}", e);
+            }
+        }
+
+        if self.log_filter.enabled(packet.device_id) {
+            tracing::trace!("TX: {} framed={}B", packet, framed.len());
+        }
+
+        Ok(())
+    }
+
+    /// Background RX thread loop
+    ///
+    /// Continuously reads bytes from serial port, parses packets, and routes them
+    ///
     /// Performance: Reads chunks of 1024 bytes at a time to minimize syscalls
     /// and mutex contention. At 115200 baud, bytes arrive ~every 86μs, so
     /// single-byte reads would cause severe CPU thrashing.
+    ///
+    /// Reads from `reader` when the port was split, and from the shared
+    /// `serial_port` otherwise.
     fn rx_thread_loop(
-        serial_port: Arc<Mutex<Box<dyn SerialPort>>>,
-        pending_requests: Arc<Mutex<HashMap<u8, ResponseSender>>>,
-        notification_tx: Sender<Packet>,
+        serial_port: Arc<Mutex<Box<dyn SerialTransport>>>,
+        reader: Option<Box<dyn SerialTransport>>,
+        routes: RxRoutes,
+        reopen: impl Fn() -> Result<OpenedPort>,
+        paused: Arc<AtomicBool>,
         shutdown: Arc<AtomicBool>,
     ) {
+        // Only this thread uses the reader, so its lock is never contended
+        let mut reader = reader.map(Mutex::new);
         let mut parser = SpheroParser::new();
         let mut buffer = [0u8; 1024]; // Read chunks to minimize syscalls
 
@@ -215,63 +1097,33 @@ impl Dispatcher {
                 break;
             }
 
-            // Read chunk from serial port (single syscall + mutex lock)
-            let bytes_read = {
-                let mut port = serial_port.lock().unwrap();
-                match port.read(&mut buffer) {
-                    Ok(0) => continue, // No data available
-                    Ok(n) => n,
-                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                        // Timeout is expected with non-blocking reads
-                        continue;
-                    }
-                    Err(e) => {
-                        tracing::error!("Serial read error: {}", e);
-                        continue;
-                    }
-                }
-            };
-
-            // Feed chunk to parser (no mutex held here)
-            for &byte in &buffer[..bytes_read] {
-                match parser.feed(byte) {
-                    Ok(Some(packet)) => {
-                        tracing::trace!(
-                            "RX: seq={} dev={:#04x} cmd={:#04x} is_resp={} payload_len={}",
-                            packet.sequence_number,
-                            packet.device_id,
-                            packet.command_id,
-                            packet.flags.is_response,
-                            packet.payload.len()
-                        );
-
-                        // Route packet based on type
-                        if packet.flags.is_response {
-                            // This is a response to a command - route to pending request
-                            let seq = packet.sequence_number;
-                            let mut pending = pending_requests.lock().unwrap();
-                            if let Some(sender) = pending.remove(&seq) {
-                                if sender.send(packet).is_err() {
-                                    tracing::warn!("Failed to send response for seq={}", seq);
-                                }
-                            } else {
-                                tracing::warn!("Received response for unknown sequence: {}", seq);
-                            }
-                        } else {
-                            // This is an async notification (sensor data, event)
-                            if notification_tx.send(packet).is_err() {
-                                tracing::warn!("Notification channel closed");
-                            }
-                        }
-                    }
-                    Ok(None) => {
-                        // Still accumulating bytes
-                    }
-                    Err(e) => {
-                        // Parser error (bad checksum, resync, etc.)
-                        // This is expected on noisy lines - just log and continue
-                        tracing::warn!("Parser error: {}", e);
-                    }
+            // A port that could not be reopened is not read, to avoid an
+            // endless stream of read errors
+            if *routes.connection.lock().unwrap() == ConnectionState::Disconnected {
+                thread::sleep(RX_PAUSE_POLL_INTERVAL);
+            } else {
+                let port = reader.as_ref().unwrap_or(&serial_port);
+                Self::rx_poll(port, &mut parser, &mut buffer, &routes, &paused);
+            }
+
+            if routes.reconnect_requested.swap(false, Ordering::SeqCst) {
+                tracing::warn!("Reopening serial port");
+                // Any partial frame belonged to the old port
+                parser.reset();
+                let install = |opened: OpenedPort| {
+                    *serial_port.lock().unwrap() = opened.port;
+                    reader = opened.reader.map(Mutex::new);
+                };
+                let result = reconnect(
+                    &reopen,
+                    install,
+                    RECONNECT_ATTEMPTS,
+                    &routes.connection,
+                    &shutdown,
+                );
+                if let Err(e) = result {
+                    tracing::error!("Failed to reopen serial port: {}", e);
+                    routes.report_error(e);
                 }
             }
         }
@@ -279,6 +1131,117 @@ impl Dispatcher {
         tracing::debug!("RX thread exited");
     }
 
+    /// Perform one read-parse-route step of the RX loop
+    ///
+    /// While `paused` is set, sleeps briefly instead of touching the port.
+    /// Returns the number of bytes read and processed.
+    fn rx_poll<R: Read>(
+        serial_port: &Mutex<R>,
+        parser: &mut SpheroParser,
+        buffer: &mut [u8],
+        routes: &RxRoutes,
+        paused: &AtomicBool,
+    ) -> usize {
+        if paused.load(Ordering::Relaxed) {
+            thread::sleep(RX_PAUSE_POLL_INTERVAL);
+            return 0;
+        }
+
+        // Read chunk from serial port (single syscall + mutex lock)
+        let bytes_read = {
+            let mut port = serial_port.lock().unwrap();
+            match port.read(buffer) {
+                Ok(n) => n, // 0 = no data available
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                    // Timeout is expected with non-blocking reads
+                    return 0;
+                }
+                Err(e) => {
+                    // Anything but a timeout means the port itself failed
+                    tracing::error!("Serial read error: {}", e);
+                    routes.reconnect_requested.store(true, Ordering::SeqCst);
+                    routes.report_error(RvrError::Io(e));
+                    return 0;
+                }
+            }
+        };
+
+        // Feed chunk to parser (no mutex held here)
+        routes.process_bytes(parser, &buffer[..bytes_read]);
+        bytes_read
+    }
+
+    /// Pause the RX thread without tearing it down
+    ///
+    /// The RX thread stops reading from the serial port (and stops taking
+    /// the port lock) until `resume_rx` is called. Incoming bytes stay
+    /// buffered by the OS and are parsed after resuming, as long as the
+    /// buffer does not overflow.
+    ///
+    /// While paused no responses are routed, so `send_command` will time
+    /// out. Use `send_packet_no_response` for commands sent while paused.
+    pub fn pause_rx(&self) {
+        tracing::debug!("Pausing RX thread");
+        self.rx_paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resume an RX thread paused with `pause_rx`
+    pub fn resume_rx(&self) {
+        tracing::debug!("Resuming RX thread");
+        self.rx_paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns true if the RX thread is paused
+    pub fn is_rx_paused(&self) -> bool {
+        self.rx_paused.load(Ordering::SeqCst)
+    }
+
+    /// Block until a notification matching `pred` arrives
+    ///
+    /// Temporarily subscribes to incoming notifications and returns the first
+    /// one for which `pred` returns true. Notifications are still delivered
+    /// to the regular notification receiver as well; this only receives a copy.
+    ///
+    /// Only notifications that arrive after this call are considered.
+    ///
+    /// # Arguments
+    ///
+    /// * `pred` - Predicate selecting the notification to wait for
+    /// * `timeout` - Maximum time to wait
+    ///
+    /// # Returns
+    ///
+    /// Returns the matching packet, or `RvrError::Timeout` if none arrived in time
+    pub fn wait_for_notification(
+        &self,
+        pred: impl Fn(&Packet) -> bool + Send + 'static,
+        timeout: Duration,
+    ) -> Result<Packet> {
+        wait_for_matching(&self.notification_waiters, Box::new(pred), timeout)
+    }
+
+    /// Subscribe to the next notification matching `pred`, without blocking
+    ///
+    /// Like `wait_for_notification`, but split in two so a command can be
+    /// sent between subscribing and waiting:
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::transport::Dispatcher;
+    /// # use sphero_rvr::protocol::Packet;
+    /// # use std::time::Duration;
+    /// # let dispatcher = Dispatcher::new("/dev/serial0", 115200).unwrap();
+    /// # let command = Packet::new_command(0x18, 0x25, 0, vec![]);
+    /// let done = dispatcher.subscribe_notification(|p| p.device_id == 0x18 && p.command_id == 0x26);
+    /// dispatcher.send_command(command).unwrap();
+    /// let notification = done.wait(Duration::from_secs(10)).unwrap();
+    /// ```
+    pub fn subscribe_notification(
+        &self,
+        pred: impl Fn(&Packet) -> bool + Send + 'static,
+    ) -> NotificationSubscription<'_> {
+        NotificationSubscription::new(&self.notification_waiters, Box::new(pred))
+    }
+
     /// Take ownership of the notification receiver
     ///
     /// This receiver gets async notifications like sensor data and events
@@ -301,10 +1264,133 @@ impl Dispatcher {
     ///     });
     /// }
     /// ```
-    pub fn take_receiver(&self) -> Option<Receiver<Packet>> {
+    pub fn take_receiver(&self) -> Option<NotificationReceiver> {
         self.notification_rx.lock().unwrap().take()
     }
 
+    /// Limit outgoing packets to at most `max_per_sec` per second
+    ///
+    /// Sending faster than the robot can process (e.g. a tight drive loop)
+    /// leads to BUSY errors and dropped packets. With a limit set, sends
+    /// that come too soon after the previous one sleep until their slot.
+    /// Pass 0 to remove the limit.
+    pub fn set_command_rate_limit(&self, max_per_sec: u32) {
+        self.rate_limiter.lock().unwrap().set_rate(max_per_sec);
+    }
+
+    /// Attach or detach a recorder for every packet sent
+    ///
+    /// Returns the previously attached recorder, if any. See the
+    /// `transport::recorder` module for the recording format.
+    pub fn set_recorder(&self, recorder: Option<CommandRecorder>) -> Option<CommandRecorder> {
+        std::mem::replace(&mut *self.recorder.lock().unwrap(), recorder)
+    }
+
+    /// Attach or detach an observer for every packet sent and received
+    ///
+    /// Sent packets are reported once written to the port, received ones
+    /// as soon as they are parsed, before routing. Returns the previously
+    /// attached tap, if any. The tap must not call `set_tap` itself.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sphero_rvr::transport::{Direction, Dispatcher};
+    ///
+    /// let dispatcher = Dispatcher::new("/dev/serial0", 115200)?;
+    /// dispatcher.set_tap(Some(Box::new(|direction, packet| {
+    ///     let arrow = if direction == Direction::Tx { "->" } else { "<-" };
+    ///     println!("{} {}", arrow, packet.to_hex_string());
+    /// })));
+    /// # Ok::<(), sphero_rvr::error::RvrError>(())
+    /// ```
+    pub fn set_tap(&self, tap: Option<PacketTap>) -> Option<PacketTap> {
+        std::mem::replace(&mut *self.tap.write().unwrap(), tap)
+    }
+
+    /// Enable or disable per-packet TX/RX logging for a device
+    ///
+    /// Every packet sent or received is logged at trace level, which floods
+    /// the log for high-rate commands like driving. Disabling a device id
+    /// suppresses its trace lines while other devices keep logging. Errors
+    /// and warnings are unaffected.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_id` - Device id (e.g. `device::DRIVE`)
+    /// * `enabled` - Whether to log packets for this device (default true)
+    pub fn set_command_log_filter(&self, device_id: u8, enabled: bool) {
+        self.log_filter.set(device_id, enabled);
+    }
+
+    /// Choose how the RX thread reacts to frames that fail their checksum
+    ///
+    /// - `LogAndContinue`: count failures quietly, for very noisy lines
+    /// - `ReconnectAfter(n)`: reopen the serial port after `n` consecutive
+    ///   failures (a good packet resets the run)
+    /// - `ErrorChannel` (default): send each failure to the error channel
+    ///   returned by `take_error_receiver`
+    pub fn set_checksum_failure_policy(&self, policy: ChecksumFailurePolicy) {
+        let mut checksum = self.checksum.lock().unwrap();
+        checksum.policy = policy;
+        checksum.consecutive = 0;
+    }
+
+    /// Current state of the serial link
+    ///
+    /// After a fatal read or write error the dispatcher reopens the port
+    /// (only possible for dispatchers created with `new`), retrying with
+    /// backoff. Commands sent while `Reconnecting` or `Disconnected` fail.
+    pub fn state(&self) -> ConnectionState {
+        *self.connection.lock().unwrap()
+    }
+
+    /// Total number of frames that failed their checksum
+    pub fn checksum_failure_count(&self) -> u64 {
+        self.checksum.lock().unwrap().total
+    }
+
+    /// Take a receiver for errors encountered by the RX thread
+    ///
+    /// Parser errors (bad checksums, invalid escapes) and serial read errors
+    /// are normally only logged. Once this is called they are also forwarded
+    /// to the returned receiver, so the application can react to a degrading
+    /// link, e.g. by slowing its command rate.
+    ///
+    /// Errors that occurred before this call are not delivered.
+    ///
+    /// Can only be called once - subsequent calls return None.
+    ///
+    /// ```no_run
+    /// # use sphero_rvr::transport::Dispatcher;
+    /// # let dispatcher = Dispatcher::new("/dev/serial0", 115200).unwrap();
+    /// if let Some(errors) = dispatcher.take_error_receiver() {
+    ///     std::thread::spawn(move || {
+    ///         for error in errors {
+    ///             eprintln!("RX error: {}", error);
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    pub fn take_error_receiver(&self) -> Option<ErrorReceiver> {
+        let mut error_tx = self.error_tx.lock().unwrap();
+        if error_tx.is_some() {
+            return None;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        *error_tx = Some(tx);
+        Some(rx)
+    }
+
+    /// Hand a notification receiver back after `take_receiver`
+    ///
+    /// Lets crate helpers borrow the receiver temporarily; a later
+    /// `take_receiver` will return it again.
+    pub(crate) fn restore_receiver(&self, rx: NotificationReceiver) {
+        *self.notification_rx.lock().unwrap() = Some(rx);
+    }
+
     /// Shutdown the dispatcher and wait for RX thread to exit
     pub fn shutdown(&self) -> Result<()> {
         tracing::debug!("Shutting down dispatcher");
@@ -334,6 +1420,7 @@ impl Drop for Dispatcher {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::framing::ESC;
 
     // Note: These tests require a real serial port or mock.
     // For now, we'll test the packet routing logic in isolation.
@@ -347,6 +1434,817 @@ mod tests {
         assert_eq!(seq.fetch_add(1, Ordering::SeqCst), 0); // Wraps to 0
     }
 
+    #[test]
+    fn test_allocate_sequence_skips_pending() {
+        let next = AtomicU8::new(10);
+        let mut pending = HashMap::new();
+        for seq in [10, 11, 13] {
+            pending.insert(seq, mpsc::channel().0);
+        }
+
+        assert_eq!(allocate_sequence(&next, &pending).unwrap(), 12);
+        assert_eq!(allocate_sequence(&next, &pending).unwrap(), 14);
+    }
+
+    #[test]
+    fn test_allocate_sequence_wraps_past_pending() {
+        let next = AtomicU8::new(254);
+        let mut pending = HashMap::new();
+        for seq in [254, 255, 0] {
+            pending.insert(seq, mpsc::channel().0);
+        }
+
+        assert_eq!(allocate_sequence(&next, &pending).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_allocate_sequence_all_in_flight() {
+        let next = AtomicU8::new(0);
+        let pending: HashMap<u8, ResponseSender> =
+            (0..=u8::MAX).map(|seq| (seq, mpsc::channel().0)).collect();
+
+        assert!(matches!(
+            allocate_sequence(&next, &pending),
+            Err(RvrError::TooManyInFlight)
+        ));
+    }
+
+    #[test]
+    fn test_send_command_never_overwrites_pending_request() {
+        use crate::transport::MockTransport;
+
+        let transport = MockTransport::new();
+        let handle = transport.handle();
+        let dispatcher = Dispatcher::from_transport(transport);
+
+        // Every sequence number is waiting on a response
+        let mut waiting = Vec::new();
+        {
+            let mut pending = dispatcher.pending_requests.lock().unwrap();
+            for seq in 0..=u8::MAX {
+                let (tx, rx) = mpsc::channel();
+                pending.insert(seq, tx);
+                waiting.push(rx);
+            }
+        }
+
+        let wake = Packet::new_command(0x13, 0x0D, 0, vec![]);
+        let result = dispatcher.send_command(wake.clone());
+        assert!(matches!(result, Err(RvrError::TooManyInFlight)));
+        assert!(handle.written().is_empty());
+
+        // Free one number; the next command takes exactly that one
+        drop(dispatcher.pending_requests.lock().unwrap().remove(&7));
+        let result = dispatcher.send_command_timeout(wake, Duration::from_millis(10));
+        assert!(matches!(result, Err(RvrError::Timeout)));
+        let mut parser = SpheroParser::new();
+        let sent = handle
+            .take_written()
+            .into_iter()
+            .find_map(|byte| parser.feed(byte).unwrap())
+            .unwrap();
+        assert_eq!(sent.sequence_number, 7);
+
+        // The original callers' channels are all still registered
+        let pending = dispatcher.pending_requests.lock().unwrap();
+        assert_eq!(pending.len(), 255);
+        for (seq, rx) in waiting.iter().enumerate() {
+            if seq != 7 {
+                pending[&(seq as u8)]
+                    .send(Packet::new_command(0, 0, 0, vec![]))
+                    .unwrap();
+                assert!(rx.try_recv().is_ok(), "seq {} was overwritten", seq);
+            }
+        }
+        drop(pending);
+        dispatcher.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_rate_limiter_spacing() {
+        let mut limiter = RateLimiter::default();
+        let t0 = Instant::now();
+
+        // Unlimited by default
+        assert_eq!(limiter.reserve(t0), Duration::ZERO);
+        assert_eq!(limiter.reserve(t0), Duration::ZERO);
+
+        // 10/s: back-to-back sends are spaced 100 ms apart
+        limiter.set_rate(10);
+        assert_eq!(limiter.reserve(t0), Duration::ZERO);
+        assert_eq!(limiter.reserve(t0), Duration::from_millis(100));
+        assert_eq!(limiter.reserve(t0), Duration::from_millis(200));
+
+        // After an idle period there is no burst credit and no wait
+        let later = t0 + Duration::from_secs(1);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+        assert_eq!(limiter.reserve(later), Duration::from_millis(100));
+
+        // 0 removes the limit
+        limiter.set_rate(0);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_rate_limiter_delays_real_sends() {
+        let limiter = Mutex::new(RateLimiter::default());
+        limiter.lock().unwrap().set_rate(100); // 10 ms spacing
+
+        let start = Instant::now();
+        for _ in 0..5 {
+            let delay = limiter.lock().unwrap().reserve(Instant::now());
+            thread::sleep(delay);
+        }
+        let elapsed = start.elapsed();
+
+        // First send is immediate, the other four wait ~10 ms each
+        assert!(elapsed >= Duration::from_millis(38), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(500), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_notification_channel_delivery() {
+        let (tx, rx) = notification_channel();
+        tx.send(Packet::new_command(0x18, 0x3D, 0, vec![0x01]))
+            .unwrap();
+
+        let received = rx.recv_timeout(Duration::from_millis(100)).unwrap();
+        assert_eq!(received.device_id, 0x18);
+        assert!(matches!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        ));
+    }
+
+    #[cfg(feature = "crossbeam")]
+    #[test]
+    fn test_notification_channel_multi_consumer() {
+        let (tx, rx) = notification_channel();
+        let rx2 = rx.clone();
+
+        let consumers: Vec<_> = [rx, rx2]
+            .into_iter()
+            .map(|rx| {
+                thread::spawn(move || {
+                    let mut count = 0;
+                    while rx.recv_timeout(Duration::from_millis(200)).is_ok() {
+                        count += 1;
+                    }
+                    count
+                })
+            })
+            .collect();
+
+        for seq in 0..10 {
+            tx.send(Packet::new_command(0x18, 0x3D, seq, vec![]))
+                .unwrap();
+        }
+        drop(tx);
+
+        let total: usize = consumers.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(total, 10);
+    }
+
+    #[test]
+    fn test_wait_for_notification_matches() {
+        let waiters = Arc::new(Mutex::new(NotificationWaiters::default()));
+
+        let injector = {
+            let waiters = Arc::clone(&waiters);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                let mut waiters = waiters.lock().unwrap();
+                // Non-matching notification is ignored
+                waiters.dispatch(&Packet::new_command(0x18, 0x3D, 0, vec![]));
+                // Matching notification is delivered
+                waiters.dispatch(&Packet::new_command(0x13, 0x1A, 0, vec![]));
+            })
+        };
+
+        let packet = wait_for_matching(
+            &waiters,
+            Box::new(|p: &Packet| p.device_id == 0x13 && p.command_id == 0x1A),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        injector.join().unwrap();
+
+        assert_eq!(packet.device_id, 0x13);
+        assert_eq!(packet.command_id, 0x1A);
+        assert!(waiters.lock().unwrap().waiters.is_empty());
+    }
+
+    #[test]
+    fn test_subscription_catches_notification_before_wait() {
+        let waiters = Mutex::new(NotificationWaiters::default());
+        let subscription =
+            NotificationSubscription::new(&waiters, Box::new(|p: &Packet| p.command_id == 0x26));
+
+        // Delivered before wait() is called (e.g. right after the command)
+        waiters
+            .lock()
+            .unwrap()
+            .dispatch(&Packet::new_command(0x18, 0x26, 0, vec![0x01]));
+
+        let packet = subscription.wait(Duration::from_millis(10)).unwrap();
+        assert_eq!(packet.payload, vec![0x01]);
+    }
+
+    #[test]
+    fn test_dropped_subscription_unregisters() {
+        let waiters = Mutex::new(NotificationWaiters::default());
+        let subscription = NotificationSubscription::new(&waiters, Box::new(|_: &Packet| true));
+        assert_eq!(waiters.lock().unwrap().waiters.len(), 1);
+
+        drop(subscription);
+        assert!(waiters.lock().unwrap().waiters.is_empty());
+    }
+
+    #[test]
+    fn test_wait_for_notification_timeout() {
+        let waiters = Arc::new(Mutex::new(NotificationWaiters::default()));
+
+        let injector = {
+            let waiters = Arc::clone(&waiters);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                waiters
+                    .lock()
+                    .unwrap()
+                    .dispatch(&Packet::new_command(0x18, 0x3D, 0, vec![]));
+            })
+        };
+
+        let result = wait_for_matching(
+            &waiters,
+            Box::new(|p: &Packet| p.device_id == 0x13),
+            Duration::from_millis(50),
+        );
+        injector.join().unwrap();
+
+        assert!(matches!(result, Err(RvrError::Timeout)));
+        // Timed-out waiter is unregistered
+        assert!(waiters.lock().unwrap().waiters.is_empty());
+    }
+
+    fn test_routes() -> RxRoutes {
+        test_routes_with_receiver().0
+    }
+
+    fn test_routes_with_receiver() -> (RxRoutes, NotificationReceiver) {
+        let (notification_tx, notification_rx) = notification_channel();
+        let routes = RxRoutes {
+            pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            pending_by_command: Arc::new(Mutex::new(HashMap::new())),
+            notification_waiters: Arc::new(Mutex::new(NotificationWaiters::default())),
+            notification_tx,
+            error_tx: Arc::new(Mutex::new(None)),
+            log_filter: Arc::new(CommandLogFilter::default()),
+            checksum: Arc::new(Mutex::new(ChecksumMonitor::default())),
+            reconnect_requested: Arc::new(AtomicBool::new(false)),
+            connection: Arc::new(Mutex::new(ConnectionState::Connected)),
+            robot_asleep: Arc::new(AtomicBool::new(false)),
+            tap: Arc::new(RwLock::new(None)),
+        };
+        (routes, notification_rx)
+    }
+
+    #[test]
+    fn test_tap_sees_both_directions() {
+        use crate::transport::MockTransport;
+
+        let transport = MockTransport::new();
+        let handle = transport.handle();
+        let dispatcher = Dispatcher::from_transport(transport);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        dispatcher.set_tap(Some(Box::new(move |direction, packet| {
+            log.lock()
+                .unwrap()
+                .push((direction, packet.device_id, packet.command_id));
+        })));
+
+        let request = Packet::new_command(device::POWER, power_command::WAKE, 0, vec![]);
+        dispatcher.send_packet_no_response(&request).unwrap();
+        let mut notification =
+            Packet::new_command(device::POWER, power_command::DID_SLEEP_NOTIFY, 0, vec![]);
+        notification.flags.requests_response = false;
+        handle.inject_packet(&notification);
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while seen.lock().unwrap().len() < 2 {
+            assert!(
+                Instant::now() < deadline,
+                "tap saw {:?}",
+                seen.lock().unwrap()
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (Direction::Tx, device::POWER, power_command::WAKE),
+                (
+                    Direction::Rx,
+                    device::POWER,
+                    power_command::DID_SLEEP_NOTIFY
+                ),
+            ]
+        );
+
+        // Detaching hands the tap back and stops further calls
+        assert!(dispatcher.set_tap(None).is_some());
+        dispatcher.send_packet_no_response(&request).unwrap();
+        assert_eq!(seen.lock().unwrap().len(), 2);
+        dispatcher.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_parser_errors_forwarded_to_error_channel() {
+        let routes = test_routes();
+        let (tx, errors) = mpsc::channel();
+        *routes.error_tx.lock().unwrap() = Some(tx);
+
+        let mut parser = SpheroParser::new();
+        routes.process_bytes(&mut parser, &corrupt_frame());
+
+        let error = errors.recv_timeout(Duration::from_millis(100)).unwrap();
+        assert!(matches!(error, RvrError::Checksum { .. }));
+        assert!(errors.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_timeout_after_sleep_notification_is_robot_asleep() {
+        let routes = test_routes();
+        let (tx, rx) = mpsc::channel();
+        routes.pending_requests.lock().unwrap().insert(7, tx);
+        assert!(matches!(
+            timeout_error(&routes.robot_asleep),
+            RvrError::Timeout
+        ));
+
+        // The robot falls asleep while request 7 is in flight
+        let sleep = Packet::new_command(device::POWER, power_command::DID_SLEEP_NOTIFY, 0, vec![]);
+        routes.route_packet(sleep);
+        assert!(rx.recv_timeout(Duration::from_millis(20)).is_err());
+        assert!(matches!(
+            timeout_error(&routes.robot_asleep),
+            RvrError::RobotAsleep
+        ));
+
+        // Once it answers again (e.g. after a wake), timeouts are plain again
+        let mut response = Packet::new_command(device::POWER, power_command::WAKE, 8, vec![0]);
+        response.flags.is_response = true;
+        routes.route_packet(response);
+        assert!(matches!(
+            timeout_error(&routes.robot_asleep),
+            RvrError::Timeout
+        ));
+    }
+
+    #[test]
+    fn test_command_matched_response_ignores_sequence() {
+        let routes = test_routes();
+        let (seq_tx, seq_rx) = mpsc::channel();
+        let (cmd_tx, cmd_rx) = mpsc::channel();
+        routes.pending_requests.lock().unwrap().insert(3, seq_tx);
+        routes
+            .pending_by_command
+            .lock()
+            .unwrap()
+            .insert((0x11, 0x02), cmd_tx);
+
+        // Firmware answered with the wrong sequence number
+        let mut response = Packet::new_command(0x11, 0x02, 3, vec![0x00, 0x07]);
+        response.flags.is_response = true;
+        routes.route_packet(response);
+
+        let routed = cmd_rx.try_recv().unwrap();
+        assert_eq!(routed.payload, vec![0x00, 0x07]);
+        assert!(seq_rx.try_recv().is_err());
+        assert!(routes.pending_by_command.lock().unwrap().is_empty());
+
+        // Other responses still match on sequence number
+        let mut response = Packet::new_command(0x13, 0x0D, 3, vec![0x00]);
+        response.flags.is_response = true;
+        routes.route_packet(response);
+        assert_eq!(seq_rx.try_recv().unwrap().command_id, 0x0D);
+    }
+
+    /// Writer whose writes fail with a fixed error kind
+    struct FailingWriter(std::io::ErrorKind);
+
+    impl std::io::Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(self.0.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_timeout_maps_to_write_stalled() {
+        let result = write_frame(
+            &mut FailingWriter(std::io::ErrorKind::TimedOut),
+            &[SOP, EOP],
+        );
+        assert!(matches!(result, Err(RvrError::WriteStalled)));
+
+        let result = write_frame(
+            &mut FailingWriter(std::io::ErrorKind::BrokenPipe),
+            &[SOP, EOP],
+        );
+        assert!(matches!(result, Err(RvrError::Io(_))));
+
+        let mut written = Vec::new();
+        write_frame(&mut written, &[SOP, EOP]).unwrap();
+        assert_eq!(written, vec![SOP, EOP]);
+    }
+
+    #[test]
+    fn test_command_timeout_is_configurable() {
+        use crate::transport::VirtualRvr;
+
+        let robot = VirtualRvr::new();
+        let handle = robot.handle();
+        let dispatcher =
+            Dispatcher::from_port(Box::new(robot)).with_timeout(Duration::from_millis(50));
+
+        // The robot never answers a packet that does not request a response
+        let mut unanswered = Packet::new_command(0x13, 0x0D, 0, vec![]);
+        unanswered.flags.requests_response = false;
+
+        let start = std::time::Instant::now();
+        let result = dispatcher.send_command(unanswered.clone());
+        assert!(matches!(result, Err(RvrError::Timeout)));
+        assert!(start.elapsed() < DEFAULT_COMMAND_TIMEOUT);
+
+        // A zero per-call timeout fails without sending
+        let sent = handle.commands().len();
+        let result = dispatcher.send_command_timeout(unanswered, Duration::ZERO);
+        assert!(matches!(result, Err(RvrError::InvalidParameter(_))));
+        assert_eq!(handle.commands().len(), sent);
+
+        // A real command still gets its answer
+        let wake = Packet::new_command(0x13, 0x0D, 0, vec![]);
+        assert!(dispatcher
+            .send_command_timeout(wake, Duration::from_millis(500))
+            .is_ok());
+        dispatcher.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(0), Duration::from_millis(50));
+        assert_eq!(retry_delay(1), Duration::from_millis(100));
+        assert_eq!(retry_delay(3), Duration::from_millis(400));
+        // Huge retry counts saturate instead of overflowing
+        assert!(retry_delay(40) >= retry_delay(31));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let response = |code| Packet::new_command(0x13, 0x0D, 0, vec![code]);
+
+        assert!(is_retryable(&Ok(response(error_code::BUSY))));
+        assert!(is_retryable(&Err(RvrError::Timeout)));
+        assert!(!is_retryable(&Ok(response(error_code::SUCCESS))));
+        assert!(!is_retryable(&Ok(response(error_code::BAD_COMMAND_ID))));
+        assert!(!is_retryable(&Err(RvrError::WriteStalled)));
+    }
+
+    #[test]
+    fn test_send_command_with_retries() {
+        use crate::transport::VirtualRvr;
+
+        let robot = VirtualRvr::new();
+        let handle = robot.handle();
+        let dispatcher =
+            Dispatcher::from_port(Box::new(robot)).with_timeout(Duration::from_millis(20));
+
+        // Unanswered: sent once plus two retries, each with a new sequence number
+        let mut unanswered = Packet::new_command(0x13, 0x0D, 0, vec![]);
+        unanswered.flags.requests_response = false;
+        let result = dispatcher.send_command_with_retries(unanswered, 2);
+        assert!(matches!(result, Err(RvrError::Timeout)));
+        assert_eq!(handle.commands().len(), 3);
+        assert_eq!(dispatcher.next_sequence.load(Ordering::SeqCst), 3);
+
+        // BAD_COMMAND_ID is definitive: sent once
+        let unknown = Packet::new_command(0x7F, 0x7F, 0, vec![]);
+        let response = dispatcher.send_command_with_retries(unknown, 2).unwrap();
+        assert_eq!(response.payload, vec![error_code::BAD_COMMAND_ID]);
+        assert_eq!(handle.commands().len(), 4);
+        dispatcher.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_reconnect_retries_until_reopened() {
+        let port = Mutex::new(0);
+        let connection = Mutex::new(ConnectionState::Connected);
+        let shutdown = AtomicBool::new(false);
+        let tries = std::cell::Cell::new(0);
+
+        // Fails twice, then a new port (1) opens
+        let reopen = || {
+            tries.set(tries.get() + 1);
+            if tries.get() < 3 {
+                Err(RvrError::Io(std::io::ErrorKind::NotFound.into()))
+            } else {
+                Ok(1)
+            }
+        };
+        let install = |p| *port.lock().unwrap() = p;
+        reconnect(reopen, install, 5, &connection, &shutdown).unwrap();
+        assert_eq!(tries.get(), 3);
+        assert_eq!(*port.lock().unwrap(), 1);
+        assert_eq!(*connection.lock().unwrap(), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn test_reconnect_gives_up_after_attempts() {
+        let port = Mutex::new(0);
+        let connection = Mutex::new(ConnectionState::Connected);
+        let shutdown = AtomicBool::new(false);
+        let tries = std::cell::Cell::new(0);
+
+        let reopen = || {
+            tries.set(tries.get() + 1);
+            Err(RvrError::Io(std::io::ErrorKind::NotFound.into()))
+        };
+        let install = |p| *port.lock().unwrap() = p;
+        let result = reconnect(reopen, install, 3, &connection, &shutdown);
+        assert!(matches!(result, Err(RvrError::Io(_))));
+        assert_eq!(tries.get(), 3);
+        assert_eq!(*port.lock().unwrap(), 0);
+        assert_eq!(*connection.lock().unwrap(), ConnectionState::Disconnected);
+    }
+
+    #[test]
+    fn test_fatal_read_error_requests_reconnect() {
+        struct BrokenPort;
+        impl Read for BrokenPort {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let routes = test_routes();
+        let mut parser = SpheroParser::new();
+        let mut buffer = [0u8; 16];
+        let paused = AtomicBool::new(false);
+        Dispatcher::rx_poll(
+            &Mutex::new(BrokenPort),
+            &mut parser,
+            &mut buffer,
+            &routes,
+            &paused,
+        );
+        assert!(routes.reconnect_requested.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_split_port_reads_do_not_block_writes() {
+        use crate::transport::MockTransport;
+
+        /// Sits in `read` like a port waiting out a long read timeout
+        struct SlowReader;
+        impl Read for SlowReader {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                thread::sleep(Duration::from_millis(200));
+                Err(std::io::ErrorKind::TimedOut.into())
+            }
+        }
+        impl std::io::Write for SlowReader {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let writer = MockTransport::new();
+        let handle = writer.handle();
+        let port = OpenedPort {
+            port: Box::new(writer),
+            reader: Some(Box::new(SlowReader)),
+        };
+        let dispatcher = Dispatcher::start(port, reopen_unsupported);
+        // Let the RX thread get into its read
+        thread::sleep(Duration::from_millis(20));
+
+        let started = Instant::now();
+        let packet = Packet::new_command(0x13, 0x0D, 0, vec![]);
+        dispatcher.send_packet_no_response(&packet).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(handle.take_written(), packet.to_framed_bytes());
+        dispatcher.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_write_failure_removes_pending_request() {
+        use crate::transport::VirtualRvr;
+
+        let robot = VirtualRvr::new();
+        let handle = robot.handle();
+        let dispatcher = Dispatcher::from_port(Box::new(robot));
+        handle.set_disconnected(true);
+
+        for matching in [ResponseMatch::Sequence, ResponseMatch::Command] {
+            let wake = Packet::new_command(0x13, 0x0D, 0, vec![]);
+            let result = dispatcher.send_command_matching(wake, matching);
+            assert!(matches!(result, Err(RvrError::Io(_))));
+        }
+        assert!(dispatcher.pending_requests.lock().unwrap().is_empty());
+        assert!(dispatcher.pending_by_command.lock().unwrap().is_empty());
+        dispatcher.shutdown().unwrap();
+    }
+
+    /// Valid frame with the checksum byte corrupted
+    fn corrupt_frame() -> Vec<u8> {
+        let mut encoded = Packet::new_command(0x13, 0x0D, 1, vec![]).to_bytes();
+        let last = encoded.len() - 1;
+        encoded[last] ^= 0xFF;
+        let mut frame = vec![SOP];
+        frame.extend_from_slice(&encode_bytes(&encoded));
+        frame.push(EOP);
+        frame
+    }
+
+    #[test]
+    fn test_checksum_policy_reconnect_after_threshold() {
+        let routes = test_routes();
+        routes.checksum.lock().unwrap().policy = ChecksumFailurePolicy::ReconnectAfter(3);
+        let mut parser = SpheroParser::new();
+
+        routes.process_bytes(&mut parser, &corrupt_frame());
+        routes.process_bytes(&mut parser, &corrupt_frame());
+        assert!(!routes.reconnect_requested.load(Ordering::SeqCst));
+
+        // A good packet resets the consecutive count
+        let good = Packet::new_command(0x18, 0x3D, 0, vec![]).to_framed_bytes();
+        routes.process_bytes(&mut parser, &good);
+        routes.process_bytes(&mut parser, &corrupt_frame());
+        routes.process_bytes(&mut parser, &corrupt_frame());
+        assert!(!routes.reconnect_requested.load(Ordering::SeqCst));
+
+        routes.process_bytes(&mut parser, &corrupt_frame());
+        assert!(routes.reconnect_requested.load(Ordering::SeqCst));
+        assert_eq!(routes.checksum.lock().unwrap().total, 5);
+    }
+
+    #[test]
+    fn test_checksum_policy_error_channel_vs_log() {
+        let routes = test_routes();
+        let (tx, errors) = mpsc::channel();
+        *routes.error_tx.lock().unwrap() = Some(tx);
+        let mut parser = SpheroParser::new();
+
+        // Default policy forwards every failure
+        routes.process_bytes(&mut parser, &corrupt_frame());
+        routes.process_bytes(&mut parser, &corrupt_frame());
+        assert_eq!(errors.try_iter().count(), 2);
+
+        // LogAndContinue only counts
+        routes.checksum.lock().unwrap().policy = ChecksumFailurePolicy::LogAndContinue;
+        routes.process_bytes(&mut parser, &corrupt_frame());
+        assert_eq!(errors.try_iter().count(), 0);
+        assert_eq!(routes.checksum.lock().unwrap().total, 3);
+        assert!(!routes.reconnect_requested.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_parser_errors_ignored_without_error_receiver() {
+        let routes = test_routes();
+        let (tx, rx) = mpsc::channel();
+        routes.pending_requests.lock().unwrap().insert(7, tx);
+
+        // Garbage followed by a valid response: the error is only logged and
+        // the response is still routed
+        let mut response = Packet::new_command(0x13, 0x0D, 7, vec![0x00]);
+        response.flags.is_response = true;
+        let mut frame = vec![SOP, EOP];
+        frame.extend_from_slice(&response.to_framed_bytes());
+
+        let mut parser = SpheroParser::new();
+        routes.process_bytes(&mut parser, &frame);
+
+        let routed = rx.recv_timeout(Duration::from_millis(100)).unwrap();
+        assert_eq!(routed.sequence_number, 7);
+        assert!(routes.pending_requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rx_pause_defers_parsing() {
+        let (routes, notifications) = test_routes_with_receiver();
+
+        let notification = Packet::new_command(0x18, 0x3D, 0, vec![0x01]);
+        let frame = notification.to_framed_bytes();
+        let port = Mutex::new(std::io::Cursor::new(frame));
+
+        let mut parser = SpheroParser::new();
+        let mut buffer = [0u8; 1024];
+        let paused = AtomicBool::new(true);
+
+        // Paused: the port is not read and nothing is routed
+        let read = Dispatcher::rx_poll(&port, &mut parser, &mut buffer, &routes, &paused);
+        assert_eq!(read, 0);
+        assert_eq!(port.lock().unwrap().position(), 0);
+        assert!(notifications.try_recv().is_err());
+
+        // Resumed: the buffered frame is parsed and delivered
+        paused.store(false, Ordering::SeqCst);
+        let read = Dispatcher::rx_poll(&port, &mut parser, &mut buffer, &routes, &paused);
+        assert!(read > 0);
+        let received = notifications.try_recv().unwrap();
+        assert_eq!(received.device_id, 0x18);
+        assert_eq!(received.command_id, 0x3D);
+    }
+
+    /// Reader that returns one predefined chunk per `read` call
+    struct ChunkedReader(std::collections::VecDeque<Vec<u8>>);
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.pop_front() {
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                None => Err(std::io::ErrorKind::TimedOut.into()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_escape_split_across_read_chunks() {
+        let (routes, notifications) = test_routes_with_receiver();
+
+        // Payload bytes that all need escaping
+        let notification = Packet::new_command(0x18, 0x3D, 0, vec![SOP, ESC, EOP]);
+        let frame = notification.to_framed_bytes();
+        let esc_at = frame.iter().position(|&b| b == ESC).unwrap();
+
+        // Split at every position, including right after the first ESC so
+        // the escaped byte starts the second read
+        for split in (1..frame.len()).chain([esc_at + 1]) {
+            let chunks = [frame[..split].to_vec(), frame[split..].to_vec()];
+            let port = Mutex::new(ChunkedReader(chunks.into_iter().collect()));
+            let mut parser = SpheroParser::new();
+            let mut buffer = [0u8; 1024];
+            let paused = AtomicBool::new(false);
+
+            Dispatcher::rx_poll(&port, &mut parser, &mut buffer, &routes, &paused);
+            Dispatcher::rx_poll(&port, &mut parser, &mut buffer, &routes, &paused);
+
+            let received = notifications.try_recv().unwrap();
+            assert_eq!(received.payload, vec![SOP, ESC, EOP], "split at {}", split);
+            assert!(notifications.try_recv().is_err());
+        }
+    }
+
+    #[test]
+    fn test_command_log_filter() {
+        let routes = test_routes();
+        routes.log_filter.set(0x16, false);
+
+        let captured = crate::transport::SharedBuffer::new();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            routes.route_packet(Packet::new_command(0x16, 0x07, 1, vec![]));
+            routes.route_packet(Packet::new_command(0x13, 0x0D, 2, vec![]));
+        });
+
+        let output = String::from_utf8(captured.contents()).unwrap();
+        assert!(!output.contains("dev=0x16"), "{}", output);
+        assert!(output.contains("dev=0x13"), "{}", output);
+
+        // Re-enabling restores logging
+        routes.log_filter.set(0x16, true);
+        assert!(routes.log_filter.enabled(0x16));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_missing_port_reports_not_found() {
+        match Dispatcher::new("/dev/sphero-rvr-does-not-exist", 115200) {
+            Err(RvrError::Serial(e)) => {
+                assert_eq!(e.kind(), serialport::ErrorKind::NoDevice);
+                assert!(e.to_string().contains("not found"), "{}", e);
+            }
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_) => panic!("opened a port that does not exist"),
+        }
+    }
+
     #[test]
     fn test_pending_requests_cleanup() {
         let pending: Arc<Mutex<HashMap<u8, ResponseSender>>> = Arc::new(Mutex::new(HashMap::new()));
@@ -0,0 +1,175 @@
+//! In-memory transport for tests
+//!
+//! `MockTransport` records every byte the dispatcher writes and feeds it
+//! whatever bytes a test injects, so request/response routing can be
+//! exercised without a serial port. Unlike `VirtualRvr` it never answers
+//! on its own; the test plays the robot through a `MockTransportHandle`.
+//! `SharedBuffer` captures other output, such as recordings or logs.
+//!
+//! Enabled by the `test-support` feature.
+
+use crate::protocol::packet::Packet;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long a read waits when there is nothing to receive
+const IDLE_READ_DELAY: Duration = Duration::from_millis(2);
+
+/// Bytes in flight in each direction
+#[derive(Debug, Default)]
+struct MockBuffers {
+    /// Injected bytes not yet read by the dispatcher
+    inbound: VecDeque<u8>,
+    /// Bytes written by the dispatcher and not yet taken
+    written: Vec<u8>,
+}
+
+/// An in-memory `SerialTransport`
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    buffers: Arc<Mutex<MockBuffers>>,
+}
+
+impl MockTransport {
+    /// Create a transport with nothing to read
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle for injecting and capturing bytes once the transport has been handed off
+    pub fn handle(&self) -> MockTransportHandle {
+        MockTransportHandle {
+            buffers: Arc::clone(&self.buffers),
+        }
+    }
+}
+
+/// Inject received bytes into, and capture written bytes from, a `MockTransport`
+#[derive(Debug, Clone)]
+pub struct MockTransportHandle {
+    buffers: Arc<Mutex<MockBuffers>>,
+}
+
+impl MockTransportHandle {
+    /// Queue raw bytes to be "received" by the dispatcher
+    pub fn inject(&self, bytes: &[u8]) {
+        self.buffers.lock().unwrap().inbound.extend(bytes);
+    }
+
+    /// Queue a packet, framed, to be "received" by the dispatcher
+    pub fn inject_packet(&self, packet: &Packet) {
+        self.inject(&packet.to_framed_bytes());
+    }
+
+    /// Everything written so far that has not been taken
+    pub fn written(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().written.clone()
+    }
+
+    /// Take everything written so far, leaving the capture empty
+    pub fn take_written(&self) -> Vec<u8> {
+        std::mem::take(&mut self.buffers.lock().unwrap().written)
+    }
+}
+
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.inbound.is_empty() {
+            drop(buffers);
+            // Behave like a serial port with a short read timeout
+            thread::sleep(IDLE_READ_DELAY);
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+
+        let count = buf.len().min(buffers.inbound.len());
+        for (slot, byte) in buf.iter_mut().zip(buffers.inbound.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffers.lock().unwrap().written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A cloneable `Write` sink whose clones all append to the same buffer
+///
+/// Hand one clone to a `CommandRecorder` or log writer and read the
+/// output back through another.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far, by any clone
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::parser::SpheroParser;
+    use crate::transport::Dispatcher;
+
+    #[test]
+    fn test_captures_writes_and_delivers_injected_bytes() {
+        let transport = MockTransport::new();
+        let handle = transport.handle();
+        let dispatcher = Dispatcher::from_transport(transport);
+        let notifications = dispatcher.take_receiver().unwrap();
+
+        // Written packets are captured framed
+        let wake = Packet::new_command(0x13, 0x0D, 7, vec![]);
+        dispatcher.send_packet_no_response(&wake).unwrap();
+        let written = handle.take_written();
+        assert_eq!(written, wake.to_framed_bytes());
+        assert!(handle.written().is_empty());
+
+        let mut parser = SpheroParser::new();
+        let sent = written
+            .iter()
+            .find_map(|&byte| parser.feed(byte).unwrap())
+            .unwrap();
+        assert_eq!(sent.sequence_number, 7);
+
+        // Injected packets are received
+        let mut notification = Packet::new_command(0x18, 0x3D, 0, vec![0x01]);
+        notification.flags.requests_response = false;
+        handle.inject_packet(&notification);
+        let received = notifications
+            .recv_timeout(Duration::from_millis(500))
+            .unwrap();
+        assert_eq!(received.payload, vec![0x01]);
+
+        dispatcher.shutdown().unwrap();
+    }
+}
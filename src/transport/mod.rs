@@ -11,6 +11,22 @@
 //! - Pushes async events/sensors to MPSC channels
 
 pub mod dispatcher;
+#[cfg(any(test, feature = "test-support"))]
+pub mod mock;
+pub mod recorder;
+pub mod serial;
+#[cfg(any(test, feature = "test-support"))]
+pub mod virtual_rvr;
 
 // Re-export commonly used items
-pub use dispatcher::Dispatcher;
+pub use dispatcher::{
+    ChecksumFailurePolicy, ConnectionState, Direction, Dispatcher, ErrorReceiver,
+    NotificationReceiver, NotificationSender, NotificationSubscription, PacketTap,
+    RecvTimeoutError, ResponseMatch,
+};
+#[cfg(any(test, feature = "test-support"))]
+pub use mock::{MockTransport, MockTransportHandle, SharedBuffer};
+pub use recorder::CommandRecorder;
+pub use serial::SerialTransport;
+#[cfg(any(test, feature = "test-support"))]
+pub use virtual_rvr::{VirtualRvr, VirtualRvrHandle};
@@ -0,0 +1,189 @@
+//! Command recording and replay
+//!
+//! A `CommandRecorder` attached to the dispatcher logs every packet sent,
+//! one line per packet, as the elapsed time since the first recorded packet
+//! followed by the framed packet in hex:
+//!
+//! ```text
+//! # sphero-rvr command recording
+//! 0 8D 32 01 02 13 0D 00 AA D8
+//! 250 8D 32 01 02 1A 1A 01 3F FF 00 00 57 D8
+//! ```
+//!
+//! Recordings are plain text so they can be inspected, edited, or pasted
+//! into bug reports. `read_recording` parses them back and `replay_with`
+//! re-sends them with the original timing.
+
+use crate::error::{Result, RvrError};
+use crate::protocol::packet::Packet;
+use std::io::{BufRead, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Header line written at the start of every recording
+const RECORDING_HEADER: &str = "# sphero-rvr command recording";
+
+/// Records outgoing packets with relative timestamps
+///
+/// Attach to a dispatcher with `Dispatcher::set_recorder`.
+pub struct CommandRecorder {
+    writer: Box<dyn Write + Send>,
+    /// Time of the first recorded packet (offsets are relative to this)
+    start: Option<Instant>,
+}
+
+impl CommandRecorder {
+    /// Create a recorder writing to any writer
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+            start: None,
+        }
+    }
+
+    /// Create a recorder writing to a new file at `path`
+    pub fn create(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let file = std::fs::File::create(path)?;
+        Ok(Self::new(std::io::BufWriter::new(file)))
+    }
+
+    /// Record a packet sent now
+    pub fn record(&mut self, packet: &Packet) -> Result<()> {
+        self.record_at(packet, Instant::now())
+    }
+
+    /// Record a packet sent at `now`
+    fn record_at(&mut self, packet: &Packet, now: Instant) -> Result<()> {
+        let start = match self.start {
+            Some(start) => start,
+            None => {
+                writeln!(self.writer, "{}", RECORDING_HEADER)?;
+                self.start = Some(now);
+                now
+            }
+        };
+
+        let offset_ms = now.saturating_duration_since(start).as_millis();
+        writeln!(self.writer, "{} {}", offset_ms, packet.to_hex_string())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A packet read back from a recording
+#[derive(Debug, Clone)]
+pub struct RecordedPacket {
+    /// Time after the first packet at which this one was sent
+    pub offset: Duration,
+    /// The packet as sent (sequence number will be reassigned on replay)
+    pub packet: Packet,
+}
+
+/// Parse a recording written by `CommandRecorder`
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub fn read_recording(reader: impl BufRead) -> Result<Vec<RecordedPacket>> {
+    let mut recording = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (offset, capture) = line.split_once(char::is_whitespace).ok_or_else(|| {
+            RvrError::Protocol(format!("Recording line {}: missing packet", index + 1))
+        })?;
+        let offset_ms: u64 = offset.parse().map_err(|_| {
+            RvrError::Protocol(format!(
+                "Recording line {}: invalid offset '{}'",
+                index + 1,
+                offset
+            ))
+        })?;
+
+        recording.push(RecordedPacket {
+            offset: Duration::from_millis(offset_ms),
+            packet: Packet::from_hex_capture(capture)?,
+        });
+    }
+
+    Ok(recording)
+}
+
+/// Re-send a recording with its original timing
+///
+/// Each packet is passed to `send` once its offset (relative to the start
+/// of the replay) has elapsed. Stops at the first send error.
+///
+/// # Returns
+///
+/// Returns the number of packets sent
+pub fn replay_with(
+    recording: &[RecordedPacket],
+    mut send: impl FnMut(&Packet) -> Result<()>,
+) -> Result<usize> {
+    let start = Instant::now();
+
+    for entry in recording {
+        if let Some(wait) = entry.offset.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+        send(&entry.packet)?;
+    }
+
+    Ok(recording.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::SharedBuffer;
+
+    #[test]
+    fn test_record_and_replay() {
+        let buffer = SharedBuffer::new();
+        let mut recorder = CommandRecorder::new(buffer.clone());
+
+        let wake = Packet::new_command(0x13, 0x0D, 0, vec![]);
+        let leds = Packet::new_command(0x1A, 0x1A, 1, vec![0x3F, 0xFF, 0x00, 0x00]);
+        let t0 = Instant::now();
+        recorder.record_at(&wake, t0).unwrap();
+        recorder
+            .record_at(&leds, t0 + Duration::from_millis(20))
+            .unwrap();
+
+        let text = String::from_utf8(buffer.contents()).unwrap();
+        assert!(text.starts_with(RECORDING_HEADER));
+
+        let recording = read_recording(text.as_bytes()).unwrap();
+        assert_eq!(recording.len(), 2);
+        assert_eq!(recording[0].offset, Duration::ZERO);
+        assert_eq!(recording[1].offset, Duration::from_millis(20));
+
+        let replay_start = Instant::now();
+        let mut sent = Vec::new();
+        let count = replay_with(&recording, |packet| {
+            sent.push((replay_start.elapsed(), packet.to_hex_string()));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(sent[0].1, wake.to_hex_string());
+        assert_eq!(sent[1].1, leds.to_hex_string());
+        // Recorded timing is preserved
+        assert!(sent[1].0 >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_read_recording_rejects_bad_lines() {
+        assert!(read_recording("abc 8D 02 13 0D 00 DD D8".as_bytes()).is_err());
+        assert!(read_recording("10".as_bytes()).is_err());
+        assert!(read_recording("10 8D 02 13".as_bytes()).is_err());
+        assert!(read_recording("\n# comment only\n".as_bytes())
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,20 @@
+//! Byte transport under the dispatcher
+//!
+//! The dispatcher only reads, writes and flushes bytes, so anything that
+//! can do that can carry the protocol: a real serial port, the in-memory
+//! `MockTransport`, or a `VirtualRvr`.
+
+use std::io::{Read, Write};
+
+/// A byte stream the dispatcher can run over
+///
+/// Implemented for every `Read + Write + Send` type, including
+/// `Box<dyn serialport::SerialPort>`.
+///
+/// Reads must not block indefinitely: with no data available, `read`
+/// should fail with `ErrorKind::TimedOut` within about 100 ms (as a serial
+/// port with a read timeout does), so the RX thread can notice shutdown
+/// and writers can get at the port.
+pub trait SerialTransport: Read + Write + Send {}
+
+impl<T: Read + Write + Send + ?Sized> SerialTransport for T {}
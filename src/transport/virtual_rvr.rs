@@ -0,0 +1,722 @@
+//! In-memory robot for running code without hardware
+//!
+//! `VirtualRvr` implements `serialport::SerialPort`, so it can stand in for
+//! the UART when building a client with `SpheroRvr::from_port`. Commands
+//! written to it are parsed and answered with protocol-correct responses,
+//! and while awake it emits periodic sensor streaming notifications for
+//! each configured stream. Until streaming is configured it sends locator
+//! samples (`SensorType::Locator`, on its own stream token). Samples carry
+//! the simulated position, orientation and ambient light; other sensors
+//! read 0. Driving moves the simulated position at the commanded speed and
+//! heading.
+//!
+//! Only the core command set is understood:
+//!
+//! - Power: wake, sleep (followed by a did-sleep notification), battery
+//!   percentage, battery voltage state (always OK), protection state (never
+//!   latched)
+//! - Drive: drive with heading, stop
+//! - IO: set all LEDs, get RGB LED
+//! - System info: firmware version
+//! - Sensor: ambient light, reset locator, configure, start and stop streaming, set
+//!   streaming interval (changes the notification rate), enable color
+//!   detection, send and enable IR messages (accepted, no notifications are
+//!   sent)
+//!
+//! Anything else is answered with `error_code::BAD_COMMAND_ID`. Tests can
+//! make any command fail instead with `VirtualRvrHandle::set_response_code`.
+//!
+//! Enabled by the `test-support` feature.
+
+use crate::api::constants::{
+    api_shell_command, device, drive_command, drive_flags, error_code, io_command, power_command,
+    sensor_command, system_info_command,
+};
+use crate::api::led::LedPosition;
+use crate::api::sensors::{encode_samples, SensorType};
+use crate::api::types::{Color, FirmwareVersion, Locator};
+use crate::protocol::packet::{Packet, PacketFlags};
+use crate::protocol::parser::SpheroParser;
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a read waits when there is nothing to send
+const IDLE_READ_DELAY: Duration = Duration::from_millis(2);
+
+/// Number of settable LEDs tracked
+const LED_COUNT: usize = 6;
+
+/// Ground speed at drive speed 255 (m/s)
+const TOP_SPEED_M_S: f32 = 1.555;
+
+/// Robot state shared between the port and its handles
+#[derive(Debug)]
+struct RobotState {
+    awake: bool,
+    battery_percentage: u8,
+    ambient_light: f32,
+    firmware: FirmwareVersion,
+    leds: [Color; LED_COUNT],
+    /// Position reported in locator samples
+    position: Locator,
+    /// Velocity from the last drive command, `(x, y)` in m/s
+    velocity: (f32, f32),
+    /// When `position` was last advanced by `velocity`
+    moved_at: Instant,
+    /// `[W, X, Y, Z]` reported in quaternion samples
+    orientation: [f32; 4],
+    /// Configured streams: token and the sensors it carries, in order
+    streams: Vec<(u8, Vec<SensorType>)>,
+    /// Every command received, in order
+    packets: Vec<Packet>,
+    /// Error codes to answer `(device, command)` with instead of executing it
+    response_codes: Vec<((u8, u8), u8)>,
+    notification_interval: Option<Duration>,
+    notifications_sent: usize,
+    /// Simulate an unplugged adapter: reads and writes fail
+    disconnected: bool,
+}
+
+/// Inspect and adjust a `VirtualRvr` after handing it to a client
+#[derive(Debug, Clone)]
+pub struct VirtualRvrHandle {
+    state: Arc<Mutex<RobotState>>,
+}
+
+impl VirtualRvrHandle {
+    /// Returns true if the robot is awake
+    pub fn is_awake(&self) -> bool {
+        self.state.lock().unwrap().awake
+    }
+
+    /// Current color of an LED
+    pub fn led_color(&self, position: LedPosition) -> Color {
+        self.state.lock().unwrap().leds[position.index().index() as usize]
+    }
+
+    /// `(device, command)` of every command received so far, in order
+    pub fn commands(&self) -> Vec<(u8, u8)> {
+        self.state
+            .lock()
+            .unwrap()
+            .packets
+            .iter()
+            .map(|packet| (packet.device_id, packet.command_id))
+            .collect()
+    }
+
+    /// Every command packet received so far, in order
+    pub fn packets(&self) -> Vec<Packet> {
+        self.state.lock().unwrap().packets.clone()
+    }
+
+    /// Number of sensor notifications emitted so far
+    pub fn notifications_sent(&self) -> usize {
+        self.state.lock().unwrap().notifications_sent
+    }
+
+    /// Set the percentage reported by the battery query
+    pub fn set_battery_percentage(&self, percentage: u8) {
+        self.state.lock().unwrap().battery_percentage = percentage;
+    }
+
+    /// Set the ambient light level reported, in lux
+    pub fn set_ambient_light(&self, lux: f32) {
+        self.state.lock().unwrap().ambient_light = lux;
+    }
+
+    /// Current simulated position
+    pub fn position(&self) -> Locator {
+        self.state.lock().unwrap().position
+    }
+
+    /// Move the robot to a position, as reported by later locator samples
+    pub fn set_position(&self, position: Locator) {
+        self.state.lock().unwrap().position = position;
+    }
+
+    /// Set the orientation `[W, X, Y, Z]` reported by later quaternion samples
+    pub fn set_orientation(&self, quaternion: [f32; 4]) {
+        self.state.lock().unwrap().orientation = quaternion;
+    }
+
+    /// Answer every later `(device_id, command_id)` command with `code`
+    ///
+    /// The command is not executed. Passing `error_code::SUCCESS` restores
+    /// normal handling.
+    pub fn set_response_code(&self, device_id: u8, command_id: u8, code: u8) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .response_codes
+            .retain(|&(command, _)| command != (device_id, command_id));
+        if code != error_code::SUCCESS {
+            state.response_codes.push(((device_id, command_id), code));
+        }
+    }
+
+    /// Simulate unplugging (`true`) or replugging (`false`) the serial adapter
+    ///
+    /// While disconnected, reads and writes fail with `BrokenPipe`.
+    pub fn set_disconnected(&self, disconnected: bool) {
+        self.state.lock().unwrap().disconnected = disconnected;
+    }
+
+    /// Set how often sensor notifications are emitted while awake
+    ///
+    /// `None` stops them.
+    pub fn set_notification_interval(&self, interval: Option<Duration>) {
+        self.state.lock().unwrap().notification_interval = interval;
+    }
+
+    /// Current period of the streaming notifications
+    pub fn notification_interval(&self) -> Option<Duration> {
+        self.state.lock().unwrap().notification_interval
+    }
+}
+
+impl RobotState {
+    /// Move the robot along its velocity up to `now`
+    fn advance(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.moved_at).as_secs_f32();
+        self.position.x += self.velocity.0 * elapsed;
+        self.position.y += self.velocity.1 * elapsed;
+        self.moved_at = now;
+    }
+}
+
+/// A simulated RVR behind a serial port interface
+pub struct VirtualRvr {
+    state: Arc<Mutex<RobotState>>,
+    parser: SpheroParser,
+    /// Framed bytes waiting to be read by the host
+    outbound: VecDeque<u8>,
+    next_notification: Instant,
+    timeout: Duration,
+}
+
+impl Default for VirtualRvr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualRvr {
+    /// Create an asleep robot with a full battery and all LEDs off
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RobotState {
+                awake: false,
+                battery_percentage: 100,
+                ambient_light: 300.0,
+                firmware: FirmwareVersion {
+                    major: 1,
+                    minor: 0,
+                    patch: 0,
+                },
+                leds: [Color::BLACK; LED_COUNT],
+                position: Locator { x: 0.0, y: 0.0 },
+                velocity: (0.0, 0.0),
+                moved_at: Instant::now(),
+                orientation: [1.0, 0.0, 0.0, 0.0],
+                streams: Vec::new(),
+                packets: Vec::new(),
+                response_codes: Vec::new(),
+                notification_interval: Some(Duration::from_millis(100)),
+                notifications_sent: 0,
+                disconnected: false,
+            })),
+            parser: SpheroParser::new(),
+            outbound: VecDeque::new(),
+            next_notification: Instant::now(),
+            timeout: Duration::from_millis(100),
+        }
+    }
+
+    /// Handle for inspecting the robot once the port has been handed off
+    pub fn handle(&self) -> VirtualRvrHandle {
+        VirtualRvrHandle {
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    /// Answer one command from the host
+    fn handle_command(&mut self, command: Packet) {
+        let (reply, notification) = {
+            let mut state = self.state.lock().unwrap();
+            state.advance(Instant::now());
+            state.packets.push(command.clone());
+            execute(&mut state, &command)
+        };
+
+        if command.flags.requests_response {
+            self.queue(&response_to(&command, reply));
+        }
+        if let Some(notification) = notification {
+            self.queue(&notification);
+        }
+    }
+
+    /// Emit a sample of every stream if one is due
+    fn poll_notifications(&mut self, now: Instant) {
+        let payloads = {
+            let mut state = self.state.lock().unwrap();
+            let interval = match state.notification_interval {
+                Some(interval) if state.awake => interval,
+                _ => return,
+            };
+            if now < self.next_notification {
+                return;
+            }
+            self.next_notification = now + interval;
+            state.advance(now);
+
+            let default_stream = [(SensorType::Locator.token(), vec![SensorType::Locator])];
+            let streams = if state.streams.is_empty() {
+                &default_stream[..]
+            } else {
+                &state.streams[..]
+            };
+            let payloads: Vec<Vec<u8>> = streams
+                .iter()
+                .map(|(token, sensors)| {
+                    let mut payload = vec![*token];
+                    for &sensor in sensors {
+                        payload.extend(encode_samples(sensor, &sample_values(&state, sensor)));
+                    }
+                    payload
+                })
+                .collect();
+            state.notifications_sent += payloads.len();
+            payloads
+        };
+
+        for payload in payloads {
+            let mut notification = Packet::new_command(
+                device::SENSOR,
+                sensor_command::STREAMING_SERVICE_DATA_NOTIFY,
+                0,
+                payload,
+            );
+            notification.flags.requests_response = false;
+            notification.flags.is_activity = true;
+            self.queue(&notification);
+        }
+    }
+
+    /// Fail like an unplugged serial port if disconnected
+    fn check_connected(&self) -> std::io::Result<()> {
+        if self.state.lock().unwrap().disconnected {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        Ok(())
+    }
+
+    /// Frame a packet onto the outbound buffer
+    fn queue(&mut self, packet: &Packet) {
+        self.outbound.extend(packet.to_framed_bytes());
+    }
+}
+
+/// Apply a command to the robot state
+///
+/// Returns the response payload (error code first) and an optional
+/// notification to send after the response.
+fn execute(state: &mut RobotState, command: &Packet) -> (Vec<u8>, Option<Packet>) {
+    let ok = error_code::SUCCESS;
+
+    let key = (command.device_id, command.command_id);
+    if let Some(&(_, code)) = state.response_codes.iter().find(|(k, _)| *k == key) {
+        return (vec![code], None);
+    }
+
+    match (command.device_id, command.command_id) {
+        (device::POWER, power_command::WAKE) => {
+            state.awake = true;
+            (vec![ok], None)
+        }
+        (device::POWER, power_command::SLEEP) => {
+            state.awake = false;
+            let mut notification =
+                Packet::new_command(device::POWER, power_command::DID_SLEEP_NOTIFY, 0, vec![]);
+            notification.flags.requests_response = false;
+            (vec![ok], Some(notification))
+        }
+        (device::API_AND_SHELL, api_shell_command::ECHO) => {
+            let mut reply = vec![ok];
+            reply.extend_from_slice(&command.payload);
+            (reply, None)
+        }
+        (device::POWER, power_command::GET_BATTERY_PERCENTAGE) => {
+            (vec![ok, state.battery_percentage], None)
+        }
+        // 1 = OK
+        (device::POWER, power_command::GET_BATTERY_VOLTAGE_STATE) => (vec![ok, 1], None),
+        (device::POWER, power_command::GET_PROTECTION_STATE) => (vec![ok, 0], None),
+        (device::DRIVE, drive_command::DRIVE_WITH_HEADING) => match command.payload[..] {
+            [speed, heading_hi, heading_lo, flags] => {
+                let heading = f32::from(u16::from_be_bytes([heading_hi, heading_lo])).to_radians();
+                let mut speed = f32::from(speed) / 255.0 * TOP_SPEED_M_S;
+                if flags & drive_flags::REVERSE != 0 {
+                    speed = -speed;
+                }
+                // Heading 0 is +Y, increasing clockwise toward +X
+                state.velocity = (speed * heading.sin(), speed * heading.cos());
+                (vec![ok], None)
+            }
+            _ => (vec![error_code::BAD_DATA_LENGTH], None),
+        },
+        (device::DRIVE, drive_command::STOP) => {
+            state.velocity = (0.0, 0.0);
+            (vec![ok], None)
+        }
+        (device::SENSOR, sensor_command::RESET_LOCATOR) => {
+            state.position = Locator { x: 0.0, y: 0.0 };
+            (vec![ok], None)
+        }
+        (device::IO, io_command::SET_ALL_LEDS) => match command.payload[..] {
+            [mask, r, g, b] => {
+                for (bit, led) in state.leds.iter_mut().enumerate() {
+                    if mask & (1 << bit) != 0 {
+                        *led = Color::new(r, g, b);
+                    }
+                }
+                (vec![ok], None)
+            }
+            _ => (vec![error_code::BAD_DATA_LENGTH], None),
+        },
+        (device::IO, io_command::GET_RGB_LED) => {
+            let color = command
+                .payload
+                .first()
+                .and_then(|&index| state.leds.get(index as usize))
+                .copied()
+                .unwrap_or(Color::BLACK);
+            (vec![ok, color.r, color.g, color.b], None)
+        }
+        (
+            device::SENSOR,
+            sensor_command::ENABLE_COLOR_DETECTION
+            | sensor_command::ENABLE_COLOR_DETECTION_NOTIFY
+            | sensor_command::ENABLE_IR_MESSAGE_NOTIFY
+            | sensor_command::SEND_IR_MESSAGE,
+        ) => (vec![ok], None),
+        (device::SENSOR, sensor_command::GET_AMBIENT_LIGHT_SENSOR_VALUE) => {
+            let mut reply = vec![ok];
+            reply.extend_from_slice(&state.ambient_light.to_be_bytes());
+            (reply, None)
+        }
+        (device::SENSOR, sensor_command::CLEAR_SENSOR_STREAMING) => {
+            state.streams.clear();
+            (vec![ok], None)
+        }
+        (device::SENSOR, sensor_command::SET_SENSOR_STREAMING) => {
+            match parse_stream_config(&command.payload) {
+                Some(stream) => {
+                    state.streams.push(stream);
+                    (vec![ok], None)
+                }
+                None => (vec![error_code::BAD_PARAMETER_VALUE], None),
+            }
+        }
+        (device::SENSOR, sensor_command::STOP_SENSOR_STREAMING) => {
+            state.notification_interval = None;
+            (vec![ok], None)
+        }
+        (
+            device::SENSOR,
+            sensor_command::START_SENSOR_STREAMING | sensor_command::SET_STREAMING_INTERVAL,
+        ) => match command.payload[..] {
+            [hi, lo] => {
+                let interval_ms = u16::from_be_bytes([hi, lo]);
+                state.notification_interval = Some(Duration::from_millis(interval_ms.into()));
+                (vec![ok], None)
+            }
+            _ => (vec![error_code::BAD_DATA_LENGTH], None),
+        },
+        (device::SYSTEM_INFO, system_info_command::GET_FIRMWARE_VERSION) => {
+            let mut reply = vec![ok];
+            for part in [
+                state.firmware.major,
+                state.firmware.minor,
+                state.firmware.patch,
+            ] {
+                reply.extend_from_slice(&part.to_be_bytes());
+            }
+            (reply, None)
+        }
+        _ => (vec![error_code::BAD_COMMAND_ID], None),
+    }
+}
+
+/// Decode a stream configuration: `[TOKEN]` then `[SERVICE_ID: u16] [SAMPLE_SIZE]` per sensor
+fn parse_stream_config(payload: &[u8]) -> Option<(u8, Vec<SensorType>)> {
+    let (&token, entries) = payload.split_first()?;
+    if entries.is_empty() || entries.len() % 3 != 0 {
+        return None;
+    }
+    let sensors = entries
+        .chunks_exact(3)
+        .map(|entry| {
+            let service_id = u16::from_be_bytes([entry[0], entry[1]]);
+            SensorType::ALL
+                .into_iter()
+                .find(|sensor| sensor.service_id() == service_id)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((token, sensors))
+}
+
+/// Values the robot currently reports for `sensor`
+fn sample_values(state: &RobotState, sensor: SensorType) -> Vec<f32> {
+    match sensor {
+        SensorType::Locator => vec![state.position.x, state.position.y],
+        SensorType::Quaternion => state.orientation.to_vec(),
+        SensorType::AmbientLight => vec![state.ambient_light],
+        _ => vec![0.0; sensor.sample_count()],
+    }
+}
+
+/// Build the response to `command` carrying `payload`
+///
+/// `payload` starts with the error code, e.g. `error_code::SUCCESS`.
+/// Shared with tests that play the robot over a `MockTransport`.
+pub fn response_to(command: &Packet, payload: Vec<u8>) -> Packet {
+    Packet {
+        flags: PacketFlags {
+            is_response: true,
+            requests_response: false,
+            requests_only_error_response: false,
+            is_activity: false,
+            has_target_id: command.flags.has_source_id,
+            has_source_id: command.flags.has_target_id,
+            reserved: 0,
+        },
+        target_id: command.source_id,
+        source_id: command.target_id,
+        device_id: command.device_id,
+        command_id: command.command_id,
+        sequence_number: command.sequence_number,
+        payload,
+    }
+}
+
+impl Read for VirtualRvr {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.check_connected()?;
+        self.poll_notifications(Instant::now());
+
+        if self.outbound.is_empty() {
+            // Behave like a serial port with a short read timeout
+            thread::sleep(IDLE_READ_DELAY);
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+
+        let count = buf.len().min(self.outbound.len());
+        for (slot, byte) in buf.iter_mut().zip(self.outbound.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for VirtualRvr {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.check_connected()?;
+        for &byte in buf {
+            match self.parser.feed(byte) {
+                Ok(Some(packet)) => self.handle_command(packet),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("VirtualRvr dropped a bad frame: {}", e),
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SerialPort for VirtualRvr {
+    fn name(&self) -> Option<String> {
+        Some("virtual-rvr".to_string())
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(115200)
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(FlowControl::None)
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(Parity::None)
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_baud_rate(&mut self, _baud_rate: u32) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, _data_bits: DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, _flow_control: FlowControl) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_parity(&mut self, _parity: Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, _stop_bits: StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, _level: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.outbound.len() as u32)
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+
+    fn clear(&self, _buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Err(serialport::Error::new(
+            serialport::ErrorKind::Unknown,
+            "VirtualRvr cannot be cloned",
+        ))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::SpheroRvr;
+
+    #[test]
+    fn test_hello_rvr_sequence() {
+        let robot = VirtualRvr::new();
+        let handle = robot.handle();
+        handle.set_battery_percentage(73);
+        handle.set_notification_interval(Some(Duration::from_millis(10)));
+        handle.set_position(Locator { x: 0.5, y: -0.25 });
+
+        let rvr = SpheroRvr::from_port(Box::new(robot));
+        let notifications = rvr.take_receiver().unwrap();
+
+        rvr.wake().unwrap();
+        assert!(handle.is_awake());
+        assert_eq!(rvr.get_battery_percentage().unwrap().percentage, 73);
+
+        for color in [Color::RED, Color::GREEN, Color::BLUE, Color::WHITE] {
+            rvr.set_all_leds(color).unwrap();
+            assert_eq!(handle.led_color(LedPosition::BatteryDoorRear), color);
+        }
+        rvr.set_all_leds(Color::BLACK).unwrap();
+
+        // Locator samples stream while awake
+        let notification = notifications
+            .recv_timeout(Duration::from_millis(500))
+            .unwrap();
+        assert_eq!(notification.device_id, device::SENSOR);
+        let sample = notifications
+            .recv_timeout(Duration::from_millis(500))
+            .unwrap();
+        let position = Locator::from_packet(&sample).unwrap();
+        assert!(
+            position.distance_to(handle.position()) < 1e-4,
+            "{:?}",
+            position
+        );
+
+        rvr.sleep().unwrap();
+        assert!(!handle.is_awake());
+        rvr.shutdown().unwrap();
+
+        let commands = handle.commands();
+        assert_eq!(
+            commands.first(),
+            Some(&(device::POWER, power_command::WAKE))
+        );
+        assert_eq!(
+            commands.last(),
+            Some(&(device::POWER, power_command::SLEEP))
+        );
+        assert_eq!(commands.len(), 8);
+        assert!(handle.notifications_sent() > 0);
+    }
+
+    #[test]
+    fn test_unknown_command_is_rejected() {
+        let mut robot = VirtualRvr::new();
+        let mut request = Packet::new_command(0x7F, 0x7F, 5, vec![]);
+        request.flags.requests_response = true;
+        robot.handle_command(request);
+
+        let bytes: Vec<u8> = robot.outbound.drain(..).collect();
+        let mut parser = SpheroParser::new();
+        let response = bytes.iter().find_map(|&b| parser.feed(b).unwrap()).unwrap();
+        assert!(response.flags.is_response);
+        assert_eq!(response.sequence_number, 5);
+        assert_eq!(response.payload, vec![error_code::BAD_COMMAND_ID]);
+    }
+}
@@ -0,0 +1,133 @@
+// Loopback tests for Dispatcher over MockTransport
+//
+// A fake robot thread reads framed requests off the mock, answers each
+// with a framed response carrying the same sequence number, and injects
+// it back. This exercises the full TX encode -> RX parse -> sequence
+// routing pipeline without hardware.
+
+use sphero_rvr::api::command;
+use sphero_rvr::api::constants::error_code;
+use sphero_rvr::protocol::packet::Packet;
+use sphero_rvr::protocol::parser::SpheroParser;
+use sphero_rvr::transport::virtual_rvr::response_to;
+use sphero_rvr::transport::{Dispatcher, MockTransport, MockTransportHandle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Answer requests once `batch` of them have arrived, last one first
+fn spawn_fake_robot(
+    handle: MockTransportHandle,
+    batch: usize,
+    stop: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut parser = SpheroParser::new();
+        let mut requests = Vec::new();
+
+        while !stop.load(Ordering::SeqCst) {
+            for byte in handle.take_written() {
+                if let Some(request) = parser.feed(byte).unwrap() {
+                    requests.push(request);
+                }
+            }
+            if requests.len() >= batch {
+                for request in requests.drain(..).rev() {
+                    handle.inject_packet(&response_to(&request, vec![error_code::SUCCESS]));
+                }
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    })
+}
+
+#[test]
+fn test_wake_round_trip() {
+    let transport = MockTransport::new();
+    let stop = Arc::new(AtomicBool::new(false));
+    let robot = spawn_fake_robot(transport.handle(), 1, Arc::clone(&stop));
+    let dispatcher = Dispatcher::from_transport(transport);
+
+    let response = dispatcher.send_command(command::wake()).unwrap();
+    assert!(response.flags.is_response);
+    assert_eq!(response.device_id, 0x13);
+    assert_eq!(response.command_id, 0x0D);
+    assert_eq!(response.payload, vec![0x00]);
+
+    // The next command gets the next sequence number and its own response
+    let second = dispatcher.send_command(command::wake()).unwrap();
+    assert_eq!(second.sequence_number, response.sequence_number + 1);
+
+    stop.store(true, Ordering::SeqCst);
+    robot.join().unwrap();
+    dispatcher.shutdown().unwrap();
+}
+
+#[test]
+fn test_out_of_order_responses_reach_their_callers() {
+    let transport = MockTransport::new();
+    let stop = Arc::new(AtomicBool::new(false));
+    let robot = spawn_fake_robot(transport.handle(), 2, Arc::clone(&stop));
+    let dispatcher = Arc::new(Dispatcher::from_transport(transport));
+
+    // Two requests in flight at once; the robot answers the second first
+    let callers: Vec<_> = [command::wake(), command::sleep()]
+        .into_iter()
+        .map(|packet| {
+            let dispatcher = Arc::clone(&dispatcher);
+            thread::spawn(move || {
+                let command_id = packet.command_id;
+                let response = dispatcher.send_command(packet).unwrap();
+                assert_eq!(response.command_id, command_id);
+            })
+        })
+        .collect();
+    for caller in callers {
+        caller.join().unwrap();
+    }
+
+    stop.store(true, Ordering::SeqCst);
+    robot.join().unwrap();
+    dispatcher.shutdown().unwrap();
+}
+
+#[test]
+fn test_notification_before_response_is_not_taken_as_reply() {
+    let transport = MockTransport::new();
+    let handle = transport.handle();
+    let dispatcher = Dispatcher::from_transport(transport);
+    let notifications = dispatcher.take_receiver().unwrap();
+
+    // A sensor sample that happens to carry the request's sequence number
+    // arrives just before the real response
+    let robot = thread::spawn(move || {
+        let mut parser = SpheroParser::new();
+        loop {
+            for byte in handle.take_written() {
+                if let Some(request) = parser.feed(byte).unwrap() {
+                    let mut sample = Packet::new_command(
+                        0x18,
+                        0x3D,
+                        request.sequence_number,
+                        vec![0x01, 0, 0, 0, 0],
+                    );
+                    sample.flags.requests_response = false;
+                    handle.inject_packet(&sample);
+                    handle.inject_packet(&response_to(&request, vec![error_code::SUCCESS]));
+                    return;
+                }
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+
+    let response = dispatcher.send_command(command::wake()).unwrap();
+    robot.join().unwrap();
+    assert!(response.flags.is_response);
+    assert_eq!(response.command_id, 0x0D);
+
+    let sample = notifications.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!((sample.device_id, sample.command_id), (0x18, 0x3D));
+    dispatcher.shutdown().unwrap();
+}